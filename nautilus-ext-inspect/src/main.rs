@@ -0,0 +1,162 @@
+#![deny(bare_trait_objects)]
+
+extern crate gio_sys as gio_ffi;
+extern crate glib_sys as glib_ffi;
+extern crate libc;
+extern crate nautilus_extension;
+extern crate nautilus_extension_test;
+
+use crate::gio_ffi::g_content_type_guess;
+use crate::glib_ffi::{g_filename_to_uri, g_free, gboolean, gpointer};
+use libc::c_char;
+use nautilus_extension_test::{Extension, MockColumn, MockFile, MockMenuItem};
+use std::env;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::ptr;
+
+mod output;
+
+const USAGE: &str =
+    "usage: nautilus-ext-inspect [--json] [--background DIR] EXTENSION.so [FILE...]";
+
+/// What one registered type provides.
+pub struct TypeReport {
+    pub name: String,
+    pub interfaces: Vec<String>,
+    pub columns: Option<Vec<MockColumn>>,
+    pub file_items: Option<Vec<MockMenuItem>>,
+    pub background_items: Option<Vec<MockMenuItem>>,
+}
+
+struct Options {
+    json: bool,
+    background: Option<PathBuf>,
+    extension: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut json = false;
+    let mut background = None;
+    let mut positional = Vec::new();
+
+    let mut args = env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--json") => json = true,
+            Some("--background") => match args.next() {
+                Some(dir) => background = Some(PathBuf::from(dir)),
+                None => return Err(String::from("--background needs a directory")),
+            },
+            Some("-h") | Some("--help") => return Err(String::new()),
+            Some(option) if option.starts_with("--") => {
+                return Err(format!("unknown option {}", option))
+            }
+            _ => positional.push(PathBuf::from(arg)),
+        }
+    }
+
+    if positional.is_empty() {
+        return Err(String::from("missing extension library"));
+    }
+    let extension = positional.remove(0);
+
+    Ok(Options {
+        json,
+        background,
+        extension,
+        files: positional,
+    })
+}
+
+fn mock_file(path: &Path) -> MockFile {
+    let absolute_path = path
+        .canonicalize()
+        .unwrap_or_else(|_| env::current_dir().unwrap().join(path));
+    let c_path = CString::new(absolute_path.as_os_str().as_bytes()).unwrap();
+
+    let (uri, mime_type) = unsafe {
+        let raw_uri = g_filename_to_uri(c_path.as_ptr(), ptr::null(), ptr::null_mut());
+        let mut uncertain: gboolean = 0;
+        let raw_mime_type = g_content_type_guess(c_path.as_ptr(), ptr::null(), 0, &mut uncertain);
+        (take_string(raw_uri), take_string(raw_mime_type))
+    };
+
+    let metadata = fs::metadata(&absolute_path).ok();
+    let is_directory = metadata.as_ref().map_or(false, |m| m.is_dir());
+    let can_write = metadata
+        .as_ref()
+        .map_or(true, |m| !m.permissions().readonly());
+
+    let file = MockFile::new(&uri)
+        .directory(is_directory)
+        .can_write(can_write);
+    if is_directory {
+        file.mime_type("inode/directory")
+    } else {
+        file.mime_type(&mime_type)
+    }
+}
+
+unsafe fn take_string(raw_string: *mut c_char) -> String {
+    if raw_string.is_null() {
+        return String::new();
+    }
+    let string = CStr::from_ptr(raw_string).to_string_lossy().into_owned();
+    g_free(raw_string as gpointer);
+    string
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("nautilus-ext-inspect: {}", message);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let extension = match Extension::load(&options.extension) {
+        Ok(extension) => extension,
+        Err(error) => {
+            eprintln!("nautilus-ext-inspect: {}", error);
+            process::exit(1);
+        }
+    };
+
+    let files: Vec<MockFile> = options.files.iter().map(|f| mock_file(f)).collect();
+    let current_folder = options.background.as_ref().map(|d| mock_file(d));
+
+    let reports: Vec<TypeReport> = extension
+        .providers()
+        .iter()
+        .map(|provider| TypeReport {
+            name: provider.type_name(),
+            interfaces: provider.interfaces(),
+            columns: provider.get_columns(),
+            file_items: if files.is_empty() {
+                None
+            } else {
+                provider.get_file_items(&files)
+            },
+            background_items: current_folder
+                .as_ref()
+                .and_then(|folder| provider.get_background_items(folder)),
+        })
+        .collect();
+
+    if options.json {
+        println!("{}", output::to_json(&reports));
+    } else {
+        print!("{}", output::to_text(&reports));
+    }
+
+    extension.shutdown();
+}
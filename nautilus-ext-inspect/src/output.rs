@@ -0,0 +1,182 @@
+use crate::TypeReport;
+use nautilus_extension::mock::{menu_to_json, menu_to_text};
+use nautilus_extension::render::json_string;
+use nautilus_extension_test::{MockColumn, MockMenuItem};
+use std::fmt::Write;
+
+pub fn to_text(reports: &[TypeReport]) -> String {
+    let mut text = String::new();
+
+    for report in reports {
+        writeln!(text, "{}", report.name).unwrap();
+        writeln!(text, "  interfaces: {}", report.interfaces.join(", ")).unwrap();
+
+        if let Some(ref columns) = report.columns {
+            writeln!(text, "  columns:").unwrap();
+            for column in columns {
+                writeln!(
+                    text,
+                    "    {} attribute={} label={} description={}",
+                    column.name,
+                    json_string(&column.attribute),
+                    json_string(&column.label),
+                    json_string(&column.description)
+                )
+                .unwrap();
+            }
+        }
+        if let Some(ref items) = report.file_items {
+            writeln!(text, "  file items:").unwrap();
+            write_text_items(&mut text, items);
+        }
+        if let Some(ref items) = report.background_items {
+            writeln!(text, "  background items:").unwrap();
+            write_text_items(&mut text, items);
+        }
+    }
+
+    text
+}
+
+fn write_text_items(text: &mut String, items: &[MockMenuItem]) {
+    for line in menu_to_text(items).lines() {
+        writeln!(text, "    {}", line).unwrap();
+    }
+}
+
+pub fn to_json(reports: &[TypeReport]) -> String {
+    let types: Vec<String> = reports
+        .iter()
+        .map(|report| {
+            let mut fields = vec![
+                format!("\"name\":{}", json_string(&report.name)),
+                format!(
+                    "\"interfaces\":[{}]",
+                    report
+                        .interfaces
+                        .iter()
+                        .map(|i| json_string(i))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ),
+            ];
+            if let Some(ref columns) = report.columns {
+                fields.push(format!("\"columns\":{}", json_columns(columns)));
+            }
+            if let Some(ref items) = report.file_items {
+                fields.push(format!("\"file_items\":{}", menu_to_json(items)));
+            }
+            if let Some(ref items) = report.background_items {
+                fields.push(format!("\"background_items\":{}", menu_to_json(items)));
+            }
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+
+    format!("{{\"types\":[{}]}}", types.join(","))
+}
+
+fn json_columns(columns: &[MockColumn]) -> String {
+    let columns: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            format!(
+                "{{\"name\":{},\"attribute\":{},\"label\":{},\"description\":{}}}",
+                json_string(&column.name),
+                json_string(&column.attribute),
+                json_string(&column.label),
+                json_string(&column.description)
+            )
+        })
+        .collect();
+    format!("[{}]", columns.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nautilus_extension_test::{cdylib_path, Extension, MockFile};
+
+    /// A report of the fixture extension's `FixtureExtension` type, plus one of a type with only
+    /// a column whose strings need escaping.
+    fn reports() -> Vec<TypeReport> {
+        let extension = Extension::load(cdylib_path("nautilus_extension_test_fixture")).unwrap();
+        let provider = &extension.providers()[0];
+        let files = [MockFile::new("file:///tmp/a.txt")];
+        let current_folder = MockFile::new("file:///tmp").directory(true);
+
+        vec![
+            TypeReport {
+                name: provider.type_name(),
+                interfaces: vec![
+                    String::from("NautilusMenuProvider"),
+                    String::from("NautilusColumnProvider"),
+                ],
+                columns: provider.get_columns(),
+                file_items: provider.get_file_items(&files),
+                background_items: provider.get_background_items(&current_folder),
+            },
+            TypeReport {
+                name: String::from("Quoted"),
+                interfaces: vec![String::from("NautilusColumnProvider")],
+                columns: Some(vec![MockColumn {
+                    name: String::from("Quoted::column"),
+                    attribute: String::from("quoted"),
+                    label: String::from("\"Quoted\""),
+                    description: String::from("Line\nbreak"),
+                }]),
+                file_items: None,
+                background_items: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn renders_text() {
+        assert_eq!(
+            to_text(&reports()),
+            "FixtureExtension\n\
+             \x20 interfaces: NautilusMenuProvider, NautilusColumnProvider\n\
+             \x20 columns:\n\
+             \x20   Fixture::name_column attribute=\"fixture_name\" label=\"Name\" description=\"Name seen by the fixture\"\n\
+             \x20 file items:\n\
+             \x20   Fixture::count label=\"1 files\" tip=\"Invalidate the files\" activate\n\
+             \x20 background items:\n\
+             \x20   Fixture::folder label=\"In file:///tmp\" tip=\"\"\n\
+             Quoted\n\
+             \x20 interfaces: NautilusColumnProvider\n\
+             \x20 columns:\n\
+             \x20   Quoted::column attribute=\"quoted\" label=\"\\\"Quoted\\\"\" description=\"Line\\nbreak\"\n"
+        );
+    }
+
+    #[test]
+    fn renders_json() {
+        assert_eq!(
+            to_json(&reports()),
+            concat!(
+                "{\"types\":[",
+                "{\"name\":\"FixtureExtension\",",
+                "\"interfaces\":[\"NautilusMenuProvider\",\"NautilusColumnProvider\"],",
+                "\"columns\":[{\"name\":\"Fixture::name_column\",\"attribute\":\"fixture_name\",",
+                "\"label\":\"Name\",\"description\":\"Name seen by the fixture\"}],",
+                "\"file_items\":[{\"name\":\"Fixture::count\",\"label\":\"1 files\",",
+                "\"tip\":\"Invalidate the files\",\"icon\":null,\"sensitive\":true,",
+                "\"priority\":true,\"has_activate_handler\":true,\"submenu\":[]}],",
+                "\"background_items\":[{\"name\":\"Fixture::folder\",\"label\":\"In file:///tmp\",",
+                "\"tip\":\"\",\"icon\":null,\"sensitive\":true,\"priority\":true,",
+                "\"has_activate_handler\":false,\"submenu\":[]}]},",
+                "{\"name\":\"Quoted\",\"interfaces\":[\"NautilusColumnProvider\"],",
+                "\"columns\":[{\"name\":\"Quoted::column\",\"attribute\":\"quoted\",",
+                "\"label\":\"\\\"Quoted\\\"\",\"description\":\"Line\\nbreak\"}]}",
+                "]}"
+            )
+        );
+    }
+
+    #[test]
+    fn renders_no_types() {
+        assert_eq!(to_text(&[]), "");
+        assert_eq!(to_json(&[]), "{\"types\":[]}");
+    }
+}
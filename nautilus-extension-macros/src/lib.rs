@@ -0,0 +1,475 @@
+#![deny(bare_trait_objects)]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::parse::Parser;
+use syn::{DeriveInput, Error, ItemFn, LitStr, Path};
+
+/// Generate the `nautilus_module_initialize`, `nautilus_module_list_types` and
+/// `nautilus_module_shutdown` entry points around a function that configures the extension.
+///
+/// The function takes the `*mut GTypeModule` handed to `nautilus_module_initialize` and returns
+/// anything implementing `ModuleTypes`, usually a `NautilusModule`, which gets registered.
+///
+/// ```ignore
+/// #[nautilus_extension]
+/// fn tmsu_extension(module: *mut GTypeModule) -> NautilusModule {
+///     let mut nautilus_module = NautilusModule::new(module, "TmsuExtension");
+///     nautilus_module.add_menu_provider(TmsuMenuProvider);
+///     nautilus_module
+/// }
+/// ```
+///
+/// If `nautilus-extension` is renamed in `Cargo.toml`, pass its path as
+/// `#[nautilus_extension(crate = "nautilus")]`. The other macros take the same argument.
+#[proc_macro_attribute]
+pub fn nautilus_extension(attr: TokenStream, item: TokenStream) -> TokenStream {
+    into_token_stream(nautilus_extension_impl(attr.into(), item.into()))
+}
+
+/// Turn `fn(files: Vec<FileInfo>)` into an `activate` handler for `MenuItem::set_activate_cb()`
+/// on items returned from `MenuProvider::get_file_items()`.
+#[proc_macro_attribute]
+pub fn menu_item_activate(attr: TokenStream, item: TokenStream) -> TokenStream {
+    into_token_stream(activate_handler(
+        attr.into(),
+        item.into(),
+        |krate| quote!(::std::vec::Vec<#krate::FileInfo>),
+    ))
+}
+
+/// Turn `fn(current_folder: FileInfo)` into an `activate` handler for `MenuItem::set_activate_cb()`
+/// on items returned from `MenuProvider::get_background_items()`.
+#[proc_macro_attribute]
+pub fn menu_background_activate(attr: TokenStream, item: TokenStream) -> TokenStream {
+    into_token_stream(activate_handler(
+        attr.into(),
+        item.into(),
+        |krate| quote!(#krate::FileInfo),
+    ))
+}
+
+/// Implement `ColumnProvider` from `#[column(...)]` attributes on the type.
+///
+/// ```ignore
+/// #[derive(ColumnProvider)]
+/// #[column(name = "TmsuTags::tags_column", attribute = "tmsu_tags", label = "Tags",
+///          description = "TMSU tags")]
+/// struct TmsuColumnProvider;
+/// ```
+///
+/// `description` is optional. Each `#[column]` attribute adds one column, in order. A renamed
+/// `nautilus-extension` is given with `#[column_provider(crate = "nautilus")]`.
+#[proc_macro_derive(ColumnProvider, attributes(column, column_provider))]
+pub fn derive_column_provider(input: TokenStream) -> TokenStream {
+    into_token_stream(syn::parse(input).and_then(|input| column_provider_impl(&input)))
+}
+
+fn into_token_stream(result: Result<TokenStream2, Error>) -> TokenStream {
+    result.unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// The path of the `nautilus-extension` crate: `::nautilus_extension`, or the path given as
+/// `crate = "..."` in `args`.
+fn crate_path(args: TokenStream2, macro_name: &str) -> Result<TokenStream2, Error> {
+    let mut krate = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("crate") {
+            let path: LitStr = meta.value()?.parse()?;
+            krate = Some(path.parse::<Path>()?);
+            Ok(())
+        } else {
+            Err(meta.error(format!("{} only takes `crate = \"...\"`", macro_name)))
+        }
+    });
+    parser.parse2(args)?;
+
+    Ok(match krate {
+        Some(krate) => quote!(#krate),
+        None => quote!(::nautilus_extension),
+    })
+}
+
+fn nautilus_extension_impl(attr: TokenStream2, item: TokenStream2) -> Result<TokenStream2, Error> {
+    let krate = crate_path(attr, "#[nautilus_extension]")?;
+
+    let function: ItemFn = syn::parse2(item)?;
+    if function.sig.inputs.len() != 1 {
+        return Err(Error::new_spanned(
+            &function.sig,
+            "#[nautilus_extension] function must take the `*mut GTypeModule` as its only argument",
+        ));
+    }
+
+    let name = &function.sig.ident;
+
+    Ok(quote! {
+        #function
+
+        #[no_mangle]
+        pub extern "C" fn nautilus_module_initialize(
+            module: *mut #krate::__private::GTypeModule,
+        ) {
+            #krate::__private::add_module_types(#name(module));
+        }
+
+        /// # Safety
+        ///
+        /// Called by Nautilus after `nautilus_module_initialize()`. Do not call directly.
+        #[no_mangle]
+        pub unsafe extern "C" fn nautilus_module_list_types(
+            types: *mut *const #krate::__private::GType,
+            num_types: *mut #krate::__private::c_int,
+        ) {
+            #krate::__private::list_module_types(types, num_types);
+        }
+
+        #[no_mangle]
+        pub extern "C" fn nautilus_module_shutdown() {
+            #krate::NautilusModule::shutdown();
+        }
+    })
+}
+
+fn activate_handler(
+    attr: TokenStream2,
+    item: TokenStream2,
+    user_data_type: fn(&TokenStream2) -> TokenStream2,
+) -> Result<TokenStream2, Error> {
+    let krate = crate_path(attr, "activate handlers")?;
+
+    let function: ItemFn = syn::parse2(item)?;
+    if function.sig.inputs.len() != 1 {
+        return Err(Error::new_spanned(
+            &function.sig,
+            "activate handlers take the activated files as their only argument",
+        ));
+    }
+
+    let user_data_type = user_data_type(&krate);
+    let attrs = &function.attrs;
+    let vis = &function.vis;
+    let name = &function.sig.ident;
+    let safe_name = format_ident!("{}_safe", name);
+
+    let mut safe_function = function.clone();
+    safe_function.attrs.clear();
+    safe_function.vis = syn::Visibility::Inherited;
+    safe_function.sig.ident = safe_name.clone();
+
+    Ok(quote! {
+        #(#attrs)*
+        ///
+        /// # Safety
+        ///
+        /// This generated function is used as a Nautilus callback. Do not call directly.
+        #vis unsafe extern "C" fn #name(
+            _nautilusmenuitem: *mut #krate::__private::GObject,
+            user_data: #krate::__private::gpointer,
+        ) {
+            #safe_function
+
+            let user_data: ::std::boxed::Box<#user_data_type> =
+                ::std::boxed::Box::from_raw(user_data as *mut #user_data_type);
+            #safe_name(*user_data);
+        }
+    })
+}
+
+fn column_provider_impl(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let mut krate = quote!(::nautilus_extension);
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("column_provider"))
+    {
+        let args = attr.meta.require_list()?.tokens.clone();
+        krate = crate_path(args, "#[column_provider]")?;
+    }
+
+    let mut columns = Vec::new();
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("column")) {
+        let mut name: Option<LitStr> = None;
+        let mut attribute: Option<LitStr> = None;
+        let mut label: Option<LitStr> = None;
+        let mut description: Option<LitStr> = None;
+
+        attr.parse_nested_meta(|meta| {
+            let slot = if meta.path.is_ident("name") {
+                &mut name
+            } else if meta.path.is_ident("attribute") {
+                &mut attribute
+            } else if meta.path.is_ident("label") {
+                &mut label
+            } else if meta.path.is_ident("description") {
+                &mut description
+            } else {
+                return Err(meta.error("expected `name`, `attribute`, `label` or `description`"));
+            };
+            *slot = Some(meta.value()?.parse()?);
+            Ok(())
+        })?;
+
+        let missing =
+            |field: &str| Error::new_spanned(attr, format!("#[column] is missing `{}`", field));
+        let name = name.ok_or_else(|| missing("name"))?;
+        let attribute = attribute.ok_or_else(|| missing("attribute"))?;
+        let label = label.ok_or_else(|| missing("label"))?;
+        let description = description.unwrap_or_else(|| LitStr::new("", Span::call_site()));
+
+        columns.push(quote! {
+            #krate::Column::new(#name, #attribute, #label, #description)
+        });
+    }
+
+    if columns.is_empty() {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "#[derive(ColumnProvider)] needs at least one #[column(...)] attribute",
+        ));
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #krate::ColumnProvider for #ident #ty_generics #where_clause {
+            fn get_columns(&self) -> ::std::vec::Vec<#krate::Column> {
+                ::std::vec![#(#columns),*]
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_message(result: Result<TokenStream2, Error>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn expands_entry_points() {
+        let expanded = nautilus_extension_impl(
+            quote!(),
+            quote! {
+                fn extension(module: *mut GTypeModule) -> NautilusModule {
+                    NautilusModule::new(module, "Extension")
+                }
+            },
+        )
+        .unwrap()
+        .to_string();
+
+        assert!(expanded.contains("fn extension"));
+        assert!(expanded.contains(
+            &quote!(::nautilus_extension::__private::add_module_types(
+                extension(module)
+            ))
+            .to_string()
+        ));
+        assert!(expanded.contains("pub unsafe extern \"C\" fn nautilus_module_list_types"));
+        assert!(expanded
+            .contains(&quote!(::nautilus_extension::NautilusModule::shutdown()).to_string()));
+    }
+
+    #[test]
+    fn rejects_bad_entry_points() {
+        assert_eq!(
+            error_message(nautilus_extension_impl(
+                quote!(),
+                quote!(
+                    fn extension() -> NautilusModule {}
+                )
+            )),
+            "#[nautilus_extension] function must take the `*mut GTypeModule` as its only argument"
+        );
+        assert_eq!(
+            error_message(nautilus_extension_impl(
+                quote!(name = "Extension"),
+                quote!(
+                    fn extension(module: *mut GTypeModule) {}
+                )
+            )),
+            "#[nautilus_extension] only takes `crate = \"...\"`"
+        );
+        assert_eq!(
+            error_message(nautilus_extension_impl(
+                quote!(),
+                quote!(
+                    struct Extension;
+                )
+            )),
+            "expected `fn`"
+        );
+    }
+
+    #[test]
+    fn expands_activate_handlers() {
+        let expanded = activate_handler(
+            quote!(),
+            quote! {
+                /// Opens the files.
+                pub fn open(files: Vec<FileInfo>) {}
+            },
+            |krate| quote!(::std::vec::Vec<#krate::FileInfo>),
+        )
+        .unwrap()
+        .to_string();
+
+        // the documentation stays on the handler, and the function moves inside it
+        let handler = expanded.find("pub unsafe extern \"C\" fn open").unwrap();
+        assert!(expanded[..handler].contains("Opens the files."));
+        assert!(expanded.contains("fn open_safe (files : Vec < FileInfo >) { }"));
+        assert!(expanded.contains(
+            &quote!(::std::boxed::Box<::std::vec::Vec<::nautilus_extension::FileInfo).to_string()
+        ));
+        assert!(expanded.contains("open_safe (* user_data)"));
+    }
+
+    #[test]
+    fn rejects_bad_activate_handlers() {
+        assert_eq!(
+            error_message(activate_handler(
+                quote!(),
+                quote!(
+                    fn open(files: Vec<FileInfo>, extra: u32) {}
+                ),
+                |krate| quote!(#krate::FileInfo),
+            )),
+            "activate handlers take the activated files as their only argument"
+        );
+        assert_eq!(
+            error_message(activate_handler(
+                quote!(crate),
+                quote!(
+                    fn open(folder: FileInfo) {}
+                ),
+                |krate| quote!(#krate::FileInfo),
+            )),
+            "expected `=`"
+        );
+    }
+
+    #[test]
+    fn expands_column_provider() {
+        let input: DeriveInput = syn::parse2(quote! {
+            #[column(name = "Tags::tags", attribute = "tags", label = "Tags")]
+            #[column(name = "Tags::count", attribute = "count", label = "Count",
+                     description = "Number of tags")]
+            struct TagColumns<T>(T);
+        })
+        .unwrap();
+
+        let expanded = column_provider_impl(&input).unwrap().to_string();
+
+        assert!(expanded.contains(
+            &quote!(impl<T> ::nautilus_extension::ColumnProvider for TagColumns<T>).to_string()
+        ));
+        assert!(expanded.contains(
+            &quote!(::nautilus_extension::Column::new(
+                "Tags::tags",
+                "tags",
+                "Tags",
+                ""
+            ))
+            .to_string()
+        ));
+        assert!(expanded.contains(
+            &quote!(::nautilus_extension::Column::new(
+                "Tags::count",
+                "count",
+                "Count",
+                "Number of tags"
+            ))
+            .to_string()
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_columns() {
+        let cases = [
+            (
+                quote!(
+                    struct Columns;
+                ),
+                "#[derive(ColumnProvider)] needs at least one #[column(...)] attribute",
+            ),
+            (
+                quote!(
+                    #[column(name = "Tags::tags", label = "Tags")]
+                    struct Columns;
+                ),
+                "#[column] is missing `attribute`",
+            ),
+            (
+                quote!(
+                    #[column(name = "Tags::tags", attribute = "tags", label = "Tags", width = "2")]
+                    struct Columns;
+                ),
+                "expected `name`, `attribute`, `label` or `description`",
+            ),
+            (
+                quote!(
+                    #[column(name = Tags, attribute = "tags", label = "Tags")]
+                    struct Columns;
+                ),
+                "expected string literal",
+            ),
+            (
+                quote!(
+                    #[column_provider(krate = "nautilus")]
+                    #[column(name = "Tags::tags", attribute = "tags", label = "Tags")]
+                    struct Columns;
+                ),
+                "#[column_provider] only takes `crate = \"...\"`",
+            ),
+        ];
+
+        for (input, message) in cases.iter() {
+            let input: DeriveInput = syn::parse2(input.clone()).unwrap();
+            assert_eq!(error_message(column_provider_impl(&input)), *message);
+        }
+    }
+
+    #[test]
+    fn uses_renamed_crate() {
+        let entry_points = nautilus_extension_impl(
+            quote!(crate = "::nautilus"),
+            quote!(
+                fn extension(module: *mut GTypeModule) -> NautilusModule {}
+            ),
+        )
+        .unwrap()
+        .to_string();
+        assert!(entry_points.contains(&quote!(::nautilus::NautilusModule::shutdown()).to_string()));
+        assert!(!entry_points.contains("nautilus_extension ::"));
+
+        let handler = activate_handler(
+            quote!(crate = "nautilus"),
+            quote!(
+                fn open(folder: FileInfo) {}
+            ),
+            |krate| quote!(#krate::FileInfo),
+        )
+        .unwrap()
+        .to_string();
+        assert!(handler.contains(&quote!(*mut nautilus::__private::GObject).to_string()));
+        assert!(!handler.contains("nautilus_extension ::"));
+
+        let input: DeriveInput = syn::parse2(quote! {
+            #[column_provider(crate = "nautilus")]
+            #[column(name = "Tags::tags", attribute = "tags", label = "Tags")]
+            struct Columns;
+        })
+        .unwrap();
+        let columns = column_provider_impl(&input).unwrap().to_string();
+        assert!(columns.contains(&quote!(impl nautilus::ColumnProvider for Columns).to_string()));
+        assert!(!columns.contains("nautilus_extension ::"));
+    }
+}
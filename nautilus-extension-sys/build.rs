@@ -0,0 +1,113 @@
+//! Find libnautilus-extension with pkg-config and expose its version as cfg flags.
+//!
+//! Each entry of `API_VERSIONS` at or below the detected version is emitted as a cfg flag, e.g.
+//! `nautilus_43` for Nautilus 43 or later, and the bindings that changed in that version are
+//! gated on it. The flags are also passed to dependent build scripts as
+//! `DEP_NAUTILUS_EXTENSION_API_FLAGS`, and the version as `DEP_NAUTILUS_EXTENSION_VERSION`.
+
+use std::env;
+use std::process;
+
+/// Versions that changed the extension API, and the cfg flag each one sets. Nothing in these
+/// crates is gated on `nautilus_3_30` yet, but dependents can require the 3.30 API with it.
+/// Nautilus 43 moved to GTK 4: it dropped the window arguments and property pages, and added
+/// properties models.
+const API_VERSIONS: [((u32, u32), &str); 2] =
+    [((3, 30), "nautilus_3_30"), ((43, 0), "nautilus_43")];
+
+/// Versions assumed by the mock unless `NAUTILUS_EXTENSION_SYS_VERSION` is set. That variable
+/// also skips pkg-config for real builds, e.g. when cross-compiling.
+const MOCK_VERSION_3: &str = "42.0";
+const MOCK_VERSION_4: &str = "43.0";
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=NAUTILUS_EXTENSION_SYS_VERSION");
+    for (_, flag) in API_VERSIONS.iter() {
+        println!("cargo:rustc-check-cfg=cfg({})", flag);
+    }
+
+    let nautilus4 = env::var_os("CARGO_FEATURE_NAUTILUS4").is_some();
+    let (package, other_package) = if nautilus4 {
+        ("libnautilus-extension-4", "libnautilus-extension")
+    } else {
+        ("libnautilus-extension", "libnautilus-extension-4")
+    };
+
+    let version_override = env::var("NAUTILUS_EXTENSION_SYS_VERSION").ok();
+
+    let version = if env::var_os("CARGO_FEATURE_MOCK").is_some() || env::var_os("DOCS_RS").is_some()
+    {
+        // nothing to link: the mock implements the library itself
+        version_override.unwrap_or_else(|| {
+            String::from(if nautilus4 {
+                MOCK_VERSION_4
+            } else {
+                MOCK_VERSION_3
+            })
+        })
+    } else if let Some(version) = version_override {
+        println!("cargo:rustc-link-lib=nautilus-extension");
+        version
+    } else {
+        match pkg_config::Config::new().probe(package) {
+            Ok(library) => library.version,
+            Err(error) => {
+                eprintln!("error: {} was not found by pkg-config.", package);
+                let other_library = pkg_config::Config::new()
+                    .cargo_metadata(false)
+                    .probe(other_package);
+                if other_library.is_ok() {
+                    eprintln!(
+                        "Only {} is installed. Build with the `{}` feature of nautilus-extension instead.",
+                        other_package,
+                        if nautilus4 { "nautilus3" } else { "nautilus4" }
+                    );
+                } else {
+                    eprintln!(
+                        "Install the Nautilus extension development files (libnautilus-extension-dev on Debian and Ubuntu, nautilus-devel on Fedora), or set PKG_CONFIG_PATH to the directory containing {}.pc.",
+                        package
+                    );
+                }
+                eprintln!("\n{}", error);
+                process::exit(1);
+            }
+        }
+    };
+
+    let parsed_version = parse_version(&version).unwrap_or_else(|| {
+        eprintln!(
+            "error: cannot parse libnautilus-extension version {:?}",
+            version
+        );
+        process::exit(1);
+    });
+
+    // the feature selects the ABI, so it must agree with the library found
+    if nautilus4 != (parsed_version >= (43, 0)) {
+        eprintln!(
+            "error: libnautilus-extension {} does not match the `{}` feature",
+            version,
+            if nautilus4 { "nautilus4" } else { "nautilus3" }
+        );
+        process::exit(1);
+    }
+
+    let flags: Vec<&str> = API_VERSIONS
+        .iter()
+        .filter(|&&(api_version, _)| parsed_version >= api_version)
+        .map(|&(_, flag)| flag)
+        .collect();
+    for flag in &flags {
+        println!("cargo:rustc-cfg={}", flag);
+    }
+    println!("cargo:version={}", version);
+    println!("cargo:api_flags={}", flags.join(","));
+}
+
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    // pre-release versions like "43.alpha" count as the start of the major version
+    let minor = parts.next().map_or(0, |minor| minor.parse().unwrap_or(0));
+    Some((major, minor))
+}
@@ -3,45 +3,61 @@
 extern crate gio_sys as gio_ffi;
 extern crate glib_sys as glib_ffi;
 extern crate gobject_sys as gobject_ffi;
+#[cfg(not(nautilus_43))]
 extern crate gtk_sys as gtk_ffi;
 extern crate libc;
 
+#[cfg(all(feature = "nautilus3", feature = "nautilus4"))]
+compile_error!("features `nautilus3` and `nautilus4` cannot be enabled together");
+#[cfg(not(any(feature = "nautilus3", feature = "nautilus4")))]
+compile_error!("enable one of the features `nautilus3` or `nautilus4`");
+
+#[cfg(nautilus_43)]
+use crate::gio_ffi::GListModel;
 use crate::gio_ffi::{GFile, GFileType, GMount};
 use crate::glib_ffi::{gboolean, gpointer, GList, GType};
 use crate::gobject_ffi::{GClosure, GTypeInterface};
+#[cfg(not(nautilus_43))]
 use crate::gtk_ffi::GtkWidget;
 use libc::c_char;
 
+#[derive(Clone, Copy)]
 #[repr(C)]
-pub struct NautilusFileInfo {
-    g_iface: GTypeInterface,
-    is_gone: Option<extern "C" fn(*mut NautilusFileInfo) -> gboolean>,
-    get_name: Option<extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
-    get_uri: Option<extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
-    get_parent_uri: Option<extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
-    get_uri_scheme: Option<extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
-    get_mime_type: Option<extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
-    is_mime_type: Option<extern "C" fn(*mut NautilusFileInfo) -> gboolean>,
-    is_directory: Option<extern "C" fn(*mut NautilusFileInfo) -> gboolean>,
-    add_emblem: Option<extern "C" fn(*mut NautilusFileInfo)>,
-    get_string_attribute: Option<extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
-    add_string_attribute: Option<extern "C" fn(*mut NautilusFileInfo)>,
-    invalidate_extension_info: Option<extern "C" fn(*mut NautilusFileInfo)>,
-    get_activation_uri: Option<extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
-    get_file_type: Option<extern "C" fn(*mut NautilusFileInfo) -> GFileType>,
-    get_location: Option<extern "C" fn(*mut NautilusFileInfo) -> *mut GFile>,
-    get_parent_location: Option<extern "C" fn(*mut NautilusFileInfo) -> *mut GFile>,
-    get_parent_info: Option<extern "C" fn(*mut NautilusFileInfo) -> *mut NautilusFileInfo>,
-    get_mount: Option<extern "C" fn(*mut NautilusFileInfo) -> *mut GMount>,
-    can_write: Option<extern "C" fn(*mut NautilusFileInfo) -> gboolean>,
+pub struct NautilusFileInfoIface {
+    pub g_iface: GTypeInterface,
+    pub is_gone: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> gboolean>,
+    pub get_name: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
+    pub get_uri: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
+    pub get_parent_uri: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
+    pub get_uri_scheme: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
+    pub get_mime_type: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
+    pub is_mime_type:
+        Option<unsafe extern "C" fn(*mut NautilusFileInfo, *const c_char) -> gboolean>,
+    pub is_directory: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> gboolean>,
+    pub add_emblem: Option<unsafe extern "C" fn(*mut NautilusFileInfo, *const c_char)>,
+    pub get_string_attribute:
+        Option<unsafe extern "C" fn(*mut NautilusFileInfo, *const c_char) -> *mut c_char>,
+    pub add_string_attribute:
+        Option<unsafe extern "C" fn(*mut NautilusFileInfo, *const c_char, *const c_char)>,
+    pub invalidate_extension_info: Option<unsafe extern "C" fn(*mut NautilusFileInfo)>,
+    pub get_activation_uri: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> *mut c_char>,
+    pub get_file_type: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> GFileType>,
+    pub get_location: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> *mut GFile>,
+    pub get_parent_location: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> *mut GFile>,
+    pub get_parent_info:
+        Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> *mut NautilusFileInfo>,
+    pub get_mount: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> *mut GMount>,
+    pub can_write: Option<unsafe extern "C" fn(*mut NautilusFileInfo) -> gboolean>,
 }
 
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub struct NautilusColumnProviderIface {
     g_iface: GTypeInterface,
     pub get_columns: Option<extern "C" fn(gpointer) -> *mut GList>,
 }
 
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub struct NautilusInfoProviderIface {
     g_iface: GTypeInterface,
@@ -57,10 +73,12 @@ pub struct NautilusInfoProviderIface {
         Option<unsafe extern "C" fn(*mut NautilusInfoProvider, *mut NautilusOperationHandle)>,
 }
 
+#[cfg(not(nautilus_43))]
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub struct NautilusLocationWidgetProviderIface {
     g_iface: GTypeInterface,
-    get_widget: Option<
+    pub get_widget: Option<
         extern "C" fn(
             *mut NautilusLocationWidgetProvider,
             *const c_char,
@@ -69,6 +87,8 @@ pub struct NautilusLocationWidgetProviderIface {
     >,
 }
 
+#[cfg(not(nautilus_43))]
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub struct NautilusMenuProviderIface {
     g_iface: GTypeInterface,
@@ -77,6 +97,18 @@ pub struct NautilusMenuProviderIface {
         Option<extern "C" fn(gpointer, *mut GtkWidget, *mut NautilusFileInfo) -> *mut GList>,
 }
 
+/// In Nautilus 4 the menu callbacks no longer receive the window.
+#[cfg(nautilus_43)]
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct NautilusMenuProviderIface {
+    g_iface: GTypeInterface,
+    pub get_file_items: Option<extern "C" fn(gpointer, *mut GList) -> *mut GList>,
+    pub get_background_items: Option<extern "C" fn(gpointer, *mut NautilusFileInfo) -> *mut GList>,
+}
+
+#[cfg(not(nautilus_43))]
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub struct NautilusPropertyPageProviderIface {
     g_iface: GTypeInterface,
@@ -84,33 +116,90 @@ pub struct NautilusPropertyPageProviderIface {
         Option<extern "C" fn(*mut NautilusPropertyPageProvider, *mut GList) -> *mut GList>,
 }
 
+#[cfg(nautilus_43)]
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct NautilusPropertiesModelProviderIface {
+    g_iface: GTypeInterface,
+    pub get_models:
+        Option<extern "C" fn(*mut NautilusPropertiesModelProvider, *mut GList) -> *mut GList>,
+}
+
 pub enum NautilusColumn {}
+pub enum NautilusColumnProvider {}
+pub enum NautilusFileInfo {}
 pub enum NautilusInfoProvider {}
+#[cfg(not(nautilus_43))]
 pub enum NautilusLocationWidgetProvider {}
 pub enum NautilusMenu {}
 pub enum NautilusMenuItem {}
+pub enum NautilusMenuProvider {}
 pub enum NautilusOperationHandle {}
+#[cfg(nautilus_43)]
+pub enum NautilusPropertiesItem {}
+#[cfg(nautilus_43)]
+pub enum NautilusPropertiesModel {}
+#[cfg(nautilus_43)]
+pub enum NautilusPropertiesModelProvider {}
+#[cfg(not(nautilus_43))]
 pub enum NautilusPropertyPage {}
+#[cfg(not(nautilus_43))]
 pub enum NautilusPropertyPageProvider {}
 
-#[link(name = "nautilus-extension")]
+// linked by build.rs
 extern "C" {
+    pub fn nautilus_column_get_type() -> GType;
     pub fn nautilus_column_new(
         name: *const c_char,
         attribute: *const c_char,
         label: *const c_char,
         description: *const c_char,
     ) -> *mut NautilusColumn;
+    pub fn nautilus_column_provider_get_columns(
+        provider: *mut NautilusColumnProvider,
+    ) -> *mut GList;
     pub fn nautilus_column_provider_get_type() -> GType;
+    pub fn nautilus_file_info_add_emblem(file: *mut NautilusFileInfo, emblem_name: *const c_char);
     pub fn nautilus_file_info_add_string_attribute(
         file: *mut NautilusFileInfo,
         attribute_name: *const c_char,
         value: *const c_char,
     );
+    pub fn nautilus_file_info_can_write(file: *mut NautilusFileInfo) -> gboolean;
+    pub fn nautilus_file_info_create(location: *mut GFile) -> *mut NautilusFileInfo;
+    pub fn nautilus_file_info_create_for_uri(uri: *const c_char) -> *mut NautilusFileInfo;
+    pub fn nautilus_file_info_get_activation_uri(file: *mut NautilusFileInfo) -> *mut c_char;
+    pub fn nautilus_file_info_get_file_type(file: *mut NautilusFileInfo) -> GFileType;
+    pub fn nautilus_file_info_get_location(file: *mut NautilusFileInfo) -> *mut GFile;
+    pub fn nautilus_file_info_get_mime_type(file: *mut NautilusFileInfo) -> *mut c_char;
+    pub fn nautilus_file_info_get_mount(file: *mut NautilusFileInfo) -> *mut GMount;
+    pub fn nautilus_file_info_get_name(file: *mut NautilusFileInfo) -> *mut c_char;
+    pub fn nautilus_file_info_get_parent_info(file: *mut NautilusFileInfo)
+        -> *mut NautilusFileInfo;
+    pub fn nautilus_file_info_get_parent_location(file: *mut NautilusFileInfo) -> *mut GFile;
+    pub fn nautilus_file_info_get_parent_uri(file: *mut NautilusFileInfo) -> *mut c_char;
+    pub fn nautilus_file_info_get_string_attribute(
+        file: *mut NautilusFileInfo,
+        attribute_name: *const c_char,
+    ) -> *mut c_char;
+    pub fn nautilus_file_info_get_type() -> GType;
     pub fn nautilus_file_info_get_uri(file_info: *mut NautilusFileInfo) -> *mut c_char;
     pub fn nautilus_file_info_get_uri_scheme(file_info: *mut NautilusFileInfo) -> *mut c_char;
     pub fn nautilus_file_info_invalidate_extension_info(file: *mut NautilusFileInfo);
+    pub fn nautilus_file_info_is_directory(file: *mut NautilusFileInfo) -> gboolean;
+    pub fn nautilus_file_info_is_gone(file: *mut NautilusFileInfo) -> gboolean;
+    pub fn nautilus_file_info_is_mime_type(
+        file: *mut NautilusFileInfo,
+        mime_type: *const c_char,
+    ) -> gboolean;
     pub fn nautilus_file_info_list_copy(files: *mut GList) -> *mut GList;
+    pub fn nautilus_file_info_list_free(files: *mut GList);
+    pub fn nautilus_file_info_lookup(location: *mut GFile) -> *mut NautilusFileInfo;
+    pub fn nautilus_file_info_lookup_for_uri(uri: *const c_char) -> *mut NautilusFileInfo;
+    pub fn nautilus_info_provider_cancel_update(
+        provider: *mut NautilusInfoProvider,
+        handle: *mut NautilusOperationHandle,
+    );
     pub fn nautilus_info_provider_get_type() -> GType;
     pub fn nautilus_info_provider_update_complete_invoke(
         update_complete: *mut GClosure,
@@ -118,7 +207,26 @@ extern "C" {
         handle: *mut NautilusOperationHandle,
         result: NautilusOperationResult,
     );
+    pub fn nautilus_info_provider_update_file_info(
+        provider: *mut NautilusInfoProvider,
+        file: *mut NautilusFileInfo,
+        update_complete: *mut GClosure,
+        handle: *mut *mut NautilusOperationHandle,
+    ) -> NautilusOperationResult;
+    #[cfg(not(nautilus_43))]
+    pub fn nautilus_location_widget_provider_get_type() -> GType;
+    #[cfg(not(nautilus_43))]
+    pub fn nautilus_location_widget_provider_get_widget(
+        provider: *mut NautilusLocationWidgetProvider,
+        uri: *const c_char,
+        window: *mut GtkWidget,
+    ) -> *mut GtkWidget;
     pub fn nautilus_menu_append_item(menu: *mut NautilusMenu, item: *mut NautilusMenuItem);
+    pub fn nautilus_menu_get_items(menu: *mut NautilusMenu) -> *mut GList;
+    pub fn nautilus_menu_get_type() -> GType;
+    pub fn nautilus_menu_item_activate(item: *mut NautilusMenuItem);
+    pub fn nautilus_menu_item_get_type() -> GType;
+    pub fn nautilus_menu_item_list_free(item_list: *mut GList);
     pub fn nautilus_menu_item_new(
         name: *const c_char,
         label: *const c_char,
@@ -127,16 +235,91 @@ extern "C" {
     ) -> *mut NautilusMenuItem;
     pub fn nautilus_menu_item_set_submenu(item: *mut NautilusMenuItem, menu: *mut NautilusMenu);
     pub fn nautilus_menu_new() -> *mut NautilusMenu;
+    pub fn nautilus_menu_provider_emit_items_updated_signal(provider: *mut NautilusMenuProvider);
+    #[cfg(not(nautilus_43))]
+    pub fn nautilus_menu_provider_get_background_items(
+        provider: *mut NautilusMenuProvider,
+        window: *mut GtkWidget,
+        current_folder: *mut NautilusFileInfo,
+    ) -> *mut GList;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_menu_provider_get_background_items(
+        provider: *mut NautilusMenuProvider,
+        current_folder: *mut NautilusFileInfo,
+    ) -> *mut GList;
+    #[cfg(not(nautilus_43))]
+    pub fn nautilus_menu_provider_get_file_items(
+        provider: *mut NautilusMenuProvider,
+        window: *mut GtkWidget,
+        files: *mut GList,
+    ) -> *mut GList;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_menu_provider_get_file_items(
+        provider: *mut NautilusMenuProvider,
+        files: *mut GList,
+    ) -> *mut GList;
     pub fn nautilus_menu_provider_get_type() -> GType;
+    pub fn nautilus_operation_result_get_type() -> GType;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_properties_item_get_name(item: *mut NautilusPropertiesItem) -> *const c_char;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_properties_item_get_type() -> GType;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_properties_item_get_value(item: *mut NautilusPropertiesItem) -> *const c_char;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_properties_item_new(
+        name: *const c_char,
+        value: *const c_char,
+    ) -> *mut NautilusPropertiesItem;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_properties_model_get_model(
+        model: *mut NautilusPropertiesModel,
+    ) -> *mut GListModel;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_properties_model_get_title(
+        model: *mut NautilusPropertiesModel,
+    ) -> *const c_char;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_properties_model_get_type() -> GType;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_properties_model_new(
+        title: *const c_char,
+        model: *mut GListModel,
+    ) -> *mut NautilusPropertiesModel;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_properties_model_provider_get_models(
+        provider: *mut NautilusPropertiesModelProvider,
+        files: *mut GList,
+    ) -> *mut GList;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_properties_model_provider_get_type() -> GType;
+    #[cfg(nautilus_43)]
+    pub fn nautilus_properties_model_set_title(
+        model: *mut NautilusPropertiesModel,
+        title: *const c_char,
+    );
+    #[cfg(not(nautilus_43))]
+    pub fn nautilus_property_page_get_type() -> GType;
+    #[cfg(not(nautilus_43))]
     pub fn nautilus_property_page_new(
         name: *const c_char,
         label: *mut GtkWidget,
         page: *mut GtkWidget,
     ) -> *mut NautilusPropertyPage;
+    #[cfg(not(nautilus_43))]
+    pub fn nautilus_property_page_provider_get_pages(
+        provider: *mut NautilusPropertyPageProvider,
+        files: *mut GList,
+    ) -> *mut GList;
+    #[cfg(not(nautilus_43))]
     pub fn nautilus_property_page_provider_get_type() -> GType;
 }
 
+#[cfg(feature = "mock")]
+pub mod mock;
+
 #[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum NautilusOperationResult {
     NautilusOperationComplete,
     NautilusOperationFailed,
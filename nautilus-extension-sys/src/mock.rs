@@ -0,0 +1,1342 @@
+//! In-process stand-in for libnautilus-extension, enabled by the `mock` feature.
+//!
+//! Every function bound by this crate is implemented here on top of GObject, so the crate no
+//! longer links against libnautilus-extension. Menus, menu items, columns, property pages and
+//! properties models are GObjects exposing the same properties and signals as the real ones, and
+//! `file_info_new()`
+//! creates files implementing the `NautilusFileInfo` interface.
+//!
+//! Types are looked up by name before being registered, so several copies of this module in one
+//! process (e.g. a test binary and a `dlopen`ed extension) share the same GTypes.
+
+// The exported functions implement libnautilus-extension's C API and share its contracts.
+#![allow(clippy::missing_safety_doc)]
+
+#[cfg(nautilus_43)]
+use crate::gio_ffi::GListModel;
+use crate::gio_ffi::{
+    g_content_type_is_a, g_file_get_uri, g_file_new_for_uri, G_FILE_TYPE_DIRECTORY,
+    G_FILE_TYPE_REGULAR, G_FILE_TYPE_UNKNOWN,
+};
+use crate::gio_ffi::{GFile, GFileType, GMount};
+use crate::glib_ffi::{
+    g_free, g_list_append, g_list_copy, g_list_free_full, g_strdup, g_uri_parse_scheme,
+    g_uri_unescape_string, gboolean, gpointer, GList, GType, GFALSE, GTRUE,
+};
+#[cfg(nautilus_43)]
+use crate::gobject_ffi::g_value_peek_pointer;
+use crate::gobject_ffi::{
+    g_closure_invoke, g_enum_register_static, g_object_class_install_property, g_object_new,
+    g_object_ref, g_object_set_property, g_object_unref, g_param_spec_boolean, g_param_spec_float,
+    g_param_spec_object, g_param_spec_string, g_param_value_set_default, g_signal_emit_by_name,
+    g_signal_newv, g_type_add_interface_static, g_type_class_peek_parent, g_type_from_name,
+    g_type_interface_add_prerequisite, g_type_interface_peek, g_type_register_static_simple,
+    g_value_copy, g_value_init, g_value_set_enum, g_value_set_object, g_value_set_pointer,
+    g_value_set_string, g_value_unset, GClosure, GEnumValue, GInterfaceInfo, GObject, GObjectClass,
+    GParamSpec, GTypeInstance, GTypeInterface, GValue, G_PARAM_READWRITE, G_SIGNAL_RUN_LAST,
+    G_TYPE_INTERFACE, G_TYPE_NONE, G_TYPE_OBJECT, G_TYPE_POINTER,
+};
+#[cfg(not(nautilus_43))]
+use crate::gtk_ffi::GtkWidget;
+use crate::{
+    NautilusColumn, NautilusColumnProvider, NautilusColumnProviderIface, NautilusFileInfo,
+    NautilusFileInfoIface, NautilusInfoProvider, NautilusInfoProviderIface, NautilusMenu,
+    NautilusMenuItem, NautilusMenuProvider, NautilusMenuProviderIface, NautilusOperationHandle,
+    NautilusOperationResult,
+};
+#[cfg(not(nautilus_43))]
+use crate::{
+    NautilusLocationWidgetProvider, NautilusLocationWidgetProviderIface, NautilusPropertyPage,
+    NautilusPropertyPageProvider, NautilusPropertyPageProviderIface,
+};
+#[cfg(nautilus_43)]
+use crate::{
+    NautilusPropertiesItem, NautilusPropertiesModel, NautilusPropertiesModelProvider,
+    NautilusPropertiesModelProviderIface,
+};
+use libc::{c_char, c_uint};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, Once};
+
+macro_rules! c_str {
+    ($s:expr) => {
+        concat!($s, "\0").as_ptr() as *const c_char
+    };
+}
+
+macro_rules! mock_type {
+    ($get_type_fn:ident, $register_fn:expr) => {
+        #[no_mangle]
+        pub extern "C" fn $get_type_fn() -> GType {
+            static REGISTERED: Once = Once::new();
+            static TYPE: AtomicUsize = AtomicUsize::new(0);
+
+            REGISTERED.call_once(|| TYPE.store(unsafe { $register_fn }, Ordering::SeqCst));
+            TYPE.load(Ordering::SeqCst)
+        }
+    };
+}
+
+mock_type!(
+    nautilus_column_provider_get_type,
+    register_interface(
+        c_str!("NautilusColumnProvider"),
+        mem::size_of::<NautilusColumnProviderIface>(),
+        None
+    )
+);
+mock_type!(
+    nautilus_file_info_get_type,
+    register_interface(
+        c_str!("NautilusFileInfo"),
+        mem::size_of::<NautilusFileInfoIface>(),
+        None
+    )
+);
+mock_type!(
+    nautilus_info_provider_get_type,
+    register_interface(
+        c_str!("NautilusInfoProvider"),
+        mem::size_of::<NautilusInfoProviderIface>(),
+        None
+    )
+);
+mock_type!(
+    nautilus_menu_provider_get_type,
+    register_interface(
+        c_str!("NautilusMenuProvider"),
+        mem::size_of::<NautilusMenuProviderIface>(),
+        Some(menu_provider_default_init)
+    )
+);
+#[cfg(not(nautilus_43))]
+mock_type!(
+    nautilus_location_widget_provider_get_type,
+    register_interface(
+        c_str!("NautilusLocationWidgetProvider"),
+        mem::size_of::<NautilusLocationWidgetProviderIface>(),
+        None
+    )
+);
+#[cfg(not(nautilus_43))]
+mock_type!(
+    nautilus_property_page_provider_get_type,
+    register_interface(
+        c_str!("NautilusPropertyPageProvider"),
+        mem::size_of::<NautilusPropertyPageProviderIface>(),
+        None
+    )
+);
+#[cfg(nautilus_43)]
+mock_type!(
+    nautilus_properties_model_provider_get_type,
+    register_interface(
+        c_str!("NautilusPropertiesModelProvider"),
+        mem::size_of::<NautilusPropertiesModelProviderIface>(),
+        None
+    )
+);
+mock_type!(
+    nautilus_column_get_type,
+    register_object(c_str!("NautilusColumn"), column_class_init)
+);
+mock_type!(
+    nautilus_menu_get_type,
+    register_object(c_str!("NautilusMenu"), mock_object_class_init)
+);
+mock_type!(
+    nautilus_menu_item_get_type,
+    register_object(c_str!("NautilusMenuItem"), menu_item_class_init)
+);
+#[cfg(not(nautilus_43))]
+mock_type!(
+    nautilus_property_page_get_type,
+    register_object(c_str!("NautilusPropertyPage"), property_page_class_init)
+);
+#[cfg(nautilus_43)]
+mock_type!(
+    nautilus_properties_item_get_type,
+    register_object(c_str!("NautilusPropertiesItem"), properties_item_class_init)
+);
+#[cfg(nautilus_43)]
+mock_type!(
+    nautilus_properties_model_get_type,
+    register_object(
+        c_str!("NautilusPropertiesModel"),
+        properties_model_class_init
+    )
+);
+mock_type!(
+    nautilus_operation_result_get_type,
+    register_operation_result()
+);
+mock_type!(mock_file_info_get_type, register_mock_file_info());
+
+unsafe fn register_interface(
+    name: *const c_char,
+    iface_size: usize,
+    default_init: Option<unsafe extern "C" fn(gpointer, gpointer)>,
+) -> GType {
+    let existing = g_type_from_name(name);
+    if existing != 0 {
+        return existing;
+    }
+
+    let iface_type = g_type_register_static_simple(
+        G_TYPE_INTERFACE,
+        name,
+        iface_size as c_uint,
+        default_init,
+        0,
+        None,
+        0,
+    );
+    g_type_interface_add_prerequisite(iface_type, G_TYPE_OBJECT);
+    iface_type
+}
+
+unsafe extern "C" fn menu_provider_default_init(iface: gpointer, _iface_data: gpointer) {
+    g_signal_newv(
+        c_str!("items-updated"),
+        (*(iface as *mut GTypeInterface)).g_type,
+        G_SIGNAL_RUN_LAST,
+        ptr::null_mut(),
+        None,
+        ptr::null_mut(),
+        None,
+        G_TYPE_NONE,
+        0,
+        ptr::null_mut(),
+    );
+}
+
+unsafe fn register_object(
+    name: *const c_char,
+    class_init: unsafe extern "C" fn(gpointer, gpointer),
+) -> GType {
+    let existing = g_type_from_name(name);
+    if existing != 0 {
+        return existing;
+    }
+
+    g_type_register_static_simple(
+        G_TYPE_OBJECT,
+        name,
+        mem::size_of::<GObjectClass>() as c_uint,
+        Some(class_init),
+        mem::size_of::<MockObject>() as c_uint,
+        Some(mock_object_init),
+        0,
+    )
+}
+
+unsafe fn register_operation_result() -> GType {
+    let name = c_str!("NautilusOperationResult");
+    let existing = g_type_from_name(name);
+    if existing != 0 {
+        return existing;
+    }
+
+    let values = vec![
+        GEnumValue {
+            value: NautilusOperationResult::NautilusOperationComplete as i32,
+            value_name: c_str!("NAUTILUS_OPERATION_COMPLETE"),
+            value_nick: c_str!("complete"),
+        },
+        GEnumValue {
+            value: NautilusOperationResult::NautilusOperationFailed as i32,
+            value_name: c_str!("NAUTILUS_OPERATION_FAILED"),
+            value_nick: c_str!("failed"),
+        },
+        GEnumValue {
+            value: NautilusOperationResult::NautilusOperationInProgress as i32,
+            value_name: c_str!("NAUTILUS_OPERATION_IN_PROGRESS"),
+            value_nick: c_str!("in-progress"),
+        },
+        GEnumValue {
+            value: 0,
+            value_name: ptr::null(),
+            value_nick: ptr::null(),
+        },
+    ];
+
+    // GLib keeps a pointer to the values for the lifetime of the type
+    g_enum_register_static(name, Box::leak(values.into_boxed_slice()).as_ptr())
+}
+
+/// Instance of every mock class except the file info: a GObject storing its properties.
+#[repr(C)]
+struct MockObject {
+    parent: GObject,
+    properties: *mut HashMap<c_uint, GValue>,
+    items: *mut GList,
+}
+
+unsafe extern "C" fn mock_object_init(instance: *mut GTypeInstance, _class: gpointer) {
+    let object = instance as *mut MockObject;
+    (*object).properties = Box::into_raw(Box::new(HashMap::new()));
+    (*object).items = ptr::null_mut();
+}
+
+unsafe extern "C" fn mock_object_class_init(class: gpointer, _class_data: gpointer) {
+    let object_class = class as *mut GObjectClass;
+    (*object_class).set_property = Some(mock_object_set_property);
+    (*object_class).get_property = Some(mock_object_get_property);
+    (*object_class).finalize = Some(mock_object_finalize);
+}
+
+unsafe extern "C" fn mock_object_set_property(
+    object: *mut GObject,
+    property_id: c_uint,
+    value: *mut GValue,
+    pspec: *mut GParamSpec,
+) {
+    let properties = &mut *(*(object as *mut MockObject)).properties;
+    let stored = properties.entry(property_id).or_insert_with(|| {
+        let mut stored: GValue = mem::zeroed();
+        g_value_init(&mut stored, (*pspec).value_type);
+        stored
+    });
+    g_value_copy(value, stored);
+}
+
+unsafe extern "C" fn mock_object_get_property(
+    object: *mut GObject,
+    property_id: c_uint,
+    value: *mut GValue,
+    pspec: *mut GParamSpec,
+) {
+    let properties = &*(*(object as *mut MockObject)).properties;
+    match properties.get(&property_id) {
+        Some(stored) => g_value_copy(stored, value),
+        None => g_param_value_set_default(pspec, value),
+    }
+}
+
+unsafe extern "C" fn mock_object_finalize(object: *mut GObject) {
+    let mock_object = object as *mut MockObject;
+
+    let properties = Box::from_raw((*mock_object).properties);
+    for (_, mut value) in *properties {
+        g_value_unset(&mut value);
+    }
+    g_list_free_full((*mock_object).items, Some(unref_object));
+
+    chain_up_finalize(object);
+}
+
+unsafe fn chain_up_finalize(object: *mut GObject) {
+    let parent_class = g_type_class_peek_parent((*object).g_type_instance.g_class as gpointer)
+        as *mut GObjectClass;
+    if let Some(finalize) = (*parent_class).finalize {
+        finalize(object);
+    }
+}
+
+unsafe extern "C" fn unref_object(object: gpointer) {
+    g_object_unref(object as *mut GObject);
+}
+
+unsafe fn install_string_property(class: gpointer, property_id: c_uint, name: *const c_char) {
+    g_object_class_install_property(
+        class as *mut GObjectClass,
+        property_id,
+        g_param_spec_string(name, name, name, ptr::null(), G_PARAM_READWRITE),
+    );
+}
+
+unsafe fn install_boolean_property(
+    class: gpointer,
+    property_id: c_uint,
+    name: *const c_char,
+    default_value: gboolean,
+) {
+    g_object_class_install_property(
+        class as *mut GObjectClass,
+        property_id,
+        g_param_spec_boolean(name, name, name, default_value, G_PARAM_READWRITE),
+    );
+}
+
+unsafe fn install_object_property(
+    class: gpointer,
+    property_id: c_uint,
+    name: *const c_char,
+    object_type: GType,
+) {
+    g_object_class_install_property(
+        class as *mut GObjectClass,
+        property_id,
+        g_param_spec_object(name, name, name, object_type, G_PARAM_READWRITE),
+    );
+}
+
+unsafe extern "C" fn column_class_init(class: gpointer, class_data: gpointer) {
+    mock_object_class_init(class, class_data);
+    install_string_property(class, 1, c_str!("name"));
+    install_string_property(class, 2, c_str!("attribute"));
+    install_string_property(class, 3, c_str!("label"));
+    install_string_property(class, 4, c_str!("description"));
+    g_object_class_install_property(
+        class as *mut GObjectClass,
+        5,
+        g_param_spec_float(
+            c_str!("xalign"),
+            c_str!("xalign"),
+            c_str!("xalign"),
+            0.0,
+            1.0,
+            0.0,
+            G_PARAM_READWRITE,
+        ),
+    );
+}
+
+unsafe extern "C" fn menu_item_class_init(class: gpointer, class_data: gpointer) {
+    mock_object_class_init(class, class_data);
+    install_string_property(class, 1, c_str!("name"));
+    install_string_property(class, 2, c_str!("label"));
+    install_string_property(class, 3, c_str!("tip"));
+    install_string_property(class, 4, c_str!("icon"));
+    install_boolean_property(class, 5, c_str!("sensitive"), GTRUE);
+    install_boolean_property(class, 6, c_str!("priority"), GTRUE);
+    install_object_property(class, 7, c_str!("menu"), nautilus_menu_get_type());
+
+    g_signal_newv(
+        c_str!("activate"),
+        (*(class as *mut GObjectClass)).g_type_class.g_type,
+        G_SIGNAL_RUN_LAST,
+        ptr::null_mut(),
+        None,
+        ptr::null_mut(),
+        None,
+        G_TYPE_NONE,
+        0,
+        ptr::null_mut(),
+    );
+}
+
+#[cfg(not(nautilus_43))]
+unsafe extern "C" fn property_page_class_init(class: gpointer, class_data: gpointer) {
+    mock_object_class_init(class, class_data);
+    install_string_property(class, 1, c_str!("name"));
+    install_object_property(class, 2, c_str!("label"), G_TYPE_OBJECT);
+    install_object_property(class, 3, c_str!("page"), G_TYPE_OBJECT);
+}
+
+#[cfg(nautilus_43)]
+unsafe extern "C" fn properties_item_class_init(class: gpointer, class_data: gpointer) {
+    mock_object_class_init(class, class_data);
+    install_string_property(class, 1, c_str!("name"));
+    install_string_property(class, 2, c_str!("value"));
+}
+
+#[cfg(nautilus_43)]
+unsafe extern "C" fn properties_model_class_init(class: gpointer, class_data: gpointer) {
+    mock_object_class_init(class, class_data);
+    install_string_property(class, 1, c_str!("title"));
+    install_object_property(class, 2, c_str!("model"), G_TYPE_OBJECT);
+}
+
+/// The value of a string or object property as stored in the object, without copying, as the
+/// real getters return it. `null` if the property was never set.
+#[cfg(nautilus_43)]
+unsafe fn stored_property(object: *mut GObject, property_id: c_uint) -> gpointer {
+    let properties = &*(*(object as *mut MockObject)).properties;
+    match properties.get(&property_id) {
+        Some(stored) => g_value_peek_pointer(stored),
+        None => ptr::null_mut(),
+    }
+}
+
+unsafe fn set_string_property(object: *mut GObject, name: *const c_char, value: *const c_char) {
+    let mut g_value: GValue = mem::zeroed();
+    g_value_init(&mut g_value, crate::gobject_ffi::G_TYPE_STRING);
+    g_value_set_string(&mut g_value, value);
+    g_object_set_property(object, name, &g_value);
+    g_value_unset(&mut g_value);
+}
+
+unsafe fn set_object_property(object: *mut GObject, name: *const c_char, value: *mut GObject) {
+    let mut g_value: GValue = mem::zeroed();
+    g_value_init(&mut g_value, G_TYPE_OBJECT);
+    g_value_set_object(&mut g_value, value);
+    g_object_set_property(object, name, &g_value);
+    g_value_unset(&mut g_value);
+}
+
+unsafe fn new_object(object_type: GType) -> *mut GObject {
+    g_object_new(object_type, ptr::null::<c_char>())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_column_new(
+    name: *const c_char,
+    attribute: *const c_char,
+    label: *const c_char,
+    description: *const c_char,
+) -> *mut NautilusColumn {
+    let column = new_object(nautilus_column_get_type());
+    set_string_property(column, c_str!("name"), name);
+    set_string_property(column, c_str!("attribute"), attribute);
+    set_string_property(column, c_str!("label"), label);
+    set_string_property(column, c_str!("description"), description);
+    column as *mut NautilusColumn
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_new() -> *mut NautilusMenu {
+    new_object(nautilus_menu_get_type()) as *mut NautilusMenu
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_append_item(
+    menu: *mut NautilusMenu,
+    item: *mut NautilusMenuItem,
+) {
+    let menu = menu as *mut MockObject;
+    let item = g_object_ref(item as *mut GObject);
+    (*menu).items = g_list_append((*menu).items, item as gpointer);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_get_items(menu: *mut NautilusMenu) -> *mut GList {
+    let items = g_list_copy((*(menu as *mut MockObject)).items);
+    let mut node = items;
+    while !node.is_null() {
+        g_object_ref((*node).data as *mut GObject);
+        node = (*node).next;
+    }
+    items
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_item_new(
+    name: *const c_char,
+    label: *const c_char,
+    tip: *const c_char,
+    icon: *const c_char,
+) -> *mut NautilusMenuItem {
+    let item = new_object(nautilus_menu_item_get_type());
+    set_string_property(item, c_str!("name"), name);
+    set_string_property(item, c_str!("label"), label);
+    set_string_property(item, c_str!("tip"), tip);
+    set_string_property(item, c_str!("icon"), icon);
+    item as *mut NautilusMenuItem
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_item_set_submenu(
+    item: *mut NautilusMenuItem,
+    menu: *mut NautilusMenu,
+) {
+    set_object_property(item as *mut GObject, c_str!("menu"), menu as *mut GObject);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_item_activate(item: *mut NautilusMenuItem) {
+    g_signal_emit_by_name(item as *mut GObject, c_str!("activate"));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_item_list_free(item_list: *mut GList) {
+    g_list_free_full(item_list, Some(unref_object));
+}
+
+/// Look up the vtable of interface `iface_type` on `instance`, the way libnautilus-extension
+/// dispatches calls through its provider wrappers.
+unsafe fn iface<T>(instance: gpointer, iface_type: GType) -> *mut T {
+    let instance = instance as *mut GTypeInstance;
+    g_type_interface_peek((*instance).g_class as gpointer, iface_type) as *mut T
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_column_provider_get_columns(
+    provider: *mut NautilusColumnProvider,
+) -> *mut GList {
+    let iface: *mut NautilusColumnProviderIface =
+        iface(provider as gpointer, nautilus_column_provider_get_type());
+    match (*iface).get_columns {
+        Some(get_columns) => get_columns(provider as gpointer),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_info_provider_update_file_info(
+    provider: *mut NautilusInfoProvider,
+    file: *mut NautilusFileInfo,
+    update_complete: *mut GClosure,
+    handle: *mut *mut NautilusOperationHandle,
+) -> NautilusOperationResult {
+    let iface: *mut NautilusInfoProviderIface =
+        iface(provider as gpointer, nautilus_info_provider_get_type());
+    match (*iface).update_file_info {
+        Some(update_file_info) => update_file_info(provider, file, update_complete, handle),
+        None => NautilusOperationResult::NautilusOperationComplete,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_info_provider_cancel_update(
+    provider: *mut NautilusInfoProvider,
+    handle: *mut NautilusOperationHandle,
+) {
+    let iface: *mut NautilusInfoProviderIface =
+        iface(provider as gpointer, nautilus_info_provider_get_type());
+    if let Some(cancel_update) = (*iface).cancel_update {
+        cancel_update(provider, handle);
+    }
+}
+
+#[cfg(not(nautilus_43))]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_location_widget_provider_get_widget(
+    provider: *mut NautilusLocationWidgetProvider,
+    uri: *const c_char,
+    window: *mut GtkWidget,
+) -> *mut GtkWidget {
+    let iface: *mut NautilusLocationWidgetProviderIface = iface(
+        provider as gpointer,
+        nautilus_location_widget_provider_get_type(),
+    );
+    match (*iface).get_widget {
+        Some(get_widget) => get_widget(provider, uri, window),
+        None => ptr::null_mut(),
+    }
+}
+
+#[cfg(not(nautilus_43))]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_provider_get_file_items(
+    provider: *mut NautilusMenuProvider,
+    window: *mut GtkWidget,
+    files: *mut GList,
+) -> *mut GList {
+    let iface: *mut NautilusMenuProviderIface =
+        iface(provider as gpointer, nautilus_menu_provider_get_type());
+    match (*iface).get_file_items {
+        Some(get_file_items) => get_file_items(provider as gpointer, window, files),
+        None => ptr::null_mut(),
+    }
+}
+
+#[cfg(nautilus_43)]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_provider_get_file_items(
+    provider: *mut NautilusMenuProvider,
+    files: *mut GList,
+) -> *mut GList {
+    let iface: *mut NautilusMenuProviderIface =
+        iface(provider as gpointer, nautilus_menu_provider_get_type());
+    match (*iface).get_file_items {
+        Some(get_file_items) => get_file_items(provider as gpointer, files),
+        None => ptr::null_mut(),
+    }
+}
+
+#[cfg(not(nautilus_43))]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_provider_get_background_items(
+    provider: *mut NautilusMenuProvider,
+    window: *mut GtkWidget,
+    current_folder: *mut NautilusFileInfo,
+) -> *mut GList {
+    let iface: *mut NautilusMenuProviderIface =
+        iface(provider as gpointer, nautilus_menu_provider_get_type());
+    match (*iface).get_background_items {
+        Some(get_background_items) => {
+            get_background_items(provider as gpointer, window, current_folder)
+        }
+        None => ptr::null_mut(),
+    }
+}
+
+#[cfg(nautilus_43)]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_provider_get_background_items(
+    provider: *mut NautilusMenuProvider,
+    current_folder: *mut NautilusFileInfo,
+) -> *mut GList {
+    let iface: *mut NautilusMenuProviderIface =
+        iface(provider as gpointer, nautilus_menu_provider_get_type());
+    match (*iface).get_background_items {
+        Some(get_background_items) => get_background_items(provider as gpointer, current_folder),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_menu_provider_emit_items_updated_signal(
+    provider: *mut NautilusMenuProvider,
+) {
+    g_signal_emit_by_name(provider as *mut GObject, c_str!("items-updated"));
+}
+
+#[cfg(not(nautilus_43))]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_property_page_provider_get_pages(
+    provider: *mut NautilusPropertyPageProvider,
+    files: *mut GList,
+) -> *mut GList {
+    let iface: *mut NautilusPropertyPageProviderIface = iface(
+        provider as gpointer,
+        nautilus_property_page_provider_get_type(),
+    );
+    match (*iface).get_pages {
+        Some(get_pages) => get_pages(provider, files),
+        None => ptr::null_mut(),
+    }
+}
+
+#[cfg(nautilus_43)]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_properties_model_provider_get_models(
+    provider: *mut NautilusPropertiesModelProvider,
+    files: *mut GList,
+) -> *mut GList {
+    let iface: *mut NautilusPropertiesModelProviderIface = iface(
+        provider as gpointer,
+        nautilus_properties_model_provider_get_type(),
+    );
+    match (*iface).get_models {
+        Some(get_models) => get_models(provider, files),
+        None => ptr::null_mut(),
+    }
+}
+
+#[cfg(not(nautilus_43))]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_property_page_new(
+    name: *const c_char,
+    label: *mut GtkWidget,
+    page: *mut GtkWidget,
+) -> *mut NautilusPropertyPage {
+    let property_page = new_object(nautilus_property_page_get_type());
+    set_string_property(property_page, c_str!("name"), name);
+    set_object_property(property_page, c_str!("label"), label as *mut GObject);
+    set_object_property(property_page, c_str!("page"), page as *mut GObject);
+    property_page as *mut NautilusPropertyPage
+}
+
+#[cfg(nautilus_43)]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_properties_item_new(
+    name: *const c_char,
+    value: *const c_char,
+) -> *mut NautilusPropertiesItem {
+    let item = new_object(nautilus_properties_item_get_type());
+    set_string_property(item, c_str!("name"), name);
+    set_string_property(item, c_str!("value"), value);
+    item as *mut NautilusPropertiesItem
+}
+
+#[cfg(nautilus_43)]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_properties_item_get_name(
+    item: *mut NautilusPropertiesItem,
+) -> *const c_char {
+    stored_property(item as *mut GObject, 1) as *const c_char
+}
+
+#[cfg(nautilus_43)]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_properties_item_get_value(
+    item: *mut NautilusPropertiesItem,
+) -> *const c_char {
+    stored_property(item as *mut GObject, 2) as *const c_char
+}
+
+#[cfg(nautilus_43)]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_properties_model_new(
+    title: *const c_char,
+    model: *mut GListModel,
+) -> *mut NautilusPropertiesModel {
+    let properties_model = new_object(nautilus_properties_model_get_type());
+    set_string_property(properties_model, c_str!("title"), title);
+    set_object_property(properties_model, c_str!("model"), model as *mut GObject);
+    // the model is `(transfer full)`, and the property holds its own reference
+    if !model.is_null() {
+        g_object_unref(model as *mut GObject);
+    }
+    properties_model as *mut NautilusPropertiesModel
+}
+
+#[cfg(nautilus_43)]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_properties_model_get_title(
+    model: *mut NautilusPropertiesModel,
+) -> *const c_char {
+    stored_property(model as *mut GObject, 1) as *const c_char
+}
+
+#[cfg(nautilus_43)]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_properties_model_set_title(
+    model: *mut NautilusPropertiesModel,
+    title: *const c_char,
+) {
+    set_string_property(model as *mut GObject, c_str!("title"), title);
+}
+
+#[cfg(nautilus_43)]
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_properties_model_get_model(
+    model: *mut NautilusPropertiesModel,
+) -> *mut GListModel {
+    stored_property(model as *mut GObject, 2) as *mut GListModel
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_info_provider_update_complete_invoke(
+    update_complete: *mut GClosure,
+    provider: *mut NautilusInfoProvider,
+    handle: *mut NautilusOperationHandle,
+    result: NautilusOperationResult,
+) {
+    let mut args: [GValue; 3] = mem::zeroed();
+    g_value_init(&mut args[0], nautilus_info_provider_get_type());
+    g_value_init(&mut args[1], G_TYPE_POINTER);
+    g_value_init(&mut args[2], nautilus_operation_result_get_type());
+    g_value_set_object(&mut args[0], provider as *mut GObject);
+    g_value_set_pointer(&mut args[1], handle as gpointer);
+    g_value_set_enum(&mut args[2], result as i32);
+
+    g_closure_invoke(
+        update_complete,
+        ptr::null_mut(),
+        args.len() as c_uint,
+        args.as_ptr(),
+        ptr::null_mut(),
+    );
+
+    for arg in args.iter_mut() {
+        g_value_unset(arg);
+    }
+}
+
+/// State of a file created by `file_info_new()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockFileData {
+    pub uri: String,
+    pub mime_type: String,
+    pub file_type: GFileType,
+    pub can_write: bool,
+    pub is_gone: bool,
+    /// Attributes added through `nautilus_file_info_add_string_attribute()`.
+    pub attributes: BTreeMap<String, String>,
+    /// Emblems added through `nautilus_file_info_add_emblem()`.
+    pub emblems: Vec<String>,
+    /// Number of `nautilus_file_info_invalidate_extension_info()` calls.
+    pub invalidations: u32,
+}
+
+impl MockFileData {
+    pub fn new(uri: &str) -> MockFileData {
+        MockFileData {
+            uri: uri.to_string(),
+            mime_type: "application/octet-stream".to_string(),
+            file_type: G_FILE_TYPE_REGULAR,
+            can_write: true,
+            is_gone: false,
+            attributes: BTreeMap::new(),
+            emblems: Vec::new(),
+            invalidations: 0,
+        }
+    }
+
+    fn parent_uri(&self) -> Option<&str> {
+        let path_start = self.uri.find("://").map(|i| i + 3).unwrap_or(0);
+        let trimmed = self.uri.trim_end_matches('/');
+        match trimmed[path_start..].rfind('/') {
+            Some(i) => Some(&trimmed[..path_start + i + 1]),
+            None => None,
+        }
+    }
+
+    fn escaped_name(&self) -> &str {
+        let trimmed = self.uri.trim_end_matches('/');
+        match trimmed.rfind('/') {
+            Some(i) => &trimmed[i + 1..],
+            None => trimmed,
+        }
+    }
+}
+
+#[repr(C)]
+struct MockFileInfo {
+    parent: GObject,
+    data: *mut Mutex<MockFileData>,
+}
+
+unsafe fn register_mock_file_info() -> GType {
+    let name = c_str!("NautilusMockFileInfo");
+    let existing = g_type_from_name(name);
+    if existing != 0 {
+        return existing;
+    }
+
+    let file_info_type = g_type_register_static_simple(
+        G_TYPE_OBJECT,
+        name,
+        mem::size_of::<GObjectClass>() as c_uint,
+        Some(mock_file_info_class_init),
+        mem::size_of::<MockFileInfo>() as c_uint,
+        Some(mock_file_info_init),
+        0,
+    );
+
+    let iface_info = GInterfaceInfo {
+        interface_init: Some(mock_file_info_iface_init),
+        interface_finalize: None,
+        interface_data: ptr::null_mut(),
+    };
+    g_type_add_interface_static(file_info_type, nautilus_file_info_get_type(), &iface_info);
+
+    file_info_type
+}
+
+unsafe extern "C" fn mock_file_info_class_init(class: gpointer, _class_data: gpointer) {
+    (*(class as *mut GObjectClass)).finalize = Some(mock_file_info_finalize);
+}
+
+unsafe extern "C" fn mock_file_info_init(instance: *mut GTypeInstance, _class: gpointer) {
+    let file_info = instance as *mut MockFileInfo;
+    (*file_info).data = Box::into_raw(Box::new(Mutex::new(MockFileData::new(""))));
+}
+
+unsafe extern "C" fn mock_file_info_finalize(object: *mut GObject) {
+    drop(Box::from_raw((*(object as *mut MockFileInfo)).data));
+    chain_up_finalize(object);
+}
+
+unsafe extern "C" fn mock_file_info_iface_init(iface: gpointer, _iface_data: gpointer) {
+    let iface = iface as *mut NautilusFileInfoIface;
+    (*iface).is_gone = Some(mock_is_gone);
+    (*iface).get_name = Some(mock_get_name);
+    (*iface).get_uri = Some(mock_get_uri);
+    (*iface).get_parent_uri = Some(mock_get_parent_uri);
+    (*iface).get_uri_scheme = Some(mock_get_uri_scheme);
+    (*iface).get_mime_type = Some(mock_get_mime_type);
+    (*iface).is_mime_type = Some(mock_is_mime_type);
+    (*iface).is_directory = Some(mock_is_directory);
+    (*iface).add_emblem = Some(mock_add_emblem);
+    (*iface).get_string_attribute = Some(mock_get_string_attribute);
+    (*iface).add_string_attribute = Some(mock_add_string_attribute);
+    (*iface).invalidate_extension_info = Some(mock_invalidate_extension_info);
+    (*iface).get_activation_uri = Some(mock_get_uri);
+    (*iface).get_file_type = Some(mock_get_file_type);
+    (*iface).get_location = Some(mock_get_location);
+    (*iface).get_parent_location = Some(mock_get_parent_location);
+    (*iface).get_parent_info = Some(mock_get_parent_info);
+    (*iface).get_mount = Some(mock_get_mount);
+    (*iface).can_write = Some(mock_can_write);
+}
+
+/// Create a file implementing the `NautilusFileInfo` interface, owned by the caller.
+///
+/// # Safety
+///
+/// Release the returned file with `g_object_unref()`.
+pub unsafe fn file_info_new(data: MockFileData) -> *mut NautilusFileInfo {
+    let file = new_object(mock_file_info_get_type()) as *mut NautilusFileInfo;
+    with_file_data(file, |file_data| *file_data = data);
+    file
+}
+
+/// Run `f` on the state of a file created by `file_info_new()`.
+///
+/// # Safety
+///
+/// `file` must come from `file_info_new()`.
+pub unsafe fn with_file_data<R, F: FnOnce(&mut MockFileData) -> R>(
+    file: *mut NautilusFileInfo,
+    f: F,
+) -> R {
+    let data = &*(*(file as *mut MockFileInfo)).data;
+    let mut data = data.lock().unwrap();
+    f(&mut data)
+}
+
+fn to_gboolean(value: bool) -> gboolean {
+    if value {
+        GTRUE
+    } else {
+        GFALSE
+    }
+}
+
+unsafe fn dup_string(s: &str) -> *mut c_char {
+    let s = CString::new(s).unwrap();
+    g_strdup(s.as_ptr())
+}
+
+unsafe extern "C" fn mock_is_gone(file: *mut NautilusFileInfo) -> gboolean {
+    to_gboolean(with_file_data(file, |data| data.is_gone))
+}
+
+unsafe extern "C" fn mock_get_name(file: *mut NautilusFileInfo) -> *mut c_char {
+    let escaped_name = with_file_data(file, |data| CString::new(data.escaped_name()).unwrap());
+    g_uri_unescape_string(escaped_name.as_ptr(), ptr::null())
+}
+
+unsafe extern "C" fn mock_get_uri(file: *mut NautilusFileInfo) -> *mut c_char {
+    with_file_data(file, |data| dup_string(&data.uri))
+}
+
+unsafe extern "C" fn mock_get_parent_uri(file: *mut NautilusFileInfo) -> *mut c_char {
+    with_file_data(file, |data| dup_string(data.parent_uri().unwrap_or("")))
+}
+
+unsafe extern "C" fn mock_get_uri_scheme(file: *mut NautilusFileInfo) -> *mut c_char {
+    let uri = with_file_data(file, |data| CString::new(data.uri.as_str()).unwrap());
+    g_uri_parse_scheme(uri.as_ptr())
+}
+
+unsafe extern "C" fn mock_get_mime_type(file: *mut NautilusFileInfo) -> *mut c_char {
+    with_file_data(file, |data| dup_string(&data.mime_type))
+}
+
+unsafe extern "C" fn mock_is_mime_type(
+    file: *mut NautilusFileInfo,
+    mime_type: *const c_char,
+) -> gboolean {
+    let own_mime_type = with_file_data(file, |data| CString::new(data.mime_type.as_str()).unwrap());
+    g_content_type_is_a(own_mime_type.as_ptr(), mime_type)
+}
+
+unsafe extern "C" fn mock_is_directory(file: *mut NautilusFileInfo) -> gboolean {
+    to_gboolean(with_file_data(file, |data| {
+        data.file_type == G_FILE_TYPE_DIRECTORY
+    }))
+}
+
+unsafe extern "C" fn mock_add_emblem(file: *mut NautilusFileInfo, emblem_name: *const c_char) {
+    let emblem_name = CStr::from_ptr(emblem_name).to_string_lossy().into_owned();
+    with_file_data(file, |data| data.emblems.push(emblem_name));
+}
+
+unsafe extern "C" fn mock_get_string_attribute(
+    file: *mut NautilusFileInfo,
+    attribute_name: *const c_char,
+) -> *mut c_char {
+    let attribute_name = CStr::from_ptr(attribute_name).to_string_lossy();
+    with_file_data(file, |data| {
+        match data.attributes.get(attribute_name.as_ref()) {
+            Some(value) => dup_string(value),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+unsafe extern "C" fn mock_add_string_attribute(
+    file: *mut NautilusFileInfo,
+    attribute_name: *const c_char,
+    value: *const c_char,
+) {
+    let attribute_name = CStr::from_ptr(attribute_name)
+        .to_string_lossy()
+        .into_owned();
+    let value = CStr::from_ptr(value).to_string_lossy().into_owned();
+    with_file_data(file, |data| data.attributes.insert(attribute_name, value));
+}
+
+unsafe extern "C" fn mock_invalidate_extension_info(file: *mut NautilusFileInfo) {
+    with_file_data(file, |data| data.invalidations += 1);
+}
+
+unsafe extern "C" fn mock_get_file_type(file: *mut NautilusFileInfo) -> GFileType {
+    with_file_data(file, |data| data.file_type)
+}
+
+unsafe extern "C" fn mock_get_location(file: *mut NautilusFileInfo) -> *mut GFile {
+    let uri = with_file_data(file, |data| CString::new(data.uri.as_str()).unwrap());
+    g_file_new_for_uri(uri.as_ptr())
+}
+
+unsafe extern "C" fn mock_get_parent_location(file: *mut NautilusFileInfo) -> *mut GFile {
+    let parent_uri = with_file_data(file, |data| {
+        data.parent_uri().map(|uri| CString::new(uri).unwrap())
+    });
+    match parent_uri {
+        Some(parent_uri) => g_file_new_for_uri(parent_uri.as_ptr()),
+        None => ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn mock_get_parent_info(_file: *mut NautilusFileInfo) -> *mut NautilusFileInfo {
+    ptr::null_mut()
+}
+
+unsafe extern "C" fn mock_get_mount(_file: *mut NautilusFileInfo) -> *mut GMount {
+    ptr::null_mut()
+}
+
+unsafe extern "C" fn mock_can_write(file: *mut NautilusFileInfo) -> gboolean {
+    to_gboolean(with_file_data(file, |data| data.can_write))
+}
+
+unsafe fn file_info_iface(file: *mut NautilusFileInfo) -> *mut NautilusFileInfoIface {
+    iface(file as gpointer, nautilus_file_info_get_type())
+}
+
+macro_rules! file_info_getter {
+    ($fn_name:ident, $vfunc:ident, $return_type:ty, $default:expr) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn $fn_name(file: *mut NautilusFileInfo) -> $return_type {
+            match (*file_info_iface(file)).$vfunc {
+                Some($vfunc) => $vfunc(file),
+                None => $default,
+            }
+        }
+    };
+}
+
+file_info_getter!(nautilus_file_info_can_write, can_write, gboolean, GFALSE);
+file_info_getter!(
+    nautilus_file_info_get_activation_uri,
+    get_activation_uri,
+    *mut c_char,
+    ptr::null_mut()
+);
+file_info_getter!(
+    nautilus_file_info_get_file_type,
+    get_file_type,
+    GFileType,
+    G_FILE_TYPE_UNKNOWN
+);
+file_info_getter!(
+    nautilus_file_info_get_location,
+    get_location,
+    *mut GFile,
+    ptr::null_mut()
+);
+file_info_getter!(
+    nautilus_file_info_get_mime_type,
+    get_mime_type,
+    *mut c_char,
+    ptr::null_mut()
+);
+file_info_getter!(
+    nautilus_file_info_get_mount,
+    get_mount,
+    *mut GMount,
+    ptr::null_mut()
+);
+file_info_getter!(
+    nautilus_file_info_get_name,
+    get_name,
+    *mut c_char,
+    ptr::null_mut()
+);
+file_info_getter!(
+    nautilus_file_info_get_parent_info,
+    get_parent_info,
+    *mut NautilusFileInfo,
+    ptr::null_mut()
+);
+file_info_getter!(
+    nautilus_file_info_get_parent_location,
+    get_parent_location,
+    *mut GFile,
+    ptr::null_mut()
+);
+file_info_getter!(
+    nautilus_file_info_get_parent_uri,
+    get_parent_uri,
+    *mut c_char,
+    ptr::null_mut()
+);
+file_info_getter!(
+    nautilus_file_info_is_directory,
+    is_directory,
+    gboolean,
+    GFALSE
+);
+file_info_getter!(nautilus_file_info_is_gone, is_gone, gboolean, GFALSE);
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_is_mime_type(
+    file: *mut NautilusFileInfo,
+    mime_type: *const c_char,
+) -> gboolean {
+    match (*file_info_iface(file)).is_mime_type {
+        Some(is_mime_type) => is_mime_type(file, mime_type),
+        None => GFALSE,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_add_emblem(
+    file: *mut NautilusFileInfo,
+    emblem_name: *const c_char,
+) {
+    if let Some(add_emblem) = (*file_info_iface(file)).add_emblem {
+        add_emblem(file, emblem_name);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_add_string_attribute(
+    file: *mut NautilusFileInfo,
+    attribute_name: *const c_char,
+    value: *const c_char,
+) {
+    if let Some(add_string_attribute) = (*file_info_iface(file)).add_string_attribute {
+        add_string_attribute(file, attribute_name, value);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_get_string_attribute(
+    file: *mut NautilusFileInfo,
+    attribute_name: *const c_char,
+) -> *mut c_char {
+    match (*file_info_iface(file)).get_string_attribute {
+        Some(get_string_attribute) => get_string_attribute(file, attribute_name),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_get_uri(
+    file_info: *mut NautilusFileInfo,
+) -> *mut c_char {
+    match (*file_info_iface(file_info)).get_uri {
+        Some(get_uri) => get_uri(file_info),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_get_uri_scheme(
+    file_info: *mut NautilusFileInfo,
+) -> *mut c_char {
+    match (*file_info_iface(file_info)).get_uri_scheme {
+        Some(get_uri_scheme) => get_uri_scheme(file_info),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_invalidate_extension_info(file: *mut NautilusFileInfo) {
+    if let Some(invalidate_extension_info) = (*file_info_iface(file)).invalidate_extension_info {
+        invalidate_extension_info(file);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_list_copy(files: *mut GList) -> *mut GList {
+    let files = g_list_copy(files);
+    let mut node = files;
+    while !node.is_null() {
+        g_object_ref((*node).data as *mut GObject);
+        node = (*node).next;
+    }
+    files
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_list_free(files: *mut GList) {
+    g_list_free_full(files, Some(unref_object));
+}
+
+/// Files handed out by `nautilus_file_info_create()`, by URI. Like Nautilus's file cache, the
+/// same URI always maps to the same file. The cache holds a reference to each file.
+fn file_info_cache() -> &'static Mutex<HashMap<String, usize>> {
+    static CREATED: Once = Once::new();
+    static CACHE: AtomicUsize = AtomicUsize::new(0);
+
+    CREATED.call_once(|| {
+        let cache: &'static Mutex<HashMap<String, usize>> =
+            Box::leak(Box::new(Mutex::new(HashMap::new())));
+        CACHE.store(cache as *const _ as usize, Ordering::SeqCst);
+    });
+    unsafe { &*(CACHE.load(Ordering::SeqCst) as *const Mutex<HashMap<String, usize>>) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_lookup_for_uri(
+    uri: *const c_char,
+) -> *mut NautilusFileInfo {
+    let uri = CStr::from_ptr(uri).to_string_lossy();
+    match file_info_cache().lock().unwrap().get(uri.as_ref()) {
+        Some(&file) => g_object_ref(file as *mut GObject) as *mut NautilusFileInfo,
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_create_for_uri(
+    uri: *const c_char,
+) -> *mut NautilusFileInfo {
+    let uri = CStr::from_ptr(uri).to_string_lossy().into_owned();
+    let mut cache = file_info_cache().lock().unwrap();
+    let file = *cache
+        .entry(uri)
+        .or_insert_with_key(|uri| file_info_new(MockFileData::new(uri)) as usize);
+    g_object_ref(file as *mut GObject) as *mut NautilusFileInfo
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_lookup(location: *mut GFile) -> *mut NautilusFileInfo {
+    let uri = g_file_get_uri(location);
+    let file = nautilus_file_info_lookup_for_uri(uri);
+    g_free(uri as gpointer);
+    file
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nautilus_file_info_create(location: *mut GFile) -> *mut NautilusFileInfo {
+    let uri = g_file_get_uri(location);
+    let file = nautilus_file_info_create_for_uri(uri);
+    g_free(uri as gpointer);
+    file
+}
+
+/// Free a string returned by one of the mock functions.
+///
+/// # Safety
+///
+/// `s` must have been allocated by GLib, or be null.
+pub unsafe fn free_string(s: *mut c_char) {
+    g_free(s as gpointer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatches_file_info_calls() {
+        unsafe {
+            let uri = c_str!("file:///tmp/mock%20dir/photo.jpg");
+            assert!(nautilus_file_info_lookup_for_uri(uri).is_null());
+
+            let file = nautilus_file_info_create_for_uri(uri);
+            assert_eq!(nautilus_file_info_lookup_for_uri(uri), file);
+            with_file_data(file, |data| data.mime_type = String::from("image/jpeg"));
+
+            let name = nautilus_file_info_get_name(file);
+            assert_eq!(CStr::from_ptr(name).to_str(), Ok("photo.jpg"));
+            free_string(name);
+            assert_eq!(nautilus_file_info_is_directory(file), GFALSE);
+            assert_eq!(
+                nautilus_file_info_is_mime_type(file, c_str!("image/*")),
+                GTRUE
+            );
+
+            nautilus_file_info_add_emblem(file, c_str!("emblem-favorite"));
+            with_file_data(file, |data| assert_eq!(data.emblems, ["emblem-favorite"]));
+
+            // one reference from each lookup/create call, one from the cache
+            g_object_unref(file as *mut GObject);
+            g_object_unref(file as *mut GObject);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! The extension loaded by the tests of `nautilus-extension-test`.
+//!
+//! It registers two types: `FixtureExtension` with a menu, column, info and property page or
+//! properties model provider, and `FixtureColumnsOnly` with a column provider alone.
+
+use nautilus_extension::{
+    menu_item_activate, nautilus_extension, ColumnProvider, FileInfo, GTypeModule, InfoProvider,
+    MenuItem, MenuProvider, NautilusModule, Selection,
+};
+use std::thread;
+use std::time::Duration;
+
+/// How long `update_file_info` takes for files whose name starts with `slow`.
+pub const SLOW_UPDATE: Duration = Duration::from_millis(500);
+
+#[derive(ColumnProvider)]
+#[column(
+    name = "Fixture::name_column",
+    attribute = "fixture_name",
+    label = "Name",
+    description = "Name seen by the fixture"
+)]
+struct FixtureColumns;
+
+#[menu_item_activate]
+fn invalidate_files(files: Vec<FileInfo>) {
+    for file in &files {
+        file.invalidate_extension_info();
+    }
+}
+
+struct FixtureMenu;
+
+impl FixtureMenu {
+    fn selection_items(&self, selection: &Selection) -> Vec<MenuItem> {
+        let mut item = MenuItem::new(
+            "Fixture::count".to_string(),
+            format!("{} files", selection.len()),
+            "Invalidate the files".to_string(),
+            None,
+        );
+        item.set_activate_cb(invalidate_files);
+        vec![item]
+    }
+
+    fn background_items(&self, current_folder: &FileInfo) -> Vec<MenuItem> {
+        vec![MenuItem::new(
+            "Fixture::folder".to_string(),
+            format!("In {}", current_folder.get_uri()),
+            String::new(),
+            None,
+        )]
+    }
+}
+
+impl MenuProvider for FixtureMenu {
+    #[cfg(feature = "nautilus3")]
+    fn get_selection_items(
+        &self,
+        _window: *mut gtk_sys::GtkWidget,
+        selection: &Selection,
+    ) -> Vec<MenuItem> {
+        self.selection_items(selection)
+    }
+
+    #[cfg(feature = "nautilus3")]
+    fn get_background_items(
+        &self,
+        _window: *mut gtk_sys::GtkWidget,
+        current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        self.background_items(current_folder)
+    }
+
+    #[cfg(feature = "nautilus4")]
+    fn get_selection_items(&self, selection: &Selection) -> Vec<MenuItem> {
+        self.selection_items(selection)
+    }
+
+    #[cfg(feature = "nautilus4")]
+    fn get_background_items(&self, current_folder: &FileInfo) -> Vec<MenuItem> {
+        self.background_items(current_folder)
+    }
+}
+
+struct FixtureInfo;
+
+impl InfoProvider for FixtureInfo {
+    fn should_update_file_info(&self, file_info: &FileInfo) -> bool {
+        file_info.get_uri_scheme() == "file"
+    }
+
+    fn update_file_info(&self, file_info: &mut FileInfo) {
+        let name = file_info
+            .name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if name.starts_with("slow") {
+            thread::sleep(SLOW_UPDATE);
+        }
+        file_info.add_attribute("fixture_name", &name);
+    }
+}
+
+#[cfg(feature = "nautilus3")]
+struct FixturePages;
+
+#[cfg(feature = "nautilus3")]
+impl nautilus_extension::PropertyPageProvider for FixturePages {
+    fn get_pages(&self, files: &[FileInfo]) -> Vec<nautilus_extension::PropertyPage> {
+        use gobject_sys::{g_object_new, G_TYPE_OBJECT};
+        use std::ptr;
+
+        // the mock stores the widgets as plain objects, so no GTK is needed
+        files
+            .iter()
+            .map(|file| unsafe {
+                nautilus_extension::PropertyPage::new(
+                    file.get_uri(),
+                    g_object_new(G_TYPE_OBJECT, ptr::null()) as *mut _,
+                    g_object_new(G_TYPE_OBJECT, ptr::null()) as *mut _,
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "nautilus4")]
+struct FixtureModels;
+
+#[cfg(feature = "nautilus4")]
+impl nautilus_extension::PropertiesModelProvider for FixtureModels {
+    fn get_models(&self, files: &[FileInfo]) -> Vec<nautilus_extension::PropertiesModel> {
+        vec![nautilus_extension::PropertiesModel::new(
+            "Fixture",
+            vec![("Files".to_string(), files.len().to_string())],
+        )]
+    }
+}
+
+#[nautilus_extension]
+fn fixture_extension(module: *mut GTypeModule) -> Vec<NautilusModule> {
+    let mut extension = NautilusModule::new(module, "FixtureExtension");
+    extension
+        .add_menu_provider(FixtureMenu)
+        .add_column_provider(FixtureColumns)
+        .add_info_provider(FixtureInfo);
+    #[cfg(feature = "nautilus3")]
+    extension.add_property_page_provider(FixturePages);
+    #[cfg(feature = "nautilus4")]
+    extension.add_properties_model_provider(FixtureModels);
+
+    let mut columns_only = NautilusModule::new(module, "FixtureColumnsOnly");
+    columns_only.add_column_provider(FixtureColumns);
+
+    vec![extension, columns_only]
+}
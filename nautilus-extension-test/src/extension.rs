@@ -0,0 +1,141 @@
+use crate::glib_ffi::GType;
+use crate::gobject_ffi::GTypeModule;
+use crate::module::{load_module, EntryPoints};
+use crate::provider::Provider;
+use libc::c_int;
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::ffi::CString;
+use std::fmt;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    /// `dlopen()` failed, with the message from `dlerror()`.
+    Open(String),
+    /// The library does not export one of the Nautilus module entry points.
+    MissingSymbol(&'static str),
+    /// The `GTypeModule` could not be used.
+    NotLoaded,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Open(ref message) => write!(f, "cannot open extension: {}", message),
+            LoadError::MissingSymbol(name) => write!(f, "extension does not export {}", name),
+            LoadError::NotLoaded => write!(f, "extension module could not be loaded"),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+/// An extension library loaded the way Nautilus loads it.
+pub struct Extension {
+    path: PathBuf,
+    module: *mut GTypeModule,
+    types: Vec<GType>,
+    entry_points: EntryPoints,
+    shut_down: AtomicBool,
+}
+
+unsafe impl Send for Extension {}
+unsafe impl Sync for Extension {}
+
+lazy_static! {
+    static ref LOADED_EXTENSIONS: Mutex<HashMap<PathBuf, &'static Extension>> =
+        Mutex::new(HashMap::new());
+}
+
+impl Extension {
+    /// Open the library, call `nautilus_module_initialize` with a new `GTypeModule`, then read
+    /// the registered types from `nautilus_module_list_types`.
+    ///
+    /// As in Nautilus, the library stays loaded until the process exits. Loading the same path
+    /// again returns the same `Extension`, so tests running in parallel can share it.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<&'static Extension, LoadError> {
+        let path = path
+            .as_ref()
+            .canonicalize()
+            .map_err(|e| LoadError::Open(format!("{}: {}", path.as_ref().display(), e)))?;
+
+        let mut loaded_extensions = LOADED_EXTENSIONS.lock().unwrap();
+        if let Some(extension) = loaded_extensions.get(&path) {
+            return Ok(extension);
+        }
+
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        let (module, entry_points) = load_module(&c_path)?;
+
+        let types = unsafe {
+            let mut raw_types: *const GType = ptr::null();
+            let mut num_types: c_int = 0;
+            (entry_points.list_types)(&mut raw_types, &mut num_types);
+            if raw_types.is_null() {
+                Vec::new()
+            } else {
+                slice::from_raw_parts(raw_types, num_types as usize).to_vec()
+            }
+        };
+
+        let extension: &'static Extension = Box::leak(Box::new(Extension {
+            path: path.clone(),
+            module,
+            types,
+            entry_points,
+            shut_down: AtomicBool::new(false),
+        }));
+        loaded_extensions.insert(path, extension);
+
+        Ok(extension)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The `GTypeModule` passed to `nautilus_module_initialize`.
+    pub fn module(&self) -> *mut GTypeModule {
+        self.module
+    }
+
+    /// The types returned by `nautilus_module_list_types`.
+    pub fn types(&self) -> &[GType] {
+        &self.types
+    }
+
+    /// A new instance of each registered type, as Nautilus creates at startup.
+    pub fn providers(&self) -> Vec<Provider> {
+        self.types.iter().map(|&t| Provider::new(t)).collect()
+    }
+
+    /// Call `nautilus_module_shutdown`. Later calls do nothing.
+    pub fn shutdown(&self) {
+        if !self.shut_down.swap(true, Ordering::SeqCst) {
+            unsafe {
+                (self.entry_points.shutdown)();
+            }
+        }
+    }
+}
+
+/// Path of the `cdylib` that cargo built for the crate named `crate_name`, next to the running
+/// test executable in `target/<profile>/deps`, or else in `target/<profile>`.
+pub fn cdylib_path(crate_name: &str) -> PathBuf {
+    let file_name = format!("lib{}.so", crate_name.replace('-', "_"));
+
+    let mut dir = env::current_exe().unwrap();
+    dir.pop();
+    if dir.join(&file_name).exists() || !dir.ends_with("deps") {
+        return dir.join(file_name);
+    }
+    dir.pop();
+    dir.join(file_name)
+}
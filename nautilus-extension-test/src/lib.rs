@@ -0,0 +1,23 @@
+#![deny(bare_trait_objects)]
+
+extern crate glib_sys as glib_ffi;
+extern crate gobject_sys as gobject_ffi;
+#[macro_use]
+extern crate lazy_static;
+extern crate libc;
+extern crate nautilus_extension;
+extern crate nautilus_extension_sys as nautilus_ffi;
+
+pub use crate::extension::{cdylib_path, Extension, LoadError};
+pub use crate::provider::Provider;
+#[cfg(feature = "nautilus4")]
+pub use nautilus_extension::mock::MockPropertiesModel;
+#[cfg(feature = "nautilus3")]
+pub use nautilus_extension::mock::MockPropertyPage;
+pub use nautilus_extension::mock::{MockColumn, MockFile, MockMenuItem};
+pub use nautilus_extension::GType;
+pub use nautilus_ffi::NautilusOperationResult;
+
+mod extension;
+mod module;
+mod provider;
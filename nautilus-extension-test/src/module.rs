@@ -0,0 +1,140 @@
+use crate::extension::LoadError;
+use crate::glib_ffi::{gboolean, gpointer, GType, GFALSE, GTRUE};
+use crate::gobject_ffi::{
+    g_object_new, g_type_from_name, g_type_module_get_type, g_type_module_use,
+    g_type_register_static_simple, GTypeModule, GTypeModuleClass,
+};
+use libc::{c_char, c_int, c_void, dlerror, dlopen, dlsym, RTLD_LAZY, RTLD_LOCAL};
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::ptr;
+use std::sync::Once;
+
+const MODULE_TYPE_NAME: &[u8] = b"NautilusExtensionTestModule\0";
+
+/// The functions every Nautilus extension exports.
+#[derive(Clone, Copy)]
+pub struct EntryPoints {
+    pub initialize: unsafe extern "C" fn(*mut GTypeModule),
+    pub list_types: unsafe extern "C" fn(*mut *const GType, *mut c_int),
+    pub shutdown: unsafe extern "C" fn(),
+}
+
+/// A `GTypeModule` that opens an extension library when first used, like Nautilus's own
+/// `NautilusModule`.
+#[repr(C)]
+struct TestModule {
+    parent: GTypeModule,
+    loader: *mut Loader,
+}
+
+struct Loader {
+    path: CString,
+    result: Option<Result<EntryPoints, LoadError>>,
+}
+
+unsafe extern "C" fn test_module_load(module: *mut GTypeModule) -> gboolean {
+    let loader = &mut *(*(module as *mut TestModule)).loader;
+
+    match open_library(&loader.path) {
+        Ok(entry_points) => {
+            (entry_points.initialize)(module);
+            loader.result = Some(Ok(entry_points));
+            GTRUE
+        }
+        Err(error) => {
+            loader.result = Some(Err(error));
+            GFALSE
+        }
+    }
+}
+
+unsafe extern "C" fn test_module_unload(_module: *mut GTypeModule) {
+    // like Nautilus, keep the library resident: the types it registered stay valid
+}
+
+unsafe extern "C" fn test_module_class_init(class: gpointer, _class_data: gpointer) {
+    let module_class = class as *mut GTypeModuleClass;
+    (*module_class).load = Some(test_module_load);
+    (*module_class).unload = Some(test_module_unload);
+}
+
+fn test_module_get_type() -> GType {
+    static REGISTER: Once = Once::new();
+
+    REGISTER.call_once(|| unsafe {
+        g_type_register_static_simple(
+            g_type_module_get_type(),
+            MODULE_TYPE_NAME.as_ptr() as *const c_char,
+            mem::size_of::<GTypeModuleClass>() as u32,
+            Some(test_module_class_init),
+            mem::size_of::<TestModule>() as u32,
+            None,
+            0,
+        );
+    });
+
+    unsafe { g_type_from_name(MODULE_TYPE_NAME.as_ptr() as *const c_char) }
+}
+
+/// Create a module for the library at `path` and use it, which opens the library and calls
+/// `nautilus_module_initialize`. The module is never released.
+pub fn load_module(path: &CStr) -> Result<(*mut GTypeModule, EntryPoints), LoadError> {
+    unsafe {
+        let module = g_object_new(test_module_get_type(), ptr::null::<c_char>()) as *mut TestModule;
+        let loader = Box::into_raw(Box::new(Loader {
+            path: path.to_owned(),
+            result: None,
+        }));
+        (*module).loader = loader;
+
+        g_type_module_use(module as *mut GTypeModule);
+
+        match (*loader).result.take() {
+            Some(Ok(entry_points)) => Ok((module as *mut GTypeModule, entry_points)),
+            Some(Err(error)) => Err(error),
+            None => Err(LoadError::NotLoaded),
+        }
+    }
+}
+
+unsafe fn open_library(path: &CStr) -> Result<EntryPoints, LoadError> {
+    let library = dlopen(path.as_ptr(), RTLD_LAZY | RTLD_LOCAL);
+    if library.is_null() {
+        return Err(LoadError::Open(last_dl_error()));
+    }
+
+    Ok(EntryPoints {
+        initialize: mem::transmute::<*mut c_void, unsafe extern "C" fn(*mut GTypeModule)>(symbol(
+            library,
+            "nautilus_module_initialize",
+        )?),
+        list_types: mem::transmute::<
+            *mut c_void,
+            unsafe extern "C" fn(*mut *const GType, *mut c_int),
+        >(symbol(library, "nautilus_module_list_types")?),
+        shutdown: mem::transmute::<*mut c_void, unsafe extern "C" fn()>(symbol(
+            library,
+            "nautilus_module_shutdown",
+        )?),
+    })
+}
+
+unsafe fn symbol(library: *mut c_void, name: &'static str) -> Result<*mut c_void, LoadError> {
+    let c_name = CString::new(name).unwrap();
+    let symbol = dlsym(library, c_name.as_ptr());
+    if symbol.is_null() {
+        Err(LoadError::MissingSymbol(name))
+    } else {
+        Ok(symbol)
+    }
+}
+
+unsafe fn last_dl_error() -> String {
+    let error = dlerror();
+    if error.is_null() {
+        String::from("unknown error")
+    } else {
+        CStr::from_ptr(error).to_string_lossy().into_owned()
+    }
+}
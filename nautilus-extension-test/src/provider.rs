@@ -0,0 +1,301 @@
+use crate::glib_ffi::{g_free, g_list_append, g_list_free, gpointer, GList, GType};
+use crate::gobject_ffi::{
+    g_cclosure_marshal_generic, g_cclosure_new, g_closure_set_marshal, g_closure_unref,
+    g_object_new, g_object_unref, g_type_interface_peek, g_type_interfaces, g_type_name, GCallback,
+    GClosure, GObject, GTypeInstance,
+};
+use crate::nautilus_ffi::{
+    nautilus_column_provider_get_type, nautilus_info_provider_get_type,
+    nautilus_menu_provider_get_type, NautilusColumnProviderIface, NautilusInfoProvider,
+    NautilusInfoProviderIface, NautilusMenuProviderIface, NautilusOperationHandle,
+    NautilusOperationResult,
+};
+#[cfg(feature = "nautilus4")]
+use crate::nautilus_ffi::{
+    nautilus_properties_model_provider_get_type, NautilusPropertiesModelProvider,
+    NautilusPropertiesModelProviderIface,
+};
+#[cfg(feature = "nautilus3")]
+use crate::nautilus_ffi::{
+    nautilus_property_page_provider_get_type, NautilusPropertyPageProvider,
+    NautilusPropertyPageProviderIface,
+};
+use libc::{c_char, c_int, c_uint, c_void};
+use nautilus_extension::mock::{
+    columns_from_g_list, menu_items_from_g_list, MockColumn, MockFile, MockMenuItem,
+};
+#[cfg(feature = "nautilus4")]
+use nautilus_extension::mock::{models_from_g_list, MockPropertiesModel};
+#[cfg(feature = "nautilus3")]
+use nautilus_extension::mock::{pages_from_g_list, MockPropertyPage};
+use std::ffi::CStr;
+use std::mem;
+use std::ptr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// How long `Provider::update_file_info()` waits for a background update before cancelling it.
+pub const UPDATE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An instance of a type registered by an extension. Each method calls the matching
+/// interface vtable, or returns `None` if the type does not implement the interface.
+pub struct Provider {
+    object: *mut GObject,
+}
+
+impl Provider {
+    pub(crate) fn new(provider_type: GType) -> Provider {
+        Provider {
+            object: unsafe { g_object_new(provider_type, ptr::null::<c_char>()) },
+        }
+    }
+
+    pub fn type_name(&self) -> String {
+        unsafe { type_name(self.g_type()) }
+    }
+
+    /// Names of the interfaces the type implements.
+    pub fn interfaces(&self) -> Vec<String> {
+        unsafe {
+            let mut num_interfaces: c_uint = 0;
+            let raw_interfaces = g_type_interfaces(self.g_type(), &mut num_interfaces);
+            let interfaces = (0..num_interfaces as usize)
+                .map(|i| type_name(*raw_interfaces.add(i)))
+                .collect();
+            g_free(raw_interfaces as gpointer);
+            interfaces
+        }
+    }
+
+    pub fn raw_object(&self) -> *mut GObject {
+        self.object
+    }
+
+    pub fn get_file_items(&self, files: &[MockFile]) -> Option<Vec<MockMenuItem>> {
+        let iface =
+            self.iface::<NautilusMenuProviderIface>(unsafe { nautilus_menu_provider_get_type() })?;
+        let get_file_items = iface.get_file_items?;
+
+        unsafe {
+            let files_g_list = files_to_g_list(files);
+            #[cfg(feature = "nautilus3")]
+            let items = get_file_items(self.object as gpointer, ptr::null_mut(), files_g_list);
+            #[cfg(feature = "nautilus4")]
+            let items = get_file_items(self.object as gpointer, files_g_list);
+            g_list_free(files_g_list);
+            Some(menu_items_from_g_list(items))
+        }
+    }
+
+    pub fn get_background_items(&self, current_folder: &MockFile) -> Option<Vec<MockMenuItem>> {
+        let iface =
+            self.iface::<NautilusMenuProviderIface>(unsafe { nautilus_menu_provider_get_type() })?;
+        let get_background_items = iface.get_background_items?;
+
+        unsafe {
+            #[cfg(feature = "nautilus3")]
+            let items = get_background_items(
+                self.object as gpointer,
+                ptr::null_mut(),
+                current_folder.file_info().raw_file_info,
+            );
+            #[cfg(feature = "nautilus4")]
+            let items = get_background_items(
+                self.object as gpointer,
+                current_folder.file_info().raw_file_info,
+            );
+            Some(menu_items_from_g_list(items))
+        }
+    }
+
+    pub fn get_columns(&self) -> Option<Vec<MockColumn>> {
+        let iface = self
+            .iface::<NautilusColumnProviderIface>(unsafe { nautilus_column_provider_get_type() })?;
+        let get_columns = iface.get_columns?;
+
+        unsafe { Some(columns_from_g_list(get_columns(self.object as gpointer))) }
+    }
+
+    /// Property pages were removed in Nautilus 4.
+    #[cfg(feature = "nautilus3")]
+    pub fn get_pages(&self, files: &[MockFile]) -> Option<Vec<MockPropertyPage>> {
+        let iface = self.iface::<NautilusPropertyPageProviderIface>(unsafe {
+            nautilus_property_page_provider_get_type()
+        })?;
+        let get_pages = iface.get_pages?;
+
+        unsafe {
+            let files_g_list = files_to_g_list(files);
+            let pages = get_pages(
+                self.object as *mut NautilusPropertyPageProvider,
+                files_g_list,
+            );
+            g_list_free(files_g_list);
+            Some(pages_from_g_list(pages))
+        }
+    }
+
+    /// Properties models replace property pages in Nautilus 4.
+    #[cfg(feature = "nautilus4")]
+    pub fn get_models(&self, files: &[MockFile]) -> Option<Vec<MockPropertiesModel>> {
+        let iface = self.iface::<NautilusPropertiesModelProviderIface>(unsafe {
+            nautilus_properties_model_provider_get_type()
+        })?;
+        let get_models = iface.get_models?;
+
+        unsafe {
+            let files_g_list = files_to_g_list(files);
+            let models = get_models(
+                self.object as *mut NautilusPropertiesModelProvider,
+                files_g_list,
+            );
+            g_list_free(files_g_list);
+            Some(models_from_g_list(models))
+        }
+    }
+
+    /// Call `update_file_info` and, if the update continues in the background, wait for its
+    /// `update_complete` closure. After `UPDATE_TIMEOUT` the update is cancelled and
+    /// `NautilusOperationInProgress` is returned.
+    pub fn update_file_info(&self, file: &MockFile) -> Option<NautilusOperationResult> {
+        self.update_file_info_within(file, UPDATE_TIMEOUT)
+    }
+
+    /// Like `update_file_info()`, but cancels the update after `timeout`.
+    pub fn update_file_info_within(
+        &self,
+        file: &MockFile,
+        timeout: Duration,
+    ) -> Option<NautilusOperationResult> {
+        let iface =
+            self.iface::<NautilusInfoProviderIface>(unsafe { nautilus_info_provider_get_type() })?;
+        let update_file_info = iface.update_file_info?;
+
+        let completion = Arc::new(Completion::default());
+
+        unsafe {
+            let update_complete = update_complete_closure(completion.clone());
+            let mut handle: *mut NautilusOperationHandle = ptr::null_mut();
+
+            let result = update_file_info(
+                self.object as *mut NautilusInfoProvider,
+                file.file_info().raw_file_info,
+                update_complete,
+                &mut handle,
+            );
+
+            let result = match result {
+                NautilusOperationResult::NautilusOperationInProgress => {
+                    match completion.wait(timeout) {
+                        Some(result) => result,
+                        None => {
+                            if let Some(cancel_update) = iface.cancel_update {
+                                cancel_update(self.object as *mut NautilusInfoProvider, handle);
+                            }
+                            NautilusOperationResult::NautilusOperationInProgress
+                        }
+                    }
+                }
+                result => result,
+            };
+
+            g_closure_unref(update_complete);
+            Some(result)
+        }
+    }
+
+    fn g_type(&self) -> GType {
+        unsafe { (*(*(self.object as *mut GTypeInstance)).g_class).g_type }
+    }
+
+    fn iface<T>(&self, iface_type: GType) -> Option<&T> {
+        unsafe {
+            let class = (*(self.object as *mut GTypeInstance)).g_class;
+            let iface = g_type_interface_peek(class as gpointer, iface_type) as *const T;
+            iface.as_ref()
+        }
+    }
+}
+
+impl Drop for Provider {
+    fn drop(&mut self) {
+        unsafe {
+            g_object_unref(self.object);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Completion {
+    result: Mutex<Option<NautilusOperationResult>>,
+    completed: Condvar,
+}
+
+impl Completion {
+    fn complete(&self, result: NautilusOperationResult) {
+        *self.result.lock().unwrap() = Some(result);
+        self.completed.notify_all();
+    }
+
+    fn wait(&self, timeout: Duration) -> Option<NautilusOperationResult> {
+        let result = self.result.lock().unwrap();
+        let (result, _) = self
+            .completed
+            .wait_timeout_while(result, timeout, |result| result.is_none())
+            .unwrap();
+        *result
+    }
+}
+
+unsafe extern "C" fn update_complete_cb(
+    _provider: *mut NautilusInfoProvider,
+    _handle: *mut NautilusOperationHandle,
+    result: c_int,
+    user_data: gpointer,
+) {
+    let completion = &*(user_data as *const Completion);
+    completion.complete(match result {
+        0 => NautilusOperationResult::NautilusOperationComplete,
+        1 => NautilusOperationResult::NautilusOperationFailed,
+        _ => NautilusOperationResult::NautilusOperationInProgress,
+    });
+}
+
+unsafe extern "C" fn release_completion(data: gpointer, _closure: *mut GClosure) {
+    drop(Arc::from_raw(data as *const Completion));
+}
+
+/// Build the `update_complete` closure the way Nautilus does.
+unsafe fn update_complete_closure(completion: Arc<Completion>) -> *mut GClosure {
+    let callback: GCallback = Some(mem::transmute::<
+        unsafe extern "C" fn(
+            *mut NautilusInfoProvider,
+            *mut NautilusOperationHandle,
+            c_int,
+            gpointer,
+        ),
+        unsafe extern "C" fn(),
+    >(update_complete_cb));
+
+    let closure = g_cclosure_new(
+        callback,
+        Arc::into_raw(completion) as gpointer,
+        Some(release_completion),
+    );
+    g_closure_set_marshal(closure, Some(g_cclosure_marshal_generic));
+    closure
+}
+
+unsafe fn files_to_g_list(files: &[MockFile]) -> *mut GList {
+    let mut files_g_list = ptr::null_mut();
+    for file in files {
+        files_g_list = g_list_append(files_g_list, file.file_info().raw_file_info as *mut c_void);
+    }
+    files_g_list
+}
+
+unsafe fn type_name(g_type: GType) -> String {
+    CStr::from_ptr(g_type_name(g_type))
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,178 @@
+//! The fixture extension, loaded with `Extension::load` and driven through its vtables.
+
+use nautilus_extension_test::{
+    cdylib_path, Extension, LoadError, MockFile, NautilusOperationResult, Provider,
+};
+use nautilus_extension_test_fixture::SLOW_UPDATE;
+
+fn fixture() -> &'static Extension {
+    Extension::load(cdylib_path("nautilus_extension_test_fixture")).unwrap()
+}
+
+/// The provider of the type registered as `type_name`.
+fn provider(type_name: &str) -> Provider {
+    fixture()
+        .providers()
+        .into_iter()
+        .find(|provider| provider.type_name() == type_name)
+        .unwrap()
+}
+
+#[test]
+fn lists_registered_types() {
+    let extension = fixture();
+    assert_eq!(extension.types().len(), 2);
+    assert!(std::ptr::eq(extension, fixture()));
+
+    let names: Vec<String> = extension
+        .providers()
+        .iter()
+        .map(Provider::type_name)
+        .collect();
+    assert_eq!(names, ["FixtureExtension", "FixtureColumnsOnly"]);
+
+    let mut interfaces = provider("FixtureExtension").interfaces();
+    interfaces.sort();
+    #[cfg(feature = "nautilus3")]
+    let pages = "NautilusPropertyPageProvider";
+    #[cfg(feature = "nautilus4")]
+    let pages = "NautilusPropertiesModelProvider";
+    let mut expected = vec![
+        "NautilusColumnProvider",
+        "NautilusInfoProvider",
+        "NautilusMenuProvider",
+        pages,
+    ];
+    expected.sort_unstable();
+    assert_eq!(interfaces, expected);
+
+    assert_eq!(
+        provider("FixtureColumnsOnly").interfaces(),
+        ["NautilusColumnProvider"]
+    );
+}
+
+#[test]
+fn reports_missing_libraries() {
+    match Extension::load("/nonexistent/libmissing.so") {
+        Err(LoadError::Open(message)) => assert!(message.contains("/nonexistent/libmissing.so")),
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("loaded a missing library"),
+    }
+}
+
+#[test]
+fn dispatches_menu_calls() {
+    let provider = provider("FixtureExtension");
+    let files = [
+        MockFile::new("file:///tmp/a.txt"),
+        MockFile::new("file:///tmp/b.txt"),
+    ];
+
+    let items = provider.get_file_items(&files).unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].name, "Fixture::count");
+    assert_eq!(items[0].label, "2 files");
+    assert!(items[0].has_activate_handler);
+
+    items[0].activate();
+    assert_eq!(files[0].invalidations(), 1);
+    assert_eq!(files[1].invalidations(), 1);
+
+    let folder = MockFile::new("file:///tmp").directory(true);
+    let items = provider.get_background_items(&folder).unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].label, "In file:///tmp");
+    assert!(!items[0].has_activate_handler);
+}
+
+#[test]
+fn dispatches_column_calls() {
+    for type_name in &["FixtureExtension", "FixtureColumnsOnly"] {
+        let columns = provider(type_name).get_columns().unwrap();
+        assert_eq!(columns.len(), 1);
+        assert_eq!(columns[0].name, "Fixture::name_column");
+        assert_eq!(columns[0].attribute, "fixture_name");
+        assert_eq!(columns[0].description, "Name seen by the fixture");
+    }
+}
+
+#[test]
+fn returns_none_for_missing_interfaces() {
+    let provider = provider("FixtureColumnsOnly");
+    let file = MockFile::new("file:///tmp/a.txt");
+
+    assert!(provider
+        .get_file_items(std::slice::from_ref(&file))
+        .is_none());
+    assert!(provider.get_background_items(&file).is_none());
+    assert!(provider.update_file_info(&file).is_none());
+}
+
+#[test]
+fn waits_for_file_info_updates() {
+    let provider = provider("FixtureExtension");
+
+    let file = MockFile::new("file:///tmp/photo.jpg");
+    assert_eq!(
+        provider.update_file_info(&file),
+        Some(NautilusOperationResult::NautilusOperationComplete)
+    );
+    assert_eq!(file.attribute("fixture_name").as_deref(), Some("photo.jpg"));
+
+    // should_update_file_info() skips the update
+    let file = MockFile::new("trash:///photo.jpg");
+    assert_eq!(
+        provider.update_file_info(&file),
+        Some(NautilusOperationResult::NautilusOperationComplete)
+    );
+    assert_eq!(file.attribute("fixture_name"), None);
+
+    let file = MockFile::new("file:///tmp/slow.jpg");
+    assert_eq!(
+        provider.update_file_info_within(&file, SLOW_UPDATE * 4),
+        Some(NautilusOperationResult::NautilusOperationComplete)
+    );
+    assert_eq!(file.attribute("fixture_name").as_deref(), Some("slow.jpg"));
+}
+
+#[test]
+fn cancels_file_info_updates_after_timeout() {
+    let provider = provider("FixtureExtension");
+    let file = MockFile::new("file:///tmp/slow.jpg");
+
+    assert_eq!(
+        provider.update_file_info_within(&file, SLOW_UPDATE / 10),
+        Some(NautilusOperationResult::NautilusOperationInProgress)
+    );
+    assert_eq!(file.attribute("fixture_name"), None);
+}
+
+#[cfg(feature = "nautilus3")]
+#[test]
+fn dispatches_property_page_calls() {
+    let files = [
+        MockFile::new("file:///tmp/a.txt"),
+        MockFile::new("file:///tmp/b.txt"),
+    ];
+
+    let pages = provider("FixtureExtension").get_pages(&files).unwrap();
+    let names: Vec<&str> = pages.iter().map(|page| page.name.as_str()).collect();
+    assert_eq!(names, ["file:///tmp/a.txt", "file:///tmp/b.txt"]);
+    assert!(provider("FixtureColumnsOnly").get_pages(&files).is_none());
+}
+
+#[cfg(feature = "nautilus4")]
+#[test]
+fn dispatches_properties_model_calls() {
+    let files = [
+        MockFile::new("file:///tmp/a.txt"),
+        MockFile::new("file:///tmp/b.txt"),
+    ];
+
+    let models = provider("FixtureExtension").get_models(&files).unwrap();
+    assert_eq!(models.len(), 1);
+    assert_eq!(models[0].title(), "Fixture");
+    assert_eq!(models[0].items(), [("Files".to_string(), "2".to_string())]);
+    assert!(provider("FixtureColumnsOnly").get_models(&files).is_none());
+}
@@ -0,0 +1,30 @@
+//! `nautilus_module_shutdown` of the fixture extension. Shutting down drops the providers of
+//! the whole library, so this has a test binary of its own.
+
+use nautilus_extension_test::{cdylib_path, Extension, MockFile, NautilusOperationResult};
+
+#[test]
+fn drops_providers_on_shutdown() {
+    let extension = Extension::load(cdylib_path("nautilus_extension_test_fixture")).unwrap();
+    let providers = extension.providers();
+    assert_eq!(providers[0].get_columns().unwrap().len(), 1);
+
+    extension.shutdown();
+    extension.shutdown();
+
+    // the types stay registered, but their vtables have nothing left to call
+    let file = MockFile::new("file:///tmp/photo.jpg");
+    assert_eq!(providers[0].get_columns(), Some(Vec::new()));
+    assert_eq!(
+        providers[0]
+            .get_file_items(std::slice::from_ref(&file))
+            .unwrap()
+            .len(),
+        0
+    );
+    assert_eq!(
+        providers[0].update_file_info(&file),
+        Some(NautilusOperationResult::NautilusOperationComplete)
+    );
+    assert_eq!(file.attribute("fixture_name"), None);
+}
@@ -0,0 +1,16 @@
+//! Re-export the API version flags detected by nautilus-extension-sys, e.g. `nautilus_43`.
+
+use std::env;
+
+const API_FLAGS: [&str; 2] = ["nautilus_3_30", "nautilus_43"];
+
+fn main() {
+    for flag in API_FLAGS.iter() {
+        println!("cargo:rustc-check-cfg=cfg({})", flag);
+    }
+
+    let flags = env::var("DEP_NAUTILUS_EXTENSION_API_FLAGS").unwrap_or_default();
+    for flag in flags.split(',').filter(|flag| !flag.is_empty()) {
+        println!("cargo:rustc-cfg={}", flag);
+    }
+}
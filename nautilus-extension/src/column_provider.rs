@@ -1,5 +1,10 @@
-use crate::glib_ffi::{gpointer, GList};
+use crate::glib_ffi::{g_list_append, gpointer, GList};
+use crate::nautilus_ffi::nautilus_column_new;
+use crate::stats::{self, Callback, ProviderKind};
+use libc::c_void;
 use std::borrow::Cow;
+use std::ffi::CString;
+use std::ptr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Mutex;
 
@@ -28,10 +33,39 @@ impl Column {
 
 pub trait ColumnProvider: Send + Sync {
     fn get_columns(&self) -> Vec<Column>;
+
+    /// Called from `nautilus_module_shutdown()`, right before the provider is dropped.
+    fn shutdown(&self) {}
+}
+
+pub(crate) fn columns_to_g_list(columns: Vec<Column>) -> *mut GList {
+    let mut columns_g_list = ptr::null_mut();
+
+    for column in columns {
+        let name = CString::new(&column.name as &str).unwrap().into_raw();
+        let attribute = CString::new(&column.attribute as &str).unwrap().into_raw();
+        let label = CString::new(&column.label as &str).unwrap().into_raw();
+        let description = CString::new(&column.description as &str)
+            .unwrap()
+            .into_raw();
+
+        unsafe {
+            let column_c = nautilus_column_new(name, attribute, label, description);
+            columns_g_list = g_list_append(columns_g_list, column_c as *mut c_void);
+
+            // deallocate CStrings
+            let _ = CString::from_raw(name);
+            let _ = CString::from_raw(attribute);
+            let _ = CString::from_raw(label);
+            let _ = CString::from_raw(description);
+        }
+    }
+
+    columns_g_list
 }
 
 macro_rules! column_provider_iface {
-    ($iface_init_fn:ident, $get_columns_fn:ident, $rust_provider:ident, $set_rust_provider:ident) => {
+    ($index:expr, $iface_init_fn:ident, $get_columns_fn:ident, $rust_provider:ident, $set_rust_provider:ident, $shutdown_rust_provider:ident) => {
         /// # Safety
         ///
         /// This generated function is used as a Nautilus callback. Do not call directly.
@@ -46,46 +80,27 @@ macro_rules! column_provider_iface {
 
         #[no_mangle]
         pub extern "C" fn $get_columns_fn(_provider: gpointer) -> *mut GList {
-            use crate::glib_ffi::g_list_append;
-            use crate::nautilus_ffi::nautilus_column_new;
-            use libc::c_void;
-            use std::ffi::CString;
-            use std::ptr;
-
-            let mut columns_g_list = ptr::null_mut();
-
-            let columns = match *$rust_provider.lock().unwrap() {
-                Some(ref p) => p.get_columns(),
-                None => Vec::new(),
-            };
-
-            for column in columns {
-                let name = CString::new(&column.name as &str).unwrap().into_raw();
-                let attribute = CString::new(&column.attribute as &str).unwrap().into_raw();
-                let label = CString::new(&column.label as &str).unwrap().into_raw();
-                let description = CString::new(&column.description as &str)
-                    .unwrap()
-                    .into_raw();
-
-                unsafe {
-                    let column_c = nautilus_column_new(name, attribute, label, description);
-                    columns_g_list = g_list_append(columns_g_list, column_c as *mut c_void);
-
-                    // deallocate CStrings
-                    let _ = CString::from_raw(name);
-                    let _ = CString::from_raw(attribute);
-                    let _ = CString::from_raw(label);
-                    let _ = CString::from_raw(description);
+            let columns = stats::timed(ProviderKind::Column, $index, Callback::GetColumns, || {
+                match *$rust_provider.lock().unwrap() {
+                    Some(ref p) => p.get_columns(),
+                    None => Vec::new(),
                 }
-            }
+            });
 
-            columns_g_list
+            columns_to_g_list(columns)
         }
 
         pub fn $set_rust_provider(column_provider: Box<dyn ColumnProvider>) {
             *$rust_provider.lock().unwrap() = Some(column_provider);
         }
 
+        pub fn $shutdown_rust_provider() {
+            let provider = $rust_provider.lock().unwrap().take();
+            if let Some(provider) = provider {
+                provider.shutdown();
+            }
+        }
+
         lazy_static! {
             static ref $rust_provider: Mutex<Option<Box<dyn ColumnProvider>>> = Mutex::new(None);
         }
@@ -93,16 +108,16 @@ macro_rules! column_provider_iface {
 }
 
 // Let library consumer add up to 10 ColumnProviders, should be more than enough. Each has its own Vec of columns.
-#[rustfmt::skip] column_provider_iface!(column_provider_iface_init_0, column_provider_get_columns_0, COLUMN_PROVIDER_0, set_column_provider_0);
-#[rustfmt::skip] column_provider_iface!(column_provider_iface_init_1, column_provider_get_columns_1, COLUMN_PROVIDER_1, set_column_provider_1);
-#[rustfmt::skip] column_provider_iface!(column_provider_iface_init_2, column_provider_get_columns_2, COLUMN_PROVIDER_2, set_column_provider_2);
-#[rustfmt::skip] column_provider_iface!(column_provider_iface_init_3, column_provider_get_columns_3, COLUMN_PROVIDER_3, set_column_provider_3);
-#[rustfmt::skip] column_provider_iface!(column_provider_iface_init_4, column_provider_get_columns_4, COLUMN_PROVIDER_4, set_column_provider_4);
-#[rustfmt::skip] column_provider_iface!(column_provider_iface_init_5, column_provider_get_columns_5, COLUMN_PROVIDER_5, set_column_provider_5);
-#[rustfmt::skip] column_provider_iface!(column_provider_iface_init_6, column_provider_get_columns_6, COLUMN_PROVIDER_6, set_column_provider_6);
-#[rustfmt::skip] column_provider_iface!(column_provider_iface_init_7, column_provider_get_columns_7, COLUMN_PROVIDER_7, set_column_provider_7);
-#[rustfmt::skip] column_provider_iface!(column_provider_iface_init_8, column_provider_get_columns_8, COLUMN_PROVIDER_8, set_column_provider_8);
-#[rustfmt::skip] column_provider_iface!(column_provider_iface_init_9, column_provider_get_columns_9, COLUMN_PROVIDER_9, set_column_provider_9);
+#[rustfmt::skip] column_provider_iface!(0, column_provider_iface_init_0, column_provider_get_columns_0, COLUMN_PROVIDER_0, set_column_provider_0, shutdown_column_provider_0);
+#[rustfmt::skip] column_provider_iface!(1, column_provider_iface_init_1, column_provider_get_columns_1, COLUMN_PROVIDER_1, set_column_provider_1, shutdown_column_provider_1);
+#[rustfmt::skip] column_provider_iface!(2, column_provider_iface_init_2, column_provider_get_columns_2, COLUMN_PROVIDER_2, set_column_provider_2, shutdown_column_provider_2);
+#[rustfmt::skip] column_provider_iface!(3, column_provider_iface_init_3, column_provider_get_columns_3, COLUMN_PROVIDER_3, set_column_provider_3, shutdown_column_provider_3);
+#[rustfmt::skip] column_provider_iface!(4, column_provider_iface_init_4, column_provider_get_columns_4, COLUMN_PROVIDER_4, set_column_provider_4, shutdown_column_provider_4);
+#[rustfmt::skip] column_provider_iface!(5, column_provider_iface_init_5, column_provider_get_columns_5, COLUMN_PROVIDER_5, set_column_provider_5, shutdown_column_provider_5);
+#[rustfmt::skip] column_provider_iface!(6, column_provider_iface_init_6, column_provider_get_columns_6, COLUMN_PROVIDER_6, set_column_provider_6, shutdown_column_provider_6);
+#[rustfmt::skip] column_provider_iface!(7, column_provider_iface_init_7, column_provider_get_columns_7, COLUMN_PROVIDER_7, set_column_provider_7, shutdown_column_provider_7);
+#[rustfmt::skip] column_provider_iface!(8, column_provider_iface_init_8, column_provider_get_columns_8, COLUMN_PROVIDER_8, set_column_provider_8, shutdown_column_provider_8);
+#[rustfmt::skip] column_provider_iface!(9, column_provider_iface_init_9, column_provider_get_columns_9, COLUMN_PROVIDER_9, set_column_provider_9, shutdown_column_provider_9);
 
 pub fn column_provider_iface_externs() -> Vec<unsafe extern "C" fn(gpointer, gpointer)> {
     vec![
@@ -134,6 +149,21 @@ pub fn rust_column_provider_setters() -> Vec<fn(Box<dyn ColumnProvider>)> {
     ]
 }
 
+pub fn rust_column_provider_shutdowns() -> Vec<fn()> {
+    vec![
+        shutdown_column_provider_0,
+        shutdown_column_provider_1,
+        shutdown_column_provider_2,
+        shutdown_column_provider_3,
+        shutdown_column_provider_4,
+        shutdown_column_provider_5,
+        shutdown_column_provider_6,
+        shutdown_column_provider_7,
+        shutdown_column_provider_8,
+        shutdown_column_provider_9,
+    ]
+}
+
 static NEXT_COLUMN_PROVIDER_IFACE_INDEX: AtomicUsize = AtomicUsize::new(0);
 
 pub fn take_next_column_provider_iface_index() -> usize {
@@ -0,0 +1,590 @@
+//! Menu items that run a program on the files they were activated for:
+//!
+//! ```ignore
+//! let mut item = MenuItem::new("Images::resize", "Resize", "Resize the images", None);
+//! item.set_command(Command::new("gimp %F"));
+//! ```
+//!
+//! `Exec` field codes of the freedesktop desktop entry specification are expanded:
+//!
+//! * `%f`, `%F`: the local path of the file, or of each file
+//! * `%u`, `%U`: the URI of the file, or of each file
+//! * `%d`, `%D`: the folder containing the file, or each file
+//! * `%n`, `%N`: the name of the file, or of each file
+//! * `%%`: a literal `%`
+//!
+//! As the specification requires, file codes must not be quoted: inside quotes, as in
+//! `sh -c 'echo %f'`, a file name could be run as code, so such `Exec` lines are rejected, and so
+//! are codes in the script of a shell's `-c`. `%f`, `%u`, `%d` and `%n` may be part of a longer
+//! argument, as in `--file=%f`, while list codes must stand alone. With `%f`, `%u`, `%d` or `%n`,
+//! the program is run once for every file. Files without a local path are passed by URI. Other
+//! codes are removed.
+
+use crate::glib_ffi::{g_error_free, g_shell_parse_argv, g_strfreev, gpointer, GError};
+use crate::gobject_ffi::{g_signal_connect_data, GClosure, GObject};
+use crate::info_provider::FileInfo;
+use crate::logging;
+use crate::nautilus_ffi::NautilusMenuItem;
+use crate::selection::Selection;
+use libc::{c_char, c_int};
+use std::borrow::Cow;
+use std::error;
+use std::ffi::{CStr, CString, OsString};
+use std::fmt;
+use std::io;
+use std::iter;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::ptr;
+use std::thread;
+#[cfg(any(feature = "declarative", feature = "scripting"))]
+use std::time::{Duration, Instant};
+
+/// How often a command run with a timeout is checked for having exited.
+#[cfg(any(feature = "declarative", feature = "scripting"))]
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    exec: Cow<'static, str>,
+}
+
+#[derive(Debug)]
+pub enum CommandError {
+    /// The `Exec` line is not a valid command line, e.g. because of an unmatched quote or a file
+    /// code inside a longer argument.
+    Parse(String),
+    /// The expanded command line is empty.
+    Empty,
+    Spawn(io::Error),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CommandError::Parse(ref message) => write!(f, "cannot parse command: {}", message),
+            CommandError::Empty => f.write_str("empty command"),
+            CommandError::Spawn(ref error) => write!(f, "cannot run command: {}", error),
+        }
+    }
+}
+
+impl error::Error for CommandError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            CommandError::Spawn(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Command {
+    /// A command run with the `Exec` line `exec`, e.g. `gimp %F`.
+    pub fn new<S: Into<Cow<'static, str>>>(exec: S) -> Command {
+        Command { exec: exec.into() }
+    }
+
+    pub fn exec(&self) -> &str {
+        &self.exec
+    }
+
+    /// Check that the `Exec` line can be parsed and uses its file codes safely, without
+    /// expanding it, e.g. when loading an action.
+    pub fn check(&self) -> Result<(), CommandError> {
+        self.args().map(|_| ())
+    }
+
+    /// The command lines to run for `files`, with the field codes expanded.
+    pub fn expand(&self, files: &[FileInfo]) -> Result<Vec<Vec<OsString>>, CommandError> {
+        let args = self.args()?;
+        let once_per_file = args
+            .iter()
+            .any(|arg| codes(arg).any(|code| matches!(field(code), Some((_, false)))));
+
+        let command_lines = if once_per_file {
+            files
+                .iter()
+                .map(|file| expand_args(&args, Some(file), files))
+                .collect()
+        } else {
+            vec![expand_args(&args, None, files)]
+        };
+        Ok(command_lines)
+    }
+
+    fn args(&self) -> Result<Vec<String>, CommandError> {
+        let args = parse_argv(&self.exec)?;
+        if args.is_empty() {
+            return Err(CommandError::Empty);
+        }
+        check_file_codes(&self.exec, &args)?;
+        Ok(args)
+    }
+
+    /// Start the command for `files` in `current_folder`, without waiting for it. A non-zero
+    /// exit status is logged as a warning.
+    pub fn spawn(
+        &self,
+        files: &[FileInfo],
+        current_folder: Option<&Path>,
+    ) -> Result<(), CommandError> {
+        for command_line in self.expand(files)? {
+            let (program, args) = match command_line.split_first() {
+                Some(split) => split,
+                None => return Err(CommandError::Empty),
+            };
+
+            let mut command = process::Command::new(program);
+            command.args(args);
+            if let Some(current_folder) = current_folder {
+                command.current_dir(current_folder);
+            }
+            let mut child = command.spawn().map_err(CommandError::Spawn)?;
+
+            let exec = self.exec.clone();
+            thread::spawn(move || match child.wait() {
+                Ok(status) if !status.success() => {
+                    logging::warning(&format!("command `{}` failed: {}", exec, status))
+                }
+                Ok(_) => {}
+                Err(error) => logging::warning(&format!("command `{}`: {}", exec, error)),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The standard output of `command`, killed if it has not exited and closed its output within
+/// `timeout`. Errors name the `Exec` line `exec` the command was expanded from.
+#[cfg(any(feature = "declarative", feature = "scripting"))]
+pub(crate) fn output_within(
+    exec: &str,
+    command: &mut process::Command,
+    timeout: Duration,
+) -> Result<Vec<u8>, String> {
+    use std::io::Read;
+    use std::sync::mpsc;
+
+    let deadline = Instant::now() + timeout;
+    let mut child = command
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::null())
+        .spawn()
+        .map_err(|error| format!("cannot run `{}`: {}", exec, error))?;
+
+    // read on another thread, so a full pipe cannot keep the command from exiting
+    let (sender, receiver) = mpsc::channel();
+    let mut stdout = child.stdout.take();
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(ref mut stdout) = stdout {
+            let _ = stdout.read_to_end(&mut output);
+        }
+        let _ = sender.send(output);
+    });
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(EXIT_POLL_INTERVAL),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("`{}` timed out after {:?}", exec, timeout));
+            }
+            Err(error) => return Err(format!("`{}`: {}", exec, error)),
+        }
+    };
+    if !status.success() {
+        return Err(format!("`{}` failed: {}", exec, status));
+    }
+    // a process the command left running may keep its output open
+    receiver
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .map_err(|_| format!("`{}` timed out after {:?}", exec, timeout))
+}
+
+/// A command item's files, kept until its menu item is finalized.
+struct Activation {
+    command: Command,
+    files: Selection,
+    current_folder: Option<PathBuf>,
+}
+
+/// Run `command` on `files` when `raw_menuitem` is activated, in the folder from
+/// `current_folder()`.
+pub(crate) unsafe fn connect_command(
+    raw_menuitem: *mut NautilusMenuItem,
+    command: &Command,
+    files: &[FileInfo],
+    background: bool,
+) {
+    let files = Selection::new(files);
+    let activation = Box::new(Activation {
+        command: command.clone(),
+        current_folder: current_folder(&files, background),
+        files,
+    });
+
+    let activate_name = CString::new("activate").unwrap();
+    g_signal_connect_data(
+        raw_menuitem as *mut GObject,
+        activate_name.as_ptr(),
+        Some(mem::transmute::<
+            unsafe extern "C" fn(*mut GObject, gpointer),
+            unsafe extern "C" fn(),
+        >(activate_command)),
+        Box::into_raw(activation) as gpointer,
+        Some(free_activation),
+        0,
+    );
+}
+
+unsafe extern "C" fn activate_command(_menuitem: *mut GObject, user_data: gpointer) {
+    let activation = &*(user_data as *const Activation);
+    let result = activation.command.spawn(
+        activation.files.files(),
+        activation.current_folder.as_deref(),
+    );
+    if let Err(error) = result {
+        logging::warning(&format!("command `{}`: {}", activation.command.exec, error));
+    }
+}
+
+unsafe extern "C" fn free_activation(data: gpointer, _closure: *mut GClosure) {
+    drop(Box::from_raw(data as *mut Activation));
+}
+
+/// The local folder a command runs in. Background items have the current folder. Nautilus does
+/// not tell file items which folder is shown, so they use the folder of the files when they
+/// are all in the same one, and otherwise, e.g. in search results, Nautilus's own.
+fn current_folder(files: &Selection, background: bool) -> Option<PathBuf> {
+    let first = files.files().first()?;
+    if background {
+        first.path()
+    } else if files.same_directory() {
+        first.path()?.parent().map(Path::to_path_buf)
+    } else {
+        None
+    }
+}
+
+fn parse_argv(exec: &str) -> Result<Vec<String>, CommandError> {
+    let exec = CString::new(exec).map_err(|e| CommandError::Parse(e.to_string()))?;
+    let mut argc: c_int = 0;
+    let mut argv: *mut *mut c_char = ptr::null_mut();
+    let mut error: *mut GError = ptr::null_mut();
+
+    unsafe {
+        if g_shell_parse_argv(exec.as_ptr(), &mut argc, &mut argv, &mut error) == 0 {
+            let message = CStr::from_ptr((*error).message)
+                .to_string_lossy()
+                .into_owned();
+            g_error_free(error);
+            return Err(CommandError::Parse(message));
+        }
+        let args = (0..argc as usize)
+            .map(|i| CStr::from_ptr(*argv.add(i)).to_string_lossy().into_owned())
+            .collect();
+        g_strfreev(argv);
+        Ok(args)
+    }
+}
+
+/// What a file code expands to for one file.
+type Field = fn(&FileInfo) -> OsString;
+
+/// Shells whose `-c` option takes a script.
+const SHELLS: [&str; 9] = [
+    "sh", "bash", "dash", "zsh", "ksh", "mksh", "fish", "csh", "tcsh",
+];
+
+/// The field of the file code `%code`, and whether it is a list code.
+fn field(code: char) -> Option<(Field, bool)> {
+    let field: Field = match code {
+        'f' | 'F' => path_or_uri,
+        'u' | 'U' => uri,
+        'd' | 'D' => folder,
+        'n' | 'N' => name,
+        _ => return None,
+    };
+    Some((field, code.is_uppercase()))
+}
+
+/// The field of a file code standing alone as `arg`, and whether it is a list code.
+fn file_code(arg: &str) -> Option<(Field, bool)> {
+    match arg.strip_prefix('%')?.chars().collect::<Vec<_>>()[..] {
+        [code] => field(code),
+        _ => None,
+    }
+}
+
+/// The codes of `arg`: the characters following a `%`, except for `%%`.
+fn codes(arg: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = arg.chars();
+    iter::from_fn(move || loop {
+        if chars.next()? == '%' {
+            match chars.next()? {
+                '%' => continue,
+                code => return Some(code),
+            }
+        }
+    })
+}
+
+/// Reject file codes where a file name could change the meaning of an argument: inside quotes
+/// and in the script of `sh -c`. List codes must also stand alone.
+fn check_file_codes(exec: &str, args: &[String]) -> Result<(), CommandError> {
+    check_quoted_codes(exec)?;
+    for arg in args.iter().filter(|arg| file_code(arg).is_none()) {
+        if let Some(code) = codes(arg).find(|&code| matches!(field(code), Some((_, true)))) {
+            return Err(CommandError::Parse(format!(
+                "%{} must be a whole argument, not part of {:?}",
+                code, arg
+            )));
+        }
+    }
+    if let Some(script) = shell_script(args) {
+        if let Some(code) = codes(script).find(|&code| field(code).is_some()) {
+            return Err(CommandError::Parse(format!(
+                "%{} cannot be part of the script run by {}",
+                code, args[0]
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Reject file codes inside single or double quotes of the unparsed `exec`, which the desktop
+/// entry specification forbids.
+fn check_quoted_codes(exec: &str) -> Result<(), CommandError> {
+    let mut quote = None;
+    let mut chars = exec.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '\\') => {
+                chars.next();
+            }
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            // inside double quotes, only these characters are escaped, the backslash is kept
+            // before any other
+            (Some('"'), '\\') => {
+                chars.next_if(|&c| matches!(c, '$' | '`' | '"' | '\\' | '\n'));
+            }
+            (_, '%') => match chars.next() {
+                Some(code) if quote.is_some() && field(code).is_some() => {
+                    return Err(CommandError::Parse(format!(
+                        "%{} must not be quoted, as in {:?}",
+                        code, exec
+                    )));
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The script of `args` if they run a shell with `-c`.
+fn shell_script(args: &[String]) -> Option<&String> {
+    let program = Path::new(args.first()?).file_name()?.to_str()?;
+    if !SHELLS.contains(&program) {
+        return None;
+    }
+    let option = args[1..]
+        .iter()
+        .position(|arg| arg.starts_with('-') && !arg.starts_with("--") && arg.contains('c'))?;
+    args.get(option + 2)
+}
+
+/// Expand the codes of `args`, with `file` for the single file codes and `files` for the lists.
+fn expand_args(args: &[String], file: Option<&FileInfo>, files: &[FileInfo]) -> Vec<OsString> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        match file_code(arg) {
+            Some((field, true)) => expanded.extend(files.iter().map(field)),
+            Some((field, false)) => expanded.extend(file.map(field)),
+            None => expanded.push(expand_codes(arg, file)),
+        }
+    }
+    expanded
+}
+
+/// Expand `%%` and the single file codes with `file` in an argument that is not a file code.
+fn expand_codes(arg: &str, file: Option<&FileInfo>) -> OsString {
+    let mut expanded = OsString::new();
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c.encode_utf8(&mut [0; 4]));
+            continue;
+        }
+        match chars.next() {
+            Some('%') => expanded.push("%"),
+            // deprecated or unsupported codes expand to nothing
+            Some(code) => {
+                if let (Some((field, false)), Some(file)) = (field(code), file) {
+                    expanded.push(field(file));
+                }
+            }
+            None => continue,
+        }
+    }
+    expanded
+}
+
+fn path_or_uri(file: &FileInfo) -> OsString {
+    file.path()
+        .map_or_else(|| uri(file), PathBuf::into_os_string)
+}
+
+fn uri(file: &FileInfo) -> OsString {
+    OsString::from(file.get_uri())
+}
+
+fn folder(file: &FileInfo) -> OsString {
+    match file.path() {
+        Some(path) => path
+            .parent()
+            .map_or_else(OsString::new, |p| p.as_os_str().to_os_string()),
+        None => OsString::from(file.get_parent_uri()),
+    }
+}
+
+fn name(file: &FileInfo) -> OsString {
+    file.name().unwrap_or_default()
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::{current_folder, Command, CommandError};
+    use crate::info_provider::FileInfo;
+    use crate::mock::MockFile;
+    use crate::selection::Selection;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn expands_field_codes() {
+        let photo = MockFile::new("file:///tmp/my%20photo.jpg");
+        let notes = MockFile::new("file:///home/user/notes.txt");
+        let files: Vec<FileInfo> = vec![photo.file_info(), notes.file_info()];
+
+        let command = Command::new("gimp --new %F");
+        assert_eq!(
+            command.expand(&files).unwrap(),
+            vec![args(&[
+                "gimp",
+                "--new",
+                "/tmp/my photo.jpg",
+                "/home/user/notes.txt"
+            ])]
+        );
+
+        let command = Command::new("convert %f -resize 50%% %n");
+        assert_eq!(
+            command.expand(&files).unwrap(),
+            vec![
+                args(&[
+                    "convert",
+                    "/tmp/my photo.jpg",
+                    "-resize",
+                    "50%",
+                    "my photo.jpg"
+                ]),
+                args(&[
+                    "convert",
+                    "/home/user/notes.txt",
+                    "-resize",
+                    "50%",
+                    "notes.txt"
+                ]),
+            ]
+        );
+
+        assert!(Command::new("open 'unterminated").expand(&files).is_err());
+    }
+
+    #[test]
+    fn rejects_unsafe_codes() {
+        // a file named `$(rm -rf ~).txt` would run as part of the script
+        let file = MockFile::new("file:///tmp/%24(rm%20-rf%20~).txt");
+        let files = [file.file_info()];
+
+        for (exec, error) in &[
+            ("sh -c 'echo \"%n in %d\"'", "must not be quoted"),
+            ("sh -c 'cat %F'", "must not be quoted"),
+            ("app '%f'", "must not be quoted"),
+            ("app \"--file=\\%f\"", "must not be quoted"),
+            ("/bin/sh -ec cat\\ %f", "script run by /bin/sh"),
+            ("bash -c %f", "script run by bash"),
+            ("app --files=%F", "whole argument"),
+        ] {
+            let command = Command::new(*exec);
+            assert!(command.check().is_err(), "{}", exec);
+            match command.expand(&files) {
+                Err(CommandError::Parse(message)) => assert!(message.contains(error), "{}", exec),
+                result => panic!("{}: {:?}", exec, result),
+            }
+        }
+
+        assert_eq!(
+            Command::new("echo %%f '%%n' %n").expand(&files).unwrap(),
+            vec![args(&["echo", "%f", "%n", "$(rm -rf ~).txt"])]
+        );
+        assert_eq!(
+            Command::new("app --file=%f --name=%n%%")
+                .expand(&files)
+                .unwrap(),
+            vec![args(&[
+                "app",
+                "--file=/tmp/$(rm -rf ~).txt",
+                "--name=$(rm -rf ~).txt%"
+            ])]
+        );
+        assert_eq!(
+            Command::new("sh -c 'cat \"$1\"' sh %f")
+                .expand(&files)
+                .unwrap(),
+            vec![args(&[
+                "sh",
+                "-c",
+                "cat \"$1\"",
+                "sh",
+                "/tmp/$(rm -rf ~).txt"
+            ])]
+        );
+    }
+
+    #[test]
+    fn runs_in_the_current_folder() {
+        let photo = MockFile::new("file:///tmp/photo.jpg");
+        let notes = MockFile::new("file:///home/user/notes.txt");
+        let folder = MockFile::new("file:///home/user").directory(true);
+        let remote = MockFile::new("sftp://host/notes.txt");
+        let selection = |files: &[&MockFile]| {
+            let files: Vec<FileInfo> = files.iter().map(|f| f.file_info()).collect();
+            Selection::new(&files)
+        };
+
+        assert_eq!(
+            current_folder(&selection(&[&folder]), true),
+            Some(PathBuf::from("/home/user"))
+        );
+        assert_eq!(
+            current_folder(&selection(&[&notes]), false),
+            Some(PathBuf::from("/home/user"))
+        );
+        // files from several folders, as in search results
+        assert_eq!(current_folder(&selection(&[&photo, &notes]), false), None);
+        assert_eq!(current_folder(&selection(&[&remote]), false), None);
+    }
+}
@@ -0,0 +1,218 @@
+//! Conditions that decide whether a `MenuItem` is shown for a selection, checked before the items
+//! are handed to Nautilus:
+//!
+//! ```ignore
+//! let mut item = MenuItem::new("Photos::rotate", "Rotate", "Rotate the photos", None);
+//! item.show_when(Condition::scheme("file").and(Condition::mime("image/*")));
+//! ```
+//!
+//! File conditions hold when every selected file matches them. Background items are checked
+//! against the current folder alone.
+
+use crate::glib_ffi::g_pattern_match_simple;
+use crate::info_provider::{FileInfo, FileType};
+use std::borrow::Cow;
+use std::ffi::CString;
+use std::fmt;
+use std::ops::{Bound, Not, RangeBounds};
+use std::slice;
+use std::sync::Arc;
+
+type Predicate = dyn Fn(&[FileInfo]) -> bool + Send + Sync;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Condition {
+    kind: Kind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Kind {
+    Scheme(Cow<'static, str>),
+    Mime(Cow<'static, str>),
+    FileType(FileType),
+    Count(Bound<usize>, Bound<usize>),
+    CanWrite,
+    Custom(Custom),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    Each(Box<Condition>),
+}
+
+#[derive(Clone)]
+struct Custom(Arc<Predicate>);
+
+impl fmt::Debug for Custom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Custom")
+    }
+}
+
+impl PartialEq for Custom {
+    /// Predicates compare by address.
+    fn eq(&self, other: &Custom) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Condition {
+    /// Every file has the URI scheme `scheme`, e.g. `file`.
+    pub fn scheme<S: Into<Cow<'static, str>>>(scheme: S) -> Condition {
+        Condition::new(Kind::Scheme(scheme.into()))
+    }
+
+    /// Every file has a MIME type matching `pattern`. Patterns with `*` or `?` are matched as
+    /// globs against the file's MIME type, like `image/*`. Others also match subtypes, so
+    /// `text/plain` matches `text/x-python`. A pattern with a NUL byte matches nothing.
+    pub fn mime<S: Into<Cow<'static, str>>>(pattern: S) -> Condition {
+        Condition::new(Kind::Mime(pattern.into()))
+    }
+
+    /// Every file is of type `file_type`.
+    pub fn file_type(file_type: FileType) -> Condition {
+        Condition::new(Kind::FileType(file_type))
+    }
+
+    /// The number of files is in `range`, e.g. `1..=1` for a single file or `2..` for several.
+    pub fn count<R: RangeBounds<usize>>(range: R) -> Condition {
+        Condition::new(Kind::Count(
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        ))
+    }
+
+    /// Every file is writable.
+    pub fn can_write() -> Condition {
+        Condition::new(Kind::CanWrite)
+    }
+
+    /// `predicate` returns `true` for the files.
+    pub fn custom<F>(predicate: F) -> Condition
+    where
+        F: Fn(&[FileInfo]) -> bool + Send + Sync + 'static,
+    {
+        Condition::new(Kind::Custom(Custom(Arc::new(predicate))))
+    }
+
+    /// Both `self` and `other` hold.
+    pub fn and(self, other: Condition) -> Condition {
+        match self.kind {
+            Kind::All(mut conditions) => {
+                conditions.push(other);
+                Condition::new(Kind::All(conditions))
+            }
+            kind => Condition::new(Kind::All(vec![Condition { kind }, other])),
+        }
+    }
+
+    /// `self` or `other` holds.
+    pub fn or(self, other: Condition) -> Condition {
+        match self.kind {
+            Kind::Any(mut conditions) => {
+                conditions.push(other);
+                Condition::new(Kind::Any(conditions))
+            }
+            kind => Condition::new(Kind::Any(vec![Condition { kind }, other])),
+        }
+    }
+
+    /// `condition` holds for every file on its own, e.g. with
+    /// `Condition::each(Condition::mime("image/png").or(Condition::mime("image/jpeg")))` for a
+    /// selection of PNG and JPEG images, which `or` alone would reject.
+    pub fn each(condition: Condition) -> Condition {
+        Condition::new(Kind::Each(Box::new(condition)))
+    }
+
+    pub fn matches(&self, files: &[FileInfo]) -> bool {
+        match self.kind {
+            Kind::Scheme(ref scheme) => files.iter().all(|f| f.get_uri_scheme() == *scheme),
+            Kind::Mime(ref pattern) => files.iter().all(|f| mime_matches(f, pattern)),
+            Kind::FileType(file_type) => files.iter().all(|f| f.get_file_type() == file_type),
+            Kind::Count(start, end) => (start, end).contains(&files.len()),
+            Kind::CanWrite => files.iter().all(FileInfo::can_write),
+            Kind::Custom(ref custom) => (custom.0)(files),
+            Kind::All(ref conditions) => conditions.iter().all(|c| c.matches(files)),
+            Kind::Any(ref conditions) => conditions.iter().any(|c| c.matches(files)),
+            Kind::Not(ref condition) => !condition.matches(files),
+            Kind::Each(ref condition) => files
+                .iter()
+                .all(|file| condition.matches(slice::from_ref(file))),
+        }
+    }
+
+    fn new(kind: Kind) -> Condition {
+        Condition { kind }
+    }
+}
+
+impl Not for Condition {
+    type Output = Condition;
+
+    fn not(self) -> Condition {
+        Condition::new(Kind::Not(Box::new(self)))
+    }
+}
+
+/// Patterns with a NUL byte cannot reach GLib and match nothing.
+fn mime_matches(file: &FileInfo, pattern: &str) -> bool {
+    if pattern.contains('\0') {
+        return false;
+    }
+    if !pattern.contains(&['*', '?'][..]) {
+        return file.is_mime_type(pattern);
+    }
+
+    let (pattern, mime_type) = match (CString::new(pattern), CString::new(file.get_mime_type())) {
+        (Ok(pattern), Ok(mime_type)) => (pattern, mime_type),
+        _ => return false,
+    };
+    unsafe { g_pattern_match_simple(pattern.as_ptr(), mime_type.as_ptr()) != 0 }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::Condition;
+    use crate::info_provider::{FileInfo, FileType};
+    use crate::mock::MockFile;
+
+    #[test]
+    fn matches_selection() {
+        let photo = MockFile::new("file:///tmp/photo.jpg").mime_type("image/jpeg");
+        let script = MockFile::new("file:///tmp/run.py")
+            .mime_type("text/x-python")
+            .can_write(false);
+        let remote = MockFile::new("sftp://host/photo.png").mime_type("image/png");
+        let files = |files: &[&MockFile]| -> Vec<FileInfo> {
+            files.iter().map(|f| f.file_info()).collect()
+        };
+
+        let local_images = Condition::scheme("file").and(Condition::mime("image/*"));
+        assert!(local_images.matches(&files(&[&photo])));
+        assert!(!local_images.matches(&files(&[&photo, &remote])));
+        assert!(Condition::mime("image/*").matches(&files(&[&photo, &remote])));
+
+        let single = Condition::count(1..=1);
+        assert!(single.matches(&files(&[&script])));
+        assert!(!single.matches(&files(&[&photo, &script])));
+        assert!((!Condition::can_write()).matches(&files(&[&script])));
+        assert!(Condition::file_type(FileType::Regular)
+            .or(Condition::custom(|files| files.is_empty()))
+            .matches(&files(&[&photo, &script])));
+
+        let jpeg_or_png = Condition::mime("image/jpeg").or(Condition::mime("image/png"));
+        assert!(!jpeg_or_png.matches(&files(&[&photo, &remote])));
+        let each = Condition::each(jpeg_or_png);
+        assert!(each.matches(&files(&[&photo, &remote])));
+        assert!(!each.matches(&files(&[&photo, &script])));
+    }
+
+    #[test]
+    fn invalid_mime_patterns_do_not_match() {
+        let photo = MockFile::new("file:///tmp/photo.jpg").mime_type("image/jpeg");
+        let files = [photo.file_info()];
+
+        assert!(!Condition::mime("image/jpeg\0").matches(&files));
+        assert!(!Condition::mime("image/*\0").matches(&files));
+        assert!((!Condition::mime("image/*\0")).matches(&files));
+    }
+}
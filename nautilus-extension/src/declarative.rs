@@ -0,0 +1,970 @@
+//! Extensions described in a TOML file rather than written in Rust, with the `declarative`
+//! feature:
+//!
+//! ```toml
+//! name = "Photos"
+//!
+//! [[item]]
+//! name = "rotate"
+//! label = "Rotate"
+//! icon = "object-rotate-right"
+//! command = "mogrify -rotate 90 %F"
+//! when = { scheme = "file", mime = ["image/jpeg", "image/png"] }
+//!
+//! [[item]]
+//! name = "tools"
+//! label = "Photo tools"
+//!
+//! [[item.item]]
+//! name = "strip"
+//! label = "Strip metadata"
+//! command = "exiftool -all= %F"
+//!
+//! [[column]]
+//! name = "Photos::camera"
+//! attribute = "camera"
+//! label = "Camera"
+//!
+//! [[info]]
+//! attribute = "camera"
+//! command = "exiftool -s3 -Model %f"
+//!
+//! [[info]]
+//! attribute = "frame"
+//! regex = '^IMG_(\d+)'
+//! value = '\1'
+//! ```
+//!
+//! ```ignore
+//! fn register_provider(module: *mut GTypeModule) -> GType {
+//!     let extension = DeclarativeExtension::load("/usr/share/photos/extension.toml").unwrap();
+//!     NautilusModule::new(module, "Photos")
+//!         .add_declarative_extension(extension)
+//!         .register()
+//! }
+//! ```
+//!
+//! The file has a `name`, which prefixes the names of its menu items, and arrays of `item`,
+//! `column` and `info` tables:
+//!
+//! * `item`: `name` and `label`, an optional `tip` and `icon`, and either a `command`, an
+//!   `Exec` line as taken by `Command`, or the `item`s of a submenu. The item is shown when its
+//!   `when` condition holds. Top-level items are shown for the selected files unless
+//!   `files = false`, and for the current folder with `background = true`.
+//! * `when`: `scheme` and `mime`, a value or a list of values one of which each file matches,
+//!   `type` (`regular`, `directory`, `symlink`, `special`, `shortcut` or `mountable`), `count`,
+//!   `min_count`, `max_count` no less than `min_count`, `writable`, `not` with a condition and
+//!   `any` with a list of them. All the keys given must hold.
+//! * `column`: `name`, `attribute` and `label`, and an optional `description`.
+//! * `info`: the `attribute` to set, and either a `command` whose first line of output is the
+//!   value, or a `regex` matched against the file name. The value of a `regex` is `value` with
+//!   references like `\1` expanded, the whole match by default. A command still running after
+//!   `COMMAND_TIMEOUT` is killed, and the attribute is not set.
+//!
+//! Only TOML is read, JSON definitions are not supported. Unknown keys and values of the wrong
+//! type are errors, reported with their line and column. A file given to `load()` is checked
+//! for changes at most every `file_manager_actions::RESCAN_INTERVAL`, and read again when it
+//! changed. If the new contents are not valid, a warning is logged and the previous definition
+//! is kept. Nautilus asks for the columns once, so a new column only shows after a restart.
+
+use crate::column_provider::{Column, ColumnProvider};
+use crate::command::{self, Command};
+use crate::condition::Condition;
+use crate::file_manager_actions::RESCAN_INTERVAL;
+use crate::glib_ffi::{
+    g_error_free, g_free, g_match_info_expand_references, g_match_info_free,
+    g_regex_check_replacement, g_regex_match, g_regex_new, g_regex_unref, GError, GMatchInfo,
+    GRegex,
+};
+#[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+use crate::gtk_ffi::GtkWidget;
+use crate::info_provider::{FileInfo, FileType, InfoProvider};
+use crate::logging;
+use crate::menu_provider::{Menu, MenuItem, MenuProvider};
+use std::collections::HashSet;
+use std::error;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::ptr;
+use std::slice;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use toml_edit::{ImDocument, Item, TableLike};
+
+const ROOT_KEYS: [&str; 4] = ["name", "item", "column", "info"];
+const ITEM_KEYS: [&str; 9] = [
+    "name",
+    "label",
+    "tip",
+    "icon",
+    "command",
+    "when",
+    "item",
+    "files",
+    "background",
+];
+/// The keys of submenu items, which are shown wherever their menu is.
+const SUBMENU_ITEM_KEYS: [&str; 7] = ["name", "label", "tip", "icon", "command", "when", "item"];
+const CONDITION_KEYS: [&str; 9] = [
+    "scheme",
+    "mime",
+    "type",
+    "count",
+    "min_count",
+    "max_count",
+    "writable",
+    "not",
+    "any",
+];
+const COLUMN_KEYS: [&str; 4] = ["name", "attribute", "label", "description"];
+const INFO_KEYS: [&str; 4] = ["attribute", "command", "regex", "value"];
+
+/// How long the command of an `info` rule may run for a file before it is killed.
+pub const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A menu, column and info provider defined by a TOML file. Clones share the definition.
+#[derive(Clone)]
+pub struct DeclarativeExtension {
+    inner: Arc<Inner>,
+    rescan_interval: Duration,
+    command_timeout: Duration,
+}
+
+struct Inner {
+    path: Option<PathBuf>,
+    loaded: Mutex<Loaded>,
+}
+
+struct Loaded {
+    checked: Option<Instant>,
+    modified: Option<SystemTime>,
+    definition: Arc<Definition>,
+}
+
+struct Definition {
+    file_items: Vec<MenuItem>,
+    background_items: Vec<MenuItem>,
+    columns: Vec<Column>,
+    rules: Vec<Rule>,
+}
+
+/// How the value of an attribute is found.
+struct Rule {
+    attribute: String,
+    kind: RuleKind,
+}
+
+enum RuleKind {
+    Command(Command),
+    Regex(Regex, String),
+}
+
+#[derive(Debug)]
+pub enum DefinitionError {
+    Io(io::Error),
+    /// The file is not valid TOML, or does not describe an extension.
+    Invalid {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for DefinitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DefinitionError::Io(ref error) => write!(f, "cannot read definition: {}", error),
+            DefinitionError::Invalid {
+                line,
+                column,
+                ref message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+        }
+    }
+}
+
+impl error::Error for DefinitionError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            DefinitionError::Io(ref error) => Some(error),
+            DefinitionError::Invalid { .. } => None,
+        }
+    }
+}
+
+impl DeclarativeExtension {
+    /// The extension defined by the file at `path`, which is read again when it changes.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<DeclarativeExtension, DefinitionError> {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified(&path);
+        let definition = read(&path)?;
+        Ok(DeclarativeExtension::with_definition(
+            Some(path),
+            modified,
+            definition,
+        ))
+    }
+
+    /// The extension defined by `text`, e.g. a file included with `include_str!`.
+    pub fn parse(text: &str) -> Result<DeclarativeExtension, DefinitionError> {
+        let definition = Definition::parse(text)?;
+        Ok(DeclarativeExtension::with_definition(
+            None, None, definition,
+        ))
+    }
+
+    fn with_definition(
+        path: Option<PathBuf>,
+        modified: Option<SystemTime>,
+        definition: Definition,
+    ) -> DeclarativeExtension {
+        DeclarativeExtension {
+            inner: Arc::new(Inner {
+                path,
+                loaded: Mutex::new(Loaded {
+                    checked: Some(Instant::now()),
+                    modified,
+                    definition: Arc::new(definition),
+                }),
+            }),
+            rescan_interval: RESCAN_INTERVAL,
+            command_timeout: COMMAND_TIMEOUT,
+        }
+    }
+
+    /// Check the file for changes at most every `interval` instead of
+    /// `file_manager_actions::RESCAN_INTERVAL`.
+    pub fn rescan_interval(mut self, interval: Duration) -> DeclarativeExtension {
+        self.rescan_interval = interval;
+        self
+    }
+
+    /// Kill the commands of `info` rules after `timeout` instead of `COMMAND_TIMEOUT`.
+    pub fn command_timeout(mut self, timeout: Duration) -> DeclarativeExtension {
+        self.command_timeout = timeout;
+        self
+    }
+
+    /// The current definition, read again first if the file has changed, unless it was checked
+    /// within the interval. The file is read without holding the lock, so other calls are not
+    /// blocked.
+    fn definition(&self) -> Arc<Definition> {
+        let path = match self.inner.path {
+            Some(ref path) => path,
+            None => return self.inner.loaded.lock().unwrap().definition.clone(),
+        };
+        let now = Instant::now();
+        let old_modified = {
+            let loaded = self.inner.loaded.lock().unwrap();
+            let recent = |checked: Instant| now.duration_since(checked) < self.rescan_interval;
+            if loaded.checked.map_or(false, recent) {
+                return loaded.definition.clone();
+            }
+            loaded.modified
+        };
+
+        let modified = modified(path);
+        let reread = if modified != old_modified {
+            Some(read(path))
+        } else {
+            None
+        };
+
+        let mut loaded = self.inner.loaded.lock().unwrap();
+        match reread {
+            Some(Ok(definition)) => loaded.definition = Arc::new(definition),
+            Some(Err(error)) => logging::warning(&format!("{}: {}", path.display(), error)),
+            None => {}
+        }
+        loaded.modified = modified;
+        loaded.checked = Some(now);
+        loaded.definition.clone()
+    }
+}
+
+impl MenuProvider for DeclarativeExtension {
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    fn get_file_items(&self, _window: *mut GtkWidget, _files: &[FileInfo]) -> Vec<MenuItem> {
+        self.definition().file_items.clone()
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    fn get_file_items(
+        &self,
+        _window: Option<&crate::gtk::Window>,
+        _files: &[FileInfo],
+    ) -> Vec<MenuItem> {
+        self.definition().file_items.clone()
+    }
+
+    #[cfg(nautilus_43)]
+    fn get_file_items(&self, _files: &[FileInfo]) -> Vec<MenuItem> {
+        self.definition().file_items.clone()
+    }
+
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    fn get_background_items(
+        &self,
+        _window: *mut GtkWidget,
+        _current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        self.definition().background_items.clone()
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    fn get_background_items(
+        &self,
+        _window: Option<&crate::gtk::Window>,
+        _current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        self.definition().background_items.clone()
+    }
+
+    #[cfg(nautilus_43)]
+    fn get_background_items(&self, _current_folder: &FileInfo) -> Vec<MenuItem> {
+        self.definition().background_items.clone()
+    }
+}
+
+impl ColumnProvider for DeclarativeExtension {
+    fn get_columns(&self) -> Vec<Column> {
+        self.definition()
+            .columns
+            .iter()
+            .map(|column| {
+                Column::new(
+                    column.name.clone(),
+                    column.attribute.clone(),
+                    column.label.clone(),
+                    column.description.clone(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl InfoProvider for DeclarativeExtension {
+    fn should_update_file_info(&self, _file_info: &FileInfo) -> bool {
+        !self.definition().rules.is_empty()
+    }
+
+    fn update_file_info(&self, file_info: &mut FileInfo) {
+        for rule in &self.definition().rules {
+            match rule.value(file_info, self.command_timeout) {
+                Ok(Some(value)) => {
+                    file_info.add_attribute(&rule.attribute, &value);
+                }
+                Ok(None) => {}
+                Err(message) => logging::warning(&format!("{}: {}", rule.attribute, message)),
+            }
+        }
+    }
+}
+
+impl Definition {
+    fn parse(text: &str) -> Result<Definition, DefinitionError> {
+        let document = ImDocument::parse(text)
+            .map_err(|error| invalid(text, error.span(), error.message().trim_end().to_string()))?;
+        let root = Fields::new(text, document.as_table(), None, &ROOT_KEYS)?;
+        let prefix = root.required_string("name")?;
+
+        let mut definition = Definition {
+            file_items: Vec::new(),
+            background_items: Vec::new(),
+            columns: Vec::new(),
+            rules: Vec::new(),
+        };
+
+        let mut names = HashSet::new();
+        for fields in root.tables("item", &ITEM_KEYS)? {
+            let item = parse_item(&fields, &prefix, &mut names)?;
+            if fields.boolean("background")?.unwrap_or(false) {
+                definition.background_items.push(item.clone());
+            }
+            if fields.boolean("files")?.unwrap_or(true) {
+                definition.file_items.push(item);
+            }
+        }
+
+        for fields in root.tables("column", &COLUMN_KEYS)? {
+            definition.columns.push(Column::new(
+                fields.required_string("name")?,
+                fields.required_string("attribute")?,
+                fields.required_string("label")?,
+                fields.string("description")?.unwrap_or_default(),
+            ));
+        }
+
+        for fields in root.tables("info", &INFO_KEYS)? {
+            definition.rules.push(parse_rule(&fields)?);
+        }
+
+        Ok(definition)
+    }
+}
+
+impl Rule {
+    /// The value of the attribute for `file`, or `None` if the regex does not match.
+    fn value(&self, file: &FileInfo, timeout: Duration) -> Result<Option<String>, String> {
+        match self.kind {
+            RuleKind::Command(ref command) => command_output(command, file, timeout).map(Some),
+            RuleKind::Regex(ref regex, ref value) => {
+                let name = file.name().unwrap_or_default();
+                Ok(regex.expand(&name.to_string_lossy(), value))
+            }
+        }
+    }
+}
+
+fn parse_item(
+    fields: &Fields,
+    prefix: &str,
+    names: &mut HashSet<String>,
+) -> Result<MenuItem, DefinitionError> {
+    let name = fields.required_string("name")?;
+    if !names.insert(name.clone()) {
+        return Err(fields.error("name", format!("duplicate item name `{}`", name)));
+    }
+
+    let mut item = MenuItem::new(
+        format!("{}::{}", prefix, name),
+        fields.required_string("label")?,
+        fields.string("tip")?.unwrap_or_default(),
+        fields.string("icon")?,
+    );
+    if let Some(when) = fields.table("when", &CONDITION_KEYS)? {
+        if let Some(condition) = parse_condition(&when)? {
+            item.show_when(condition);
+        }
+    }
+
+    let submenu = fields.tables("item", &SUBMENU_ITEM_KEYS)?;
+    match (fields.string("command")?, submenu.is_empty()) {
+        (Some(_), false) => {
+            return Err(fields.error("command", "an item with a submenu has no `command`"));
+        }
+        (Some(exec), true) => {
+            let command = Command::new(exec);
+            // check the command line now, rather than when the item is activated
+            command
+                .check()
+                .map_err(|error| fields.error("command", error.to_string()))?;
+            item.set_command(command);
+        }
+        (None, false) => {
+            let items = submenu
+                .iter()
+                .map(|fields| parse_item(fields, prefix, names))
+                .collect::<Result<Vec<MenuItem>, DefinitionError>>()?;
+            item.set_submenu(&Menu::new(&items));
+        }
+        (None, true) => {
+            return Err(fields.table_error("an item needs a `command` or the `item`s of a submenu"));
+        }
+    }
+    Ok(item)
+}
+
+/// The condition of `fields`, or `None` if it has no keys.
+fn parse_condition(fields: &Fields) -> Result<Option<Condition>, DefinitionError> {
+    let mut conditions = Vec::new();
+
+    // each file matches one of the values, rather than all files the same one
+    if let Some(schemes) = fields.strings("scheme")? {
+        conditions.extend(any(schemes.into_iter().map(Condition::scheme)).map(Condition::each));
+    }
+    if let Some(patterns) = fields.strings("mime")? {
+        conditions.extend(any(patterns.into_iter().map(Condition::mime)).map(Condition::each));
+    }
+    if let Some(file_type) = fields.string("type")? {
+        let file_type = match file_type.as_str() {
+            "regular" => FileType::Regular,
+            "directory" => FileType::Directory,
+            "symlink" => FileType::SymbolicLink,
+            "special" => FileType::Special,
+            "shortcut" => FileType::Shortcut,
+            "mountable" => FileType::Mountable,
+            other => {
+                return Err(fields.error("type", format!("unknown file type `{}`", other)));
+            }
+        };
+        conditions.push(Condition::file_type(file_type));
+    }
+    if let Some(count) = fields.count("count")? {
+        conditions.push(Condition::count(count..=count));
+    }
+    match (fields.count("min_count")?, fields.count("max_count")?) {
+        (Some(min), Some(max)) if min > max => {
+            return Err(fields.error(
+                "max_count",
+                format!("`max_count` {} is less than `min_count` {}", max, min),
+            ));
+        }
+        (Some(min), Some(max)) => conditions.push(Condition::count(min..=max)),
+        (Some(min), None) => conditions.push(Condition::count(min..)),
+        (None, Some(max)) => conditions.push(Condition::count(..=max)),
+        (None, None) => {}
+    }
+    match fields.boolean("writable")? {
+        Some(true) => conditions.push(Condition::can_write()),
+        Some(false) => conditions.push(!Condition::can_write()),
+        None => {}
+    }
+    if let Some(not) = fields.table("not", &CONDITION_KEYS)? {
+        conditions.extend(parse_condition(&not)?.map(|condition| !condition));
+    }
+    let alternatives = fields
+        .tables("any", &CONDITION_KEYS)?
+        .iter()
+        .map(parse_condition)
+        .collect::<Result<Vec<Option<Condition>>, DefinitionError>>()?;
+    conditions.extend(any(alternatives.into_iter().flatten()));
+
+    let mut conditions = conditions.into_iter();
+    Ok(conditions
+        .next()
+        .map(|first| conditions.fold(first, Condition::and)))
+}
+
+fn any<I: Iterator<Item = Condition>>(mut conditions: I) -> Option<Condition> {
+    let first = conditions.next()?;
+    Some(conditions.fold(first, Condition::or))
+}
+
+fn parse_rule(fields: &Fields) -> Result<Rule, DefinitionError> {
+    let attribute = fields.required_string("attribute")?;
+    let value = fields.string("value")?;
+
+    let kind = match (fields.string("command")?, fields.string("regex")?) {
+        (Some(_), Some(_)) => {
+            return Err(fields.error("regex", "a rule has a `command` or a `regex`, not both"));
+        }
+        (Some(exec), None) => {
+            if value.is_some() {
+                return Err(fields.error("value", "`value` is only used with `regex`"));
+            }
+            let command = Command::new(exec);
+            command
+                .check()
+                .map_err(|error| fields.error("command", error.to_string()))?;
+            RuleKind::Command(command)
+        }
+        (None, Some(pattern)) => {
+            let regex = Regex::new(&pattern).map_err(|message| fields.error("regex", message))?;
+            let value = value.unwrap_or_else(|| String::from("\\0"));
+            check_replacement(&value).map_err(|message| fields.error("value", message))?;
+            RuleKind::Regex(regex, value)
+        }
+        (None, None) => return Err(fields.table_error("a rule needs a `command` or a `regex`")),
+    };
+    Ok(Rule { attribute, kind })
+}
+
+/// A table of the definition, whose keys have been checked.
+struct Fields<'a> {
+    text: &'a str,
+    table: &'a dyn TableLike,
+    span: Option<Range<usize>>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(
+        text: &'a str,
+        table: &'a dyn TableLike,
+        span: Option<Range<usize>>,
+        keys: &[&str],
+    ) -> Result<Fields<'a>, DefinitionError> {
+        if let Some((key, _)) = table.iter().find(|(key, _)| !keys.contains(key)) {
+            let span = table.key(key).and_then(|key| key.span());
+            return Err(invalid(text, span, format!("unknown key `{}`", key)));
+        }
+        Ok(Fields { text, table, span })
+    }
+
+    fn string(&self, key: &str) -> Result<Option<String>, DefinitionError> {
+        self.value(key, "a string", |item| item.as_str().map(String::from))
+    }
+
+    fn required_string(&self, key: &str) -> Result<String, DefinitionError> {
+        self.string(key)?
+            .ok_or_else(|| self.table_error(format!("missing key `{}`", key)))
+    }
+
+    fn boolean(&self, key: &str) -> Result<Option<bool>, DefinitionError> {
+        self.value(key, "a boolean", Item::as_bool)
+    }
+
+    fn count(&self, key: &str) -> Result<Option<usize>, DefinitionError> {
+        self.value(key, "a number of files", |item| {
+            item.as_integer().and_then(|n| usize::try_from(n).ok())
+        })
+    }
+
+    /// A string, or a list of them.
+    fn strings(&self, key: &str) -> Result<Option<Vec<String>>, DefinitionError> {
+        self.value(key, "a string or a list of strings", |item| {
+            match item.as_array() {
+                Some(array) => array
+                    .iter()
+                    .map(|value| value.as_str().map(String::from))
+                    .collect(),
+                None => item.as_str().map(|value| vec![value.to_string()]),
+            }
+        })
+    }
+
+    fn table(&self, key: &str, keys: &[&str]) -> Result<Option<Fields<'a>>, DefinitionError> {
+        let item = match self.table.get(key) {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        match item.as_table_like() {
+            Some(table) => Fields::new(self.text, table, item.span(), keys).map(Some),
+            None => Err(self.expected(key, item, "a table")),
+        }
+    }
+
+    /// An array of tables, like `[[item]]`, or a list of inline tables.
+    fn tables(&self, key: &str, keys: &[&str]) -> Result<Vec<Fields<'a>>, DefinitionError> {
+        let item = match self.table.get(key) {
+            Some(item) => item,
+            None => return Ok(Vec::new()),
+        };
+        if let Some(tables) = item.as_array_of_tables() {
+            return tables
+                .iter()
+                .map(|table| Fields::new(self.text, table, table.span(), keys))
+                .collect();
+        }
+        match item.as_array() {
+            Some(array) => array
+                .iter()
+                .map(|value| match value.as_inline_table() {
+                    Some(table) => Fields::new(self.text, table, value.span(), keys),
+                    None => Err(invalid(
+                        self.text,
+                        value.span(),
+                        format!("expected a table, found {}", value.type_name()),
+                    )),
+                })
+                .collect(),
+            None => Err(self.expected(key, item, "an array of tables")),
+        }
+    }
+
+    fn value<T, F: FnOnce(&Item) -> Option<T>>(
+        &self,
+        key: &str,
+        expected: &str,
+        convert: F,
+    ) -> Result<Option<T>, DefinitionError> {
+        match self.table.get(key) {
+            Some(item) => convert(item)
+                .map(Some)
+                .ok_or_else(|| self.expected(key, item, expected)),
+            None => Ok(None),
+        }
+    }
+
+    fn expected(&self, key: &str, item: &Item, expected: &str) -> DefinitionError {
+        self.error(
+            key,
+            format!(
+                "`{}`: expected {}, found {}",
+                key,
+                expected,
+                item.type_name()
+            ),
+        )
+    }
+
+    /// An error about the value of `key`.
+    fn error<S: Into<String>>(&self, key: &str, message: S) -> DefinitionError {
+        let span = self.table.get(key).and_then(Item::span);
+        invalid(
+            self.text,
+            span.or_else(|| self.span.clone()),
+            message.into(),
+        )
+    }
+
+    /// An error about the table as a whole.
+    fn table_error<S: Into<String>>(&self, message: S) -> DefinitionError {
+        invalid(self.text, self.span.clone(), message.into())
+    }
+}
+
+/// An error at the start of `span` in `text`, or at its beginning.
+fn invalid(text: &str, span: Option<Range<usize>>, message: String) -> DefinitionError {
+    let offset = span.map_or(0, |span| span.start).min(text.len());
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    DefinitionError::Invalid {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+        message,
+    }
+}
+
+fn read(path: &Path) -> Result<Definition, DefinitionError> {
+    let text = fs::read_to_string(path).map_err(DefinitionError::Io)?;
+    Definition::parse(&text)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The first line of the output of `command` run for `file`, within `timeout`.
+fn command_output(command: &Command, file: &FileInfo, timeout: Duration) -> Result<String, String> {
+    let command_line = command
+        .expand(slice::from_ref(file))
+        .map_err(|error| error.to_string())?;
+    let (program, args) = match command_line.first().and_then(|args| args.split_first()) {
+        Some(split) => split,
+        None => return Err(String::from("empty command")),
+    };
+
+    let mut process = process::Command::new(program);
+    process.args(args);
+    if let Some(folder) = file.path().as_deref().and_then(Path::parent) {
+        process.current_dir(folder);
+    }
+    let stdout = command::output_within(command.exec(), &mut process, timeout)?;
+    let stdout = String::from_utf8_lossy(&stdout);
+    Ok(stdout.lines().next().unwrap_or("").trim().to_string())
+}
+
+/// A compiled `GRegex`, which may be used from any thread once created.
+struct Regex(*mut GRegex);
+
+unsafe impl Send for Regex {}
+unsafe impl Sync for Regex {}
+
+impl Regex {
+    fn new(pattern: &str) -> Result<Regex, String> {
+        let pattern = CString::new(pattern).map_err(|error| error.to_string())?;
+        let mut error: *mut GError = ptr::null_mut();
+        let regex = unsafe { g_regex_new(pattern.as_ptr(), 0, 0, &mut error) };
+        if regex.is_null() {
+            return Err(unsafe { take_message(error) });
+        }
+        Ok(Regex(regex))
+    }
+
+    /// `replacement` with its references expanded, if the regex matches `value`.
+    fn expand(&self, value: &str, replacement: &str) -> Option<String> {
+        let value = CString::new(value).ok()?;
+        let replacement = CString::new(replacement).ok()?;
+        let mut match_info: *mut GMatchInfo = ptr::null_mut();
+        unsafe {
+            let matched = g_regex_match(self.0, value.as_ptr(), 0, &mut match_info) != 0;
+            let mut expanded = None;
+            if matched {
+                let raw = g_match_info_expand_references(
+                    match_info,
+                    replacement.as_ptr(),
+                    ptr::null_mut(),
+                );
+                if !raw.is_null() {
+                    expanded = Some(CStr::from_ptr(raw).to_string_lossy().into_owned());
+                    g_free(raw as *mut _);
+                }
+            }
+            g_match_info_free(match_info);
+            expanded
+        }
+    }
+}
+
+impl Drop for Regex {
+    fn drop(&mut self) {
+        unsafe {
+            g_regex_unref(self.0);
+        }
+    }
+}
+
+fn check_replacement(replacement: &str) -> Result<(), String> {
+    let replacement = CString::new(replacement).map_err(|error| error.to_string())?;
+    let mut error: *mut GError = ptr::null_mut();
+    unsafe {
+        if g_regex_check_replacement(replacement.as_ptr(), ptr::null_mut(), &mut error) == 0 {
+            return Err(take_message(error));
+        }
+    }
+    Ok(())
+}
+
+unsafe fn take_message(error: *mut GError) -> String {
+    let message = CStr::from_ptr((*error).message)
+        .to_string_lossy()
+        .into_owned();
+    g_error_free(error);
+    message
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::{DeclarativeExtension, DefinitionError};
+    use crate::info_provider::FileInfo;
+    use crate::mock::MockFile;
+    use crate::ColumnProvider;
+    use crate::InfoProvider;
+    use std::env;
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn builds_providers_from_definition() {
+        let extension = DeclarativeExtension::parse(
+            r#"
+name = "Photos"
+
+[[item]]
+name = "rotate"
+label = "Rotate"
+command = "mogrify -rotate 90 %F"
+when = { mime = "image/*", count = 1 }
+
+[[item]]
+name = "tools"
+label = "Tools"
+background = true
+
+[[item.item]]
+name = "list"
+label = "List"
+command = "ls -l %F"
+
+[[column]]
+name = "Photos::frame"
+attribute = "frame"
+label = "Frame"
+
+[[info]]
+attribute = "frame"
+regex = '^IMG_0*(\d+)'
+value = 'frame \1'
+"#,
+        )
+        .unwrap();
+
+        let definition = extension.definition();
+        let rotate = &definition.file_items[0];
+        assert_eq!(rotate.name(), "Photos::rotate");
+        let photo = MockFile::new("file:///tmp/IMG_0042.jpg").mime_type("image/jpeg");
+        let notes = MockFile::new("file:///tmp/notes.txt");
+        assert!(rotate.condition().unwrap().matches(&[photo.file_info()]));
+        let files: Vec<FileInfo> = vec![photo.file_info(), notes.file_info()];
+        assert!(!rotate.condition().unwrap().matches(&files));
+        assert_eq!(definition.background_items.len(), 1);
+        assert_eq!(
+            definition.background_items[0].submenu().unwrap().items()[0].name(),
+            "Photos::list"
+        );
+
+        assert_eq!(extension.get_columns()[0].attribute, "frame");
+        let mut file_info = photo.file_info();
+        extension.update_file_info(&mut file_info);
+        assert_eq!(file_info.attributes["frame"], "frame 42");
+
+        let error = DeclarativeExtension::parse(
+            "name = \"Photos\"\n\n[[item]]\nname = \"rotate\"\nlable = \"Rotate\"\n",
+        )
+        .err()
+        .unwrap();
+        match error {
+            DefinitionError::Invalid {
+                line,
+                column,
+                message,
+            } => {
+                assert_eq!((line, column), (5, 1));
+                assert_eq!(message, "unknown key `lable`");
+            }
+            DefinitionError::Io(error) => panic!("{}", error),
+        }
+    }
+
+    #[test]
+    fn matches_lists_per_file() {
+        let extension = DeclarativeExtension::parse(
+            r#"
+name = "Photos"
+
+[[item]]
+name = "rotate"
+label = "Rotate"
+command = "mogrify -rotate 90 %F"
+when = { scheme = ["file", "sftp"], mime = ["image/jpeg", "image/png"] }
+"#,
+        )
+        .unwrap();
+
+        let condition = extension.definition().file_items[0]
+            .condition()
+            .unwrap()
+            .clone();
+        let photo = MockFile::new("file:///tmp/photo.jpg").mime_type("image/jpeg");
+        let remote = MockFile::new("sftp://host/photo.png").mime_type("image/png");
+        let notes = MockFile::new("file:///tmp/notes.txt");
+        assert!(condition.matches(&[photo.file_info(), remote.file_info()]));
+        assert!(!condition.matches(&[photo.file_info(), notes.file_info()]));
+
+        let error = DeclarativeExtension::parse(
+            "name = \"Photos\"\n\n[[item]]\nname = \"rotate\"\nlabel = \"Rotate\"\n\
+             command = \"mogrify %F\"\nwhen = { min_count = 3, max_count = 2 }\n",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "line 7, column 37: `max_count` 2 is less than `min_count` 3"
+        );
+    }
+
+    #[test]
+    fn kills_slow_commands() {
+        let extension = DeclarativeExtension::parse(
+            "name = \"Slow\"\n\n[[info]]\nattribute = \"slow\"\ncommand = \"sleep 10\"\n",
+        )
+        .unwrap()
+        .command_timeout(Duration::from_millis(100));
+
+        let file = MockFile::new("file:///tmp/notes.txt");
+        let mut file_info = file.file_info();
+        let start = Instant::now();
+        extension.update_file_info(&mut file_info);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(!file_info.attributes.contains_key("slow"));
+    }
+
+    #[test]
+    fn rereads_at_most_once_per_interval() {
+        let path = env::temp_dir().join(format!("declarative-{}.toml", std::process::id()));
+        let definition = |label: &str| {
+            format!(
+                "name = \"Notes\"\n\n[[item]]\nname = \"count\"\nlabel = \"{}\"\n\
+                 command = \"wc -l %F\"\n",
+                label
+            )
+        };
+        fs::write(&path, definition("Count")).unwrap();
+        let extension = DeclarativeExtension::load(&path).unwrap();
+        let label = || extension.definition().file_items[0].label().to_string();
+
+        // the change is only seen once the interval has passed
+        fs::write(&path, definition("Count lines")).unwrap();
+        assert_eq!(label(), "Count");
+        extension.inner.loaded.lock().unwrap().checked = None;
+        assert_eq!(label(), "Count lines");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,210 @@
+//! A reader for the key file format of freedesktop desktop entries, shared by the action file
+//! formats.
+
+use std::env;
+use std::fmt;
+
+pub(crate) struct DesktopFile {
+    groups: Vec<Group>,
+}
+
+pub(crate) struct Group {
+    name: String,
+    entries: Vec<(String, String)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl DesktopFile {
+    pub fn parse(text: &str) -> Result<DesktopFile, ParseError> {
+        let mut groups: Vec<Group> = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| ParseError {
+                line: i + 1,
+                message: message.to_string(),
+            };
+
+            if line.starts_with('[') {
+                if !line.ends_with(']') {
+                    return Err(error("unterminated group header"));
+                }
+                groups.push(Group {
+                    name: line[1..line.len() - 1].to_string(),
+                    entries: Vec::new(),
+                });
+                continue;
+            }
+
+            let group = groups
+                .last_mut()
+                .ok_or_else(|| error("entry before the first group"))?;
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => return Err(error("expected `key=value`")),
+            };
+            if key.is_empty() {
+                return Err(error("empty key"));
+            }
+            group.entries.push((key.to_string(), value.to_string()));
+        }
+
+        Ok(DesktopFile { groups })
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+}
+
+impl Group {
+    fn raw(&self, key: &str) -> Option<&str> {
+        // later entries override earlier ones
+        self.entries
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn string(&self, key: &str) -> Option<String> {
+        self.raw(key).map(unescape)
+    }
+
+    /// The value of `key` translated for the current locale, e.g. `Name[de]` in a German
+    /// session, falling back to `key` itself.
+    pub fn locale_string(&self, key: &str) -> Option<String> {
+        locale_variants()
+            .iter()
+            .find_map(|locale| self.string(&format!("{}[{}]", key, locale)))
+            .or_else(|| self.string(key))
+    }
+
+    pub fn boolean(&self, key: &str) -> Option<bool> {
+        match self.raw(key)? {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The `;` separated values of `key`. A list may end with a `;`.
+    pub fn list(&self, key: &str) -> Option<Vec<String>> {
+        let raw = self.raw(key)?;
+        let mut values = Vec::new();
+        let mut value = String::new();
+        let mut chars = raw.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(';') => value.push(';'),
+                    Some(c) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => value.push('\\'),
+                },
+                ';' => values.push(unescape(&std::mem::take(&mut value))),
+                c => value.push(c),
+            }
+        }
+        if !value.is_empty() {
+            values.push(unescape(&value));
+        }
+        Some(values)
+    }
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// The locales to look up translations for, most specific first: `de_AT`, then `de`.
+fn locale_variants() -> Vec<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    // strip the encoding and modifier, as in `de_AT.UTF-8@euro`
+    let locale = locale.split(&['.', '@'][..]).next().unwrap_or("");
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return Vec::new();
+    }
+
+    let mut variants = vec![locale.to_string()];
+    if let Some(i) = locale.find('_') {
+        variants.push(locale[..i].to_string());
+    }
+    variants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DesktopFile, ParseError};
+
+    #[test]
+    fn parses_groups_and_values() {
+        let file = DesktopFile::parse(
+            "# comment\n\
+             [Desktop Entry]\n\
+             Name=Resize\\simages\n\
+             Profiles=a;b\\;c;\n\
+             Enabled=false\n\
+             \n\
+             [X-Action-Profile a]\n\
+             Exec = gimp %F\n",
+        )
+        .unwrap();
+
+        let entry = file.group("Desktop Entry").unwrap();
+        assert_eq!(entry.string("Name").as_deref(), Some("Resize images"));
+        assert_eq!(
+            entry.list("Profiles"),
+            Some(vec!["a".to_string(), "b;c".to_string()])
+        );
+        assert_eq!(entry.boolean("Enabled"), Some(false));
+        assert_eq!(
+            file.group("X-Action-Profile a").unwrap().string("Exec"),
+            Some("gimp %F".to_string())
+        );
+
+        assert_eq!(
+            DesktopFile::parse("[Desktop Entry]\nName\n").err(),
+            Some(ParseError {
+                line: 2,
+                message: "expected `key=value`".to_string()
+            })
+        );
+    }
+}
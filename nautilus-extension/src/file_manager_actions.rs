@@ -0,0 +1,812 @@
+//! A `MenuProvider` for the actions of the freedesktop file manager actions specification, the
+//! `.desktop` files in `file-manager/actions` below the XDG data directories:
+//!
+//! ```ignore
+//! fn register_provider(module: *mut GTypeModule) -> GType {
+//!     NautilusModule::new(module, "FileManagerActions")
+//!         .add_menu_provider(FileManagerActions::new())
+//!         .register()
+//! }
+//! ```
+//!
+//! An action is shown when one of its `[X-Action-Profile]` sections matches the files, and runs
+//! the `Exec` line of the first one that does. Menus nest actions and other menus through
+//! `ItemsList`. When a menu is requested, the directories are checked for changes if they have
+//! not been for `RESCAN_INTERVAL`, and the files are read again if one of them changed.
+//!
+//! `MimeTypes`, `Basenames`, `Matchcase`, `Schemes`, `Folders`, `SelectionCount` and
+//! `Capabilities` are supported, as are the `Exec` parameters `%b %B %d %D %f %F %u %U`. Other
+//! parameters expand to nothing. A profile whose `Exec` line fails `Command::check()`, e.g. with
+//! a quoted file code, is dropped with a warning when the file is read. The `Path` key is not
+//! supported and logs a warning: commands run in the current folder.
+//!
+//! Actions of Nemo (`.nemo_action` files) and service menus of Dolphin (`.desktop` files of
+//! `Type=Service`) in the same directories are shown alongside, so a directory of actions can
+//! be shared between file managers.
+
+use crate::command::Command;
+use crate::desktop_file::{DesktopFile, Group};
+use crate::glib_ffi::g_pattern_match_simple;
+#[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+use crate::gtk_ffi::GtkWidget;
+use crate::info_provider::FileInfo;
+use crate::logging;
+use crate::menu_provider::{Menu, MenuItem, MenuProvider};
+use crate::nemo_action;
+use crate::service_menu;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::{CString, OsStr};
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// The directories of actions below each XDG data directory.
+const ACTIONS_DIRS: [&str; 4] = [
+    "file-manager/actions",
+    "nemo/actions",
+    "kio/servicemenus",
+    "kservices5/ServiceMenus",
+];
+
+/// How long the directories of actions are not checked for changes after a check.
+pub const RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct FileManagerActions {
+    dirs: Vec<PathBuf>,
+    rescan_interval: Duration,
+    loaded: Mutex<Loaded>,
+}
+
+#[derive(Default)]
+struct Loaded {
+    checked: Option<Instant>,
+    stamps: Vec<(PathBuf, Option<SystemTime>)>,
+    entries: HashMap<String, Entry>,
+    top_level: Vec<String>,
+}
+
+/// An action or menu, whichever format it was read from.
+pub(crate) struct Entry {
+    pub label: String,
+    pub tip: String,
+    pub icon: Option<String>,
+    /// Shown for selected files.
+    pub files: bool,
+    /// Shown for the current folder.
+    pub background: bool,
+    pub kind: Kind,
+}
+
+pub(crate) enum Kind {
+    Action(Vec<Profile>),
+    /// The ids of the items of the menu.
+    Menu(Vec<String>),
+}
+
+/// The conditions for an action to be shown, and what it runs then.
+pub(crate) struct Profile {
+    pub command: Command,
+    pub mime_types: Vec<Pattern>,
+    pub basenames: Vec<Pattern>,
+    pub match_case: bool,
+    pub schemes: Vec<Pattern>,
+    pub folders: Vec<Pattern>,
+    pub capabilities: Vec<Pattern>,
+    /// `('=', 1)` for a single file, `('>', 0)` for any, all of which must hold.
+    pub selection_counts: Vec<(char, usize)>,
+}
+
+/// A value of a condition list, `!` negating it.
+pub(crate) struct Pattern {
+    negated: bool,
+    value: String,
+}
+
+impl FileManagerActions {
+    /// The actions of the user, in `$XDG_DATA_HOME/file-manager/actions`, and of the system, in
+    /// the same directory below each of `$XDG_DATA_DIRS`. The directories of Nemo actions and
+    /// Dolphin service menus are read too.
+    pub fn new() -> FileManagerActions {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+        let data_dirs = env::var("XDG_DATA_DIRS")
+            .ok()
+            .filter(|dirs| !dirs.is_empty())
+            .unwrap_or_else(|| String::from("/usr/local/share:/usr/share"));
+
+        let dirs = data_home
+            .into_iter()
+            .chain(data_dirs.split(':').map(PathBuf::from))
+            .flat_map(|dir| ACTIONS_DIRS.iter().map(move |actions| dir.join(actions)))
+            .collect();
+        FileManagerActions::with_dirs(dirs)
+    }
+
+    /// The actions in `dirs`, in any of the formats. An action in an earlier directory hides one
+    /// with the same file name in a later directory.
+    pub fn with_dirs(dirs: Vec<PathBuf>) -> FileManagerActions {
+        FileManagerActions {
+            dirs,
+            rescan_interval: RESCAN_INTERVAL,
+            loaded: Mutex::new(Loaded::default()),
+        }
+    }
+
+    /// Check the directories for changes at most every `interval` instead of
+    /// `RESCAN_INTERVAL`.
+    pub fn rescan_interval(mut self, interval: Duration) -> FileManagerActions {
+        self.rescan_interval = interval;
+        self
+    }
+
+    fn file_items(&self, files: &[FileInfo]) -> Vec<MenuItem> {
+        self.items(files, |entry| entry.files)
+    }
+
+    fn background_items(&self, current_folder: &FileInfo) -> Vec<MenuItem> {
+        self.items(std::slice::from_ref(current_folder), |entry| {
+            entry.background
+        })
+    }
+
+    fn items<F: Fn(&Entry) -> bool>(&self, files: &[FileInfo], targets: F) -> Vec<MenuItem> {
+        self.rescan();
+
+        let loaded = self.loaded.lock().unwrap();
+        let mut visited = HashSet::new();
+        loaded
+            .top_level
+            .iter()
+            .filter_map(|id| loaded.item(id, files, &targets, &mut visited))
+            .collect()
+    }
+
+    /// Read the files again if they changed, unless they were checked within the interval. The
+    /// directories are read without holding the lock, so other menu requests are not blocked.
+    fn rescan(&self) {
+        let now = Instant::now();
+        let old_stamps = {
+            let loaded = self.loaded.lock().unwrap();
+            let recent = |checked: Instant| now.duration_since(checked) < self.rescan_interval;
+            if loaded.checked.map_or(false, recent) {
+                return;
+            }
+            loaded.stamps.clone()
+        };
+
+        let stamps = stamps(&self.dirs);
+        let reloaded = if stamps != old_stamps {
+            Some(load(&self.dirs))
+        } else {
+            None
+        };
+
+        let mut loaded = self.loaded.lock().unwrap();
+        if let Some(reloaded) = reloaded {
+            *loaded = reloaded;
+            loaded.stamps = stamps;
+        }
+        loaded.checked = Some(now);
+    }
+}
+
+impl Default for FileManagerActions {
+    fn default() -> FileManagerActions {
+        FileManagerActions::new()
+    }
+}
+
+impl MenuProvider for FileManagerActions {
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    fn get_file_items(&self, _window: *mut GtkWidget, files: &[FileInfo]) -> Vec<MenuItem> {
+        self.file_items(files)
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    fn get_file_items(
+        &self,
+        _window: Option<&crate::gtk::Window>,
+        files: &[FileInfo],
+    ) -> Vec<MenuItem> {
+        self.file_items(files)
+    }
+
+    #[cfg(nautilus_43)]
+    fn get_file_items(&self, files: &[FileInfo]) -> Vec<MenuItem> {
+        self.file_items(files)
+    }
+
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    fn get_background_items(
+        &self,
+        _window: *mut GtkWidget,
+        current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        self.background_items(current_folder)
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    fn get_background_items(
+        &self,
+        _window: Option<&crate::gtk::Window>,
+        current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        self.background_items(current_folder)
+    }
+
+    #[cfg(nautilus_43)]
+    fn get_background_items(&self, current_folder: &FileInfo) -> Vec<MenuItem> {
+        self.background_items(current_folder)
+    }
+}
+
+impl Loaded {
+    /// The item for `id`, or `None` if it does not apply to `files`. Menus without any items are
+    /// left out.
+    fn item<F: Fn(&Entry) -> bool>(
+        &self,
+        id: &str,
+        files: &[FileInfo],
+        targets: &F,
+        visited: &mut HashSet<String>,
+    ) -> Option<MenuItem> {
+        let entry = self.entries.get(id).filter(|entry| targets(entry))?;
+        let mut item = MenuItem::new(
+            format!("FileManagerActions::{}", id),
+            entry.label.clone(),
+            entry.tip.clone(),
+            entry.icon.clone(),
+        );
+
+        match entry.kind {
+            Kind::Action(ref profiles) => {
+                let profile = profiles.iter().find(|p| p.matches(files))?;
+                item.set_command(profile.command.clone());
+            }
+            Kind::Menu(ref items_list) => {
+                // a menu listing itself, directly or not, is shown once
+                if !visited.insert(id.to_string()) {
+                    return None;
+                }
+                let items: Vec<MenuItem> = items_list
+                    .iter()
+                    .filter_map(|id| self.item(id, files, targets, visited))
+                    .collect();
+                visited.remove(id);
+                if items.is_empty() {
+                    return None;
+                }
+                item.set_submenu(&Menu::new(&items));
+            }
+        }
+        Some(item)
+    }
+}
+
+impl Profile {
+    /// A profile running `command` for any files.
+    pub fn new(command: Command) -> Profile {
+        Profile {
+            command,
+            mime_types: Vec::new(),
+            basenames: Vec::new(),
+            match_case: true,
+            schemes: Vec::new(),
+            folders: Vec::new(),
+            capabilities: Vec::new(),
+            selection_counts: Vec::new(),
+        }
+    }
+
+    /// The profile of `group`, read from `path`, or `None` without a valid `Exec` line.
+    fn from_group(path: &Path, group: &Group) -> Option<Profile> {
+        let list = |key: &str, default: &str| -> Vec<Pattern> {
+            Pattern::list(&group.list(key).unwrap_or_else(|| vec![default.to_string()]))
+        };
+
+        let command = Command::new(translate_exec(&group.string("Exec")?));
+        if let Err(error) = command.check() {
+            logging::warning(&format!("{}: {}", path.display(), error));
+            return None;
+        }
+
+        Some(Profile {
+            command,
+            mime_types: list("MimeTypes", "*"),
+            basenames: list("Basenames", "*"),
+            match_case: group.boolean("Matchcase").unwrap_or(true),
+            schemes: list("Schemes", "file"),
+            folders: list("Folders", "/"),
+            capabilities: Pattern::list(&group.list("Capabilities").unwrap_or_default()),
+            selection_counts: group
+                .string("SelectionCount")
+                .and_then(|count| parse_selection_count(&count))
+                .into_iter()
+                .collect(),
+        })
+    }
+
+    fn matches(&self, files: &[FileInfo]) -> bool {
+        let count_matches = self
+            .selection_counts
+            .iter()
+            .all(|&(operator, count)| match operator {
+                '=' => files.len() == count,
+                '<' => files.len() < count,
+                '>' => files.len() > count,
+                _ => true,
+            });
+        count_matches && files.iter().all(|file| self.file_matches(file))
+    }
+
+    fn file_matches(&self, file: &FileInfo) -> bool {
+        let path = file.path();
+
+        let scheme = file.get_uri_scheme();
+        if !list_matches(&self.schemes, |scheme_pattern| {
+            glob_matches(scheme_pattern, &scheme)
+        }) {
+            return false;
+        }
+
+        let name = file
+            .name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (name, match_case) = if self.match_case {
+            (name, true)
+        } else {
+            (name.to_lowercase(), false)
+        };
+        if !list_matches(&self.basenames, |basename| {
+            if match_case {
+                glob_matches(basename, &name)
+            } else {
+                glob_matches(&basename.to_lowercase(), &name)
+            }
+        }) {
+            return false;
+        }
+
+        let mime_type = file.get_mime_type();
+        if !list_matches(&self.mime_types, |pattern| match pattern {
+            "*" | "*/*" | "all/all" => true,
+            "all/allfiles" => !file.is_directory(),
+            pattern if pattern.contains(&['*', '?'][..]) => glob_matches(pattern, &mime_type),
+            pattern => file.is_mime_type(pattern),
+        }) {
+            return false;
+        }
+
+        let folder = path.as_ref().and_then(|path| path.parent());
+        if !list_matches(&self.folders, |pattern| {
+            if pattern == "/" {
+                return true;
+            }
+            let folder = match folder {
+                Some(folder) => folder,
+                None => return false,
+            };
+            if pattern.contains(&['*', '?'][..]) {
+                let folder = folder.to_string_lossy();
+                glob_matches(pattern, &folder)
+                    || glob_matches(&format!("{}/*", pattern.trim_end_matches('/')), &folder)
+            } else {
+                folder.starts_with(pattern)
+            }
+        }) {
+            return false;
+        }
+
+        self.capabilities.iter().all(|capability| {
+            let has_capability = match capability.value.as_str() {
+                "Local" => path.is_some(),
+                "Writable" => file.can_write(),
+                "Readable" => access(path.as_deref(), libc::R_OK),
+                "Executable" => access(path.as_deref(), libc::X_OK),
+                "Owner" => is_owner(path.as_deref()),
+                _ => true,
+            };
+            has_capability != capability.negated
+        })
+    }
+}
+
+impl Pattern {
+    pub fn new(value: &str) -> Pattern {
+        match value.strip_prefix('!') {
+            Some(value) => Pattern {
+                negated: true,
+                value: value.to_string(),
+            },
+            None => Pattern {
+                negated: false,
+                value: value.to_string(),
+            },
+        }
+    }
+
+    pub fn list(values: &[String]) -> Vec<Pattern> {
+        values.iter().map(|value| Pattern::new(value)).collect()
+    }
+}
+
+/// Whether a value matches at least one positive pattern of `patterns`, or there are only
+/// negative ones, and no negative pattern.
+fn list_matches<F: Fn(&str) -> bool>(patterns: &[Pattern], matches: F) -> bool {
+    let mut any_positive = false;
+    let mut positive_match = false;
+    for pattern in patterns {
+        if pattern.negated {
+            if matches(&pattern.value) {
+                return false;
+            }
+        } else {
+            any_positive = true;
+            positive_match = positive_match || matches(&pattern.value);
+        }
+    }
+    positive_match || !any_positive
+}
+
+fn glob_matches(pattern: &str, value: &str) -> bool {
+    let (pattern, value) = match (CString::new(pattern), CString::new(value)) {
+        (Ok(pattern), Ok(value)) => (pattern, value),
+        _ => return false,
+    };
+    unsafe { g_pattern_match_simple(pattern.as_ptr(), value.as_ptr()) != 0 }
+}
+
+fn access(path: Option<&Path>, mode: libc::c_int) -> bool {
+    match path.map(|path| CString::new(path.as_os_str().as_bytes())) {
+        Some(Ok(path)) => unsafe { libc::access(path.as_ptr(), mode) == 0 },
+        _ => false,
+    }
+}
+
+fn is_owner(path: Option<&Path>) -> bool {
+    match path.map(fs::metadata) {
+        Some(Ok(metadata)) => metadata.uid() == unsafe { libc::geteuid() },
+        _ => false,
+    }
+}
+
+/// `=1`, `<3` or `>0`, as `('=', 1)`.
+fn parse_selection_count(count: &str) -> Option<(char, usize)> {
+    let count = count.trim();
+    let operator = count.chars().next()?;
+    if !['=', '<', '>'].contains(&operator) {
+        return None;
+    }
+    Some((operator, count[1..].trim().parse().ok()?))
+}
+
+/// Translate the `Exec` parameters of the specification to the field codes of `Command`.
+fn translate_exec(exec: &str) -> String {
+    let mut translated = String::with_capacity(exec.len());
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            translated.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('b') => translated.push_str("%n"),
+            Some('B') => translated.push_str("%N"),
+            Some(c @ 'd') | Some(c @ 'D') | Some(c @ 'f') | Some(c @ 'F') | Some(c @ 'u')
+            | Some(c @ 'U') | Some(c @ '%') => {
+                translated.push('%');
+                translated.push(c);
+            }
+            Some(c) if "cChmMnoOpswWxX".contains(c) => {}
+            // not a parameter, so the `%` is kept
+            Some(c) => {
+                translated.push_str("%%");
+                translated.push(c);
+            }
+            None => translated.push_str("%%"),
+        }
+    }
+    translated
+}
+
+/// The action files of `dirs` and their modification times, to notice changes.
+fn stamps(dirs: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut stamps = Vec::new();
+    for dir in dirs {
+        let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension() == Some(OsStr::new("desktop"))
+                        || path.extension() == Some(OsStr::new("nemo_action"))
+                })
+                .collect(),
+            Err(_) => continue,
+        };
+        paths.sort();
+        for path in paths {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            stamps.push((path, modified));
+        }
+    }
+    stamps
+}
+
+fn load(dirs: &[PathBuf]) -> Loaded {
+    let mut loaded = Loaded::default();
+    let mut ids = Vec::new();
+    let mut loaded_ids = HashSet::new();
+
+    for (path, _) in stamps(dirs) {
+        let id = match path.file_stem() {
+            Some(stem) => stem.to_string_lossy().into_owned(),
+            None => continue,
+        };
+        if !loaded_ids.insert(id.clone()) {
+            continue;
+        }
+
+        let entries = fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|text| DesktopFile::parse(&text).map_err(|error| error.to_string()))
+            .map(|file| parse_entries(&path, &id, &file));
+        match entries {
+            Ok(entries) => {
+                for (id, entry) in entries {
+                    ids.push(id.clone());
+                    loaded.entries.insert(id, entry);
+                }
+            }
+            Err(error) => logging::warning(&format!("{}: {}", path.display(), error)),
+        }
+    }
+
+    // items listed by a menu are not shown on their own
+    let listed: HashSet<&String> = loaded
+        .entries
+        .values()
+        .filter_map(|entry| match entry.kind {
+            Kind::Menu(ref items_list) => Some(items_list),
+            Kind::Action(_) => None,
+        })
+        .flatten()
+        .collect();
+    let mut top_level: Vec<String> = ids.into_iter().filter(|id| !listed.contains(id)).collect();
+    top_level.sort_by(|a, b| loaded.entries[a].label.cmp(&loaded.entries[b].label));
+    loaded.top_level = top_level;
+
+    loaded
+}
+
+/// The entries of the file at `path`, named `id`, in whichever format it has.
+fn parse_entries(path: &Path, id: &str, file: &DesktopFile) -> Vec<(String, Entry)> {
+    if path.extension() == Some(OsStr::new("nemo_action")) {
+        nemo_action::parse_entry(path, file)
+            .map(|entry| (id.to_string(), entry))
+            .into_iter()
+            .collect()
+    } else if service_menu::is_service_menu(file) {
+        service_menu::parse_entries(path, id, file)
+    } else {
+        parse_entry(path, file)
+            .map(|entry| (id.to_string(), entry))
+            .into_iter()
+            .collect()
+    }
+}
+
+/// The action or menu of `file`, read from `path`, or `None` if it is disabled or not one.
+fn parse_entry(path: &Path, file: &DesktopFile) -> Option<Entry> {
+    let group = file.group("Desktop Entry")?;
+    if !group.boolean("Enabled").unwrap_or(true) || group.boolean("Hidden").unwrap_or(false) {
+        return None;
+    }
+
+    let kind = match group.string("Type").as_deref().unwrap_or("Action") {
+        "Action" => {
+            let profiles = group
+                .list("Profiles")
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|id| file.group(&format!("X-Action-Profile {}", id)))
+                .filter_map(|group| {
+                    if group.string("Path").is_some() {
+                        logging::warning(&format!(
+                            "{}: the Path key is not supported, the command runs in the current folder",
+                            path.display()
+                        ));
+                    }
+                    Profile::from_group(path, group)
+                })
+                .collect::<Vec<_>>();
+            if profiles.is_empty() {
+                return None;
+            }
+            Kind::Action(profiles)
+        }
+        "Menu" => Kind::Menu(group.list("ItemsList").unwrap_or_default()),
+        _ => return None,
+    };
+
+    Some(Entry {
+        label: group.locale_string("Name")?,
+        tip: group.locale_string("Tooltip").unwrap_or_default(),
+        icon: group.locale_string("Icon").filter(|icon| !icon.is_empty()),
+        files: group.boolean("TargetContext").unwrap_or(true),
+        background: group.boolean("TargetLocation").unwrap_or(false),
+        kind,
+    })
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::{FileManagerActions, RESCAN_INTERVAL};
+    use crate::info_provider::FileInfo;
+    use crate::mock::MockFile;
+    use std::env;
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn builds_items_from_actions() {
+        let dir = env::temp_dir().join(format!("file-manager-actions-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("tools.desktop"),
+            "[Desktop Entry]\nType=Menu\nName=Tools\nItemsList=resize;count;\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("resize.desktop"),
+            "[Desktop Entry]\nName=Resize\nProfiles=images;\n\n\
+             [X-Action-Profile images]\nMimeTypes=image/*;\nExec=convert %f -resize 50% %b\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("count.desktop"),
+            "[Desktop Entry]\nName=Count\nProfiles=many;\n\n\
+             [X-Action-Profile many]\nSelectionCount=>1\nExec=wc -l %F\n",
+        )
+        .unwrap();
+
+        let actions =
+            FileManagerActions::with_dirs(vec![dir.clone()]).rescan_interval(Duration::ZERO);
+        let photo = MockFile::new("file:///tmp/photo.jpg").mime_type("image/jpeg");
+        let notes = MockFile::new("file:///tmp/notes.txt");
+
+        let items = actions.file_items(&[photo.file_info()]);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label(), "Tools");
+        let submenu = items[0].submenu().unwrap().items();
+        assert_eq!(submenu.len(), 1);
+        assert_eq!(
+            submenu[0].command().unwrap().exec(),
+            "convert %f -resize 50%% %n"
+        );
+
+        let files: Vec<FileInfo> = vec![photo.file_info(), notes.file_info()];
+        let items = actions.file_items(&files);
+        assert_eq!(items[0].submenu().unwrap().items()[0].label(), "Count");
+
+        fs::remove_file(dir.join("tools.desktop")).unwrap();
+        let items = actions.file_items(&files);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label(), "Count");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn drops_actions_with_unsafe_commands() {
+        let dir = env::temp_dir().join(format!("unsafe-actions-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("show.desktop"),
+            "[Desktop Entry]\nName=Show\nProfiles=quoted;script;\n\n\
+             [X-Action-Profile quoted]\nExec=sh -c 'cat %f'\n\n\
+             [X-Action-Profile script]\nExec=bash -c %f\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("open.desktop"),
+            "[Desktop Entry]\nName=Open\nProfiles=unsafe;safe;\n\n\
+             [X-Action-Profile unsafe]\nExec=app '%f'\n\n\
+             [X-Action-Profile safe]\nExec=app --file=%f\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("print.nemo_action"),
+            "[Nemo Action]\nName=Print\nExec=sh -c \"lp %F\"\nSelection=any\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("tools.desktop"),
+            "[Desktop Entry]\nType=Service\nActions=unsafe;\n\n\
+             [Desktop Action unsafe]\nName=Unsafe\nExec=bash -c %f\n",
+        )
+        .unwrap();
+
+        let actions = FileManagerActions::with_dirs(vec![dir.clone()]);
+        let notes = MockFile::new("file:///tmp/notes.txt");
+        let items = actions.file_items(&[notes.file_info()]);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label(), "Open");
+        assert_eq!(items[0].command().unwrap().exec(), "app --file=%f");
+        let loaded = actions.loaded.lock().unwrap();
+        for id in &["show", "print", "tools/unsafe"] {
+            assert!(!loaded.entries.contains_key(*id), "{}", id);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rescans_at_most_once_per_interval() {
+        let dir = env::temp_dir().join(format!("rescanned-actions-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("count.desktop"),
+            "[Desktop Entry]\nName=Count\nProfiles=any;\n\n[X-Action-Profile any]\nExec=wc -l %F\n",
+        )
+        .unwrap();
+
+        let actions = FileManagerActions::with_dirs(vec![dir.clone()]);
+        let notes = MockFile::new("file:///tmp/notes.txt");
+        assert_eq!(actions.file_items(&[notes.file_info()]).len(), 1);
+
+        // the change is only seen once the interval has passed
+        fs::remove_file(dir.join("count.desktop")).unwrap();
+        assert_eq!(actions.file_items(&[notes.file_info()]).len(), 1);
+        actions.loaded.lock().unwrap().checked = Some(Instant::now() - RESCAN_INTERVAL);
+        assert_eq!(actions.file_items(&[notes.file_info()]).len(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shares_dir_with_nemo_and_dolphin() {
+        let dir = env::temp_dir().join(format!("shared-actions-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("checksum.nemo_action"),
+            "[Nemo Action]\nName=Checksum\nExec=<checksum.sh %F>\n\
+             Selection=notnone\nExtensions=iso;img;\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("images.desktop"),
+            "[Desktop Entry]\nType=Service\nMimeType=image/*;\nActions=rotate;\n\
+             X-KDE-Submenu=Images\n\n\
+             [Desktop Action rotate]\nName=Rotate\nIcon=object-rotate-right\n\
+             Exec=mogrify -rotate 90 %F\n",
+        )
+        .unwrap();
+
+        let actions = FileManagerActions::with_dirs(vec![dir.clone()]);
+        let image = MockFile::new("file:///tmp/disk.IMG");
+        let photo = MockFile::new("file:///tmp/photo.jpg").mime_type("image/jpeg");
+
+        let items = actions.file_items(&[image.file_info()]);
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0].command().unwrap().exec(),
+            format!("'{}/'checksum.sh %F", dir.display())
+        );
+
+        let items = actions.file_items(&[photo.file_info()]);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label(), "Images");
+        let rotate = &items[0].submenu().unwrap().items()[0];
+        assert_eq!(rotate.icon(), Some("object-rotate-right"));
+        assert_eq!(rotate.command().unwrap().exec(), "mogrify -rotate 90 %F");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
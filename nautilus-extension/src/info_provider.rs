@@ -1,21 +1,43 @@
-use crate::glib_ffi::gpointer;
-use crate::gobject_ffi::{g_closure_ref, GClosure};
+use crate::gio_ffi::{g_file_get_basename, g_file_get_path, GFile};
+use crate::gio_ffi::{
+    GFileType, G_FILE_TYPE_DIRECTORY, G_FILE_TYPE_MOUNTABLE, G_FILE_TYPE_REGULAR,
+    G_FILE_TYPE_SHORTCUT, G_FILE_TYPE_SPECIAL, G_FILE_TYPE_SYMBOLIC_LINK,
+};
+use crate::glib_ffi::{g_free, gpointer};
+#[cfg(not(feature = "gtk-rs"))]
+use crate::gobject_ffi::g_object_ref;
+use crate::gobject_ffi::{g_closure_ref, g_object_unref, GClosure, GObject};
+use crate::logging;
 use crate::nautilus_ffi::nautilus_file_info_invalidate_extension_info;
 use crate::nautilus_ffi::{
-    nautilus_file_info_add_string_attribute, nautilus_file_info_get_uri,
-    nautilus_file_info_get_uri_scheme,
+    nautilus_file_info_add_string_attribute, nautilus_file_info_can_write,
+    nautilus_file_info_get_file_type, nautilus_file_info_get_location,
+    nautilus_file_info_get_mime_type, nautilus_file_info_get_parent_uri,
+    nautilus_file_info_get_uri, nautilus_file_info_get_uri_scheme, nautilus_file_info_is_directory,
+    nautilus_file_info_is_mime_type,
 };
 use crate::nautilus_ffi::{
     NautilusFileInfo, NautilusInfoProvider, NautilusOperationHandle, NautilusOperationResult,
 };
+use crate::stats::{self, Callback, ProviderKind};
+use libc::c_char;
 use std::collections::HashMap;
-use std::ffi::{CStr, CString};
+use std::ffi::{CStr, CString, OsString};
+use std::os::unix::ffi::OsStringExt;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
 
+/// Calls for different files may run at the same time, each on its own thread.
 pub trait InfoProvider: Send + Sync {
     fn should_update_file_info(&self, file_info: &FileInfo) -> bool;
     fn update_file_info(&self, file_info: &mut FileInfo);
+
+    /// Called from `nautilus_module_shutdown()` once all pending `update_file_info` calls have
+    /// finished, right before the provider is dropped. If one of this provider's calls is still
+    /// running when the shutdown timeout expires, the provider is neither shut down nor dropped.
+    fn shutdown(&self) {}
 }
 
 #[derive(Clone)]
@@ -52,6 +74,72 @@ impl FileInfo {
         }
     }
 
+    /// The URI of the folder containing the file, or an empty string for a root.
+    pub fn get_parent_uri(&self) -> String {
+        unsafe { take_string(nautilus_file_info_get_parent_uri(self.raw_file_info)) }
+    }
+
+    /// The local path of the file, or `None` if it has none, e.g. on a remote server. The path
+    /// is taken byte for byte from GIO, so names that are not UTF-8 are kept as they are.
+    pub fn path(&self) -> Option<PathBuf> {
+        self.with_location(|location| unsafe { take_bytes(g_file_get_path(location)) })
+            .map(|path| PathBuf::from(OsString::from_vec(path)))
+    }
+
+    /// The name of the file on disk, byte for byte, unlike the display name Nautilus shows, or
+    /// `None` if the file has no location.
+    pub fn name(&self) -> Option<OsString> {
+        self.with_location(|location| unsafe { take_bytes(g_file_get_basename(location)) })
+            .map(OsString::from_vec)
+    }
+
+    /// The `GFile` for the location of the file, released when the returned value is dropped.
+    #[cfg(not(feature = "gtk-rs"))]
+    pub fn as_gfile(&self) -> Option<GFileRef> {
+        let location = unsafe { nautilus_file_info_get_location(self.raw_file_info) };
+        if location.is_null() {
+            None
+        } else {
+            Some(GFileRef { raw: location })
+        }
+    }
+
+    /// The `GFile` for the location of the file.
+    #[cfg(feature = "gtk-rs")]
+    pub fn as_gfile(&self) -> Option<gtk::gio::File> {
+        use gtk::glib::translate::from_glib_full;
+
+        let location = unsafe { nautilus_file_info_get_location(self.raw_file_info) };
+        if location.is_null() {
+            None
+        } else {
+            Some(unsafe { from_glib_full(location as *mut gtk::gio::ffi::GFile) })
+        }
+    }
+
+    pub fn get_mime_type(&self) -> String {
+        unsafe { take_string(nautilus_file_info_get_mime_type(self.raw_file_info)) }
+    }
+
+    /// Whether the file's MIME type is `mime_type` or a subtype of it, such as `text/x-python`
+    /// for `text/plain`.
+    pub fn is_mime_type(&self, mime_type: &str) -> bool {
+        let mime_type = CString::new(mime_type).unwrap();
+        unsafe { nautilus_file_info_is_mime_type(self.raw_file_info, mime_type.as_ptr()) != 0 }
+    }
+
+    pub fn get_file_type(&self) -> FileType {
+        FileType::from_raw(unsafe { nautilus_file_info_get_file_type(self.raw_file_info) })
+    }
+
+    pub fn is_directory(&self) -> bool {
+        unsafe { nautilus_file_info_is_directory(self.raw_file_info) != 0 }
+    }
+
+    pub fn can_write(&self) -> bool {
+        unsafe { nautilus_file_info_can_write(self.raw_file_info) != 0 }
+    }
+
     pub fn invalidate_extension_info(&self) {
         unsafe {
             nautilus_file_info_invalidate_extension_info(self.raw_file_info);
@@ -62,14 +150,185 @@ impl FileInfo {
         self.attributes.insert(name.to_string(), value.to_string());
         self
     }
+
+    fn with_location<T, F: FnOnce(*mut GFile) -> Option<T>>(&self, f: F) -> Option<T> {
+        let location = unsafe { nautilus_file_info_get_location(self.raw_file_info) };
+        if location.is_null() {
+            return None;
+        }
+        let result = f(location);
+        unsafe {
+            g_object_unref(location as *mut GObject);
+        }
+        result
+    }
+}
+
+/// A reference to a `GFile`, released with `g_object_unref()` when dropped.
+#[cfg(not(feature = "gtk-rs"))]
+pub struct GFileRef {
+    raw: *mut GFile,
+}
+
+#[cfg(not(feature = "gtk-rs"))]
+impl GFileRef {
+    /// The `GFile`, valid for as long as this reference is alive.
+    pub fn as_ptr(&self) -> *mut GFile {
+        self.raw
+    }
+}
+
+#[cfg(not(feature = "gtk-rs"))]
+impl Clone for GFileRef {
+    fn clone(&self) -> GFileRef {
+        unsafe {
+            g_object_ref(self.raw as *mut GObject);
+        }
+        GFileRef { raw: self.raw }
+    }
+}
+
+#[cfg(not(feature = "gtk-rs"))]
+impl Drop for GFileRef {
+    fn drop(&mut self) {
+        unsafe {
+            g_object_unref(self.raw as *mut GObject);
+        }
+    }
+}
+
+/// The kind of file, from `GFileType`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+    Unknown,
+    Regular,
+    Directory,
+    SymbolicLink,
+    Special,
+    Shortcut,
+    Mountable,
+}
+
+impl FileType {
+    fn from_raw(file_type: GFileType) -> FileType {
+        match file_type {
+            G_FILE_TYPE_REGULAR => FileType::Regular,
+            G_FILE_TYPE_DIRECTORY => FileType::Directory,
+            G_FILE_TYPE_SYMBOLIC_LINK => FileType::SymbolicLink,
+            G_FILE_TYPE_SPECIAL => FileType::Special,
+            G_FILE_TYPE_SHORTCUT => FileType::Shortcut,
+            G_FILE_TYPE_MOUNTABLE => FileType::Mountable,
+            _ => FileType::Unknown,
+        }
+    }
+}
+
+/// Copy the bytes of a newly allocated string and free it.
+unsafe fn take_bytes(raw_string: *mut c_char) -> Option<Vec<u8>> {
+    if raw_string.is_null() {
+        return None;
+    }
+    let bytes = CStr::from_ptr(raw_string).to_bytes().to_vec();
+    g_free(raw_string as gpointer);
+    Some(bytes)
+}
+
+/// Copy a newly allocated string and free it.
+unsafe fn take_string(raw_string: *mut c_char) -> String {
+    if raw_string.is_null() {
+        return String::new();
+    }
+    let string = CStr::from_ptr(raw_string).to_string_lossy().into_owned();
+    g_free(raw_string as gpointer);
+    string
 }
 
 pub struct UpdateFileInfoOperationHandle {
     pub skip_response: bool,
 }
 
+lazy_static! {
+    static ref UPDATE_WORKERS: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+}
+
+/// Counts a background `update_file_info` thread as running until dropped.
+struct UpdateWorkerGuard;
+
+impl UpdateWorkerGuard {
+    fn new() -> UpdateWorkerGuard {
+        *UPDATE_WORKERS.0.lock().unwrap() += 1;
+        UpdateWorkerGuard
+    }
+}
+
+impl Drop for UpdateWorkerGuard {
+    fn drop(&mut self) {
+        let (ref active, ref finished) = *UPDATE_WORKERS;
+        *active.lock().unwrap() -= 1;
+        finished.notify_all();
+    }
+}
+
+/// Block until every background `update_file_info` thread has finished, or `timeout` has passed.
+/// Returns the number of threads still running.
+pub fn join_update_workers(timeout: Duration) -> usize {
+    let (ref active, ref finished) = *UPDATE_WORKERS;
+    let active = active.lock().unwrap();
+    let (active, _) = finished
+        .wait_timeout_while(active, timeout, |active| *active > 0)
+        .unwrap();
+    *active
+}
+
+/// Copy the attributes set by `InfoProvider::update_file_info()` onto the Nautilus file.
+pub(crate) fn add_attributes_to_raw(file_info: &FileInfo) {
+    unsafe {
+        for (attr_name, attr_value) in &file_info.attributes {
+            let attr_name_c = CString::new(attr_name.as_str()).unwrap().into_raw();
+            let attr_value_c = CString::new(attr_value.as_str()).unwrap().into_raw();
+
+            nautilus_file_info_add_string_attribute(
+                file_info.raw_file_info,
+                attr_name_c,
+                attr_value_c,
+            );
+
+            // deallocate CStrings
+            let _ = CString::from_raw(attr_name_c);
+            let _ = CString::from_raw(attr_value_c);
+        }
+    }
+}
+
+/// The provider of an interface index. Calls take a reference to the provider, so the slot is
+/// only locked while it is taken and slow calls do not hold up one another.
+#[derive(Default)]
+struct ProviderSlot {
+    slot: Mutex<Option<Arc<dyn InfoProvider>>>,
+}
+
+impl ProviderSlot {
+    fn get(&self) -> Option<Arc<dyn InfoProvider>> {
+        self.lock().clone()
+    }
+
+    /// Take the provider unless a call still holds it, as a worker that outlived the shutdown
+    /// timeout does.
+    fn take_idle(&self) -> Option<Arc<dyn InfoProvider>> {
+        let mut slot = self.lock();
+        match *slot {
+            Some(ref provider) if Arc::strong_count(provider) == 1 => slot.take(),
+            _ => None,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Arc<dyn InfoProvider>>> {
+        self.slot.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
 macro_rules! info_provider_iface {
-    ($iface_init_fn:ident, $update_file_info_fn:ident, $update_file_info_bg_fn:ident, $cancel_update_fn:ident, $rust_provider:ident, $set_rust_provider:ident) => {
+    ($index:expr, $iface_init_fn:ident, $update_file_info_fn:ident, $update_file_info_bg_fn:ident, $cancel_update_fn:ident, $rust_provider:ident, $set_rust_provider:ident, $shutdown_rust_provider:ident) => {
         /// # Safety
         ///
         /// This generated function is used as a Nautilus callback. Do not call directly.
@@ -100,10 +359,13 @@ macro_rules! info_provider_iface {
 
             let file_info = FileInfo::new(file);
 
-            let should_update_file_info = match *$rust_provider.lock().unwrap() {
-                Some(ref p) => p.should_update_file_info(&file_info),
-                None => false,
-            };
+            let should_update_file_info =
+                stats::timed(ProviderKind::Info, $index, Callback::UpdateFileInfo, || {
+                    match $rust_provider.get() {
+                        Some(p) => p.should_update_file_info(&file_info),
+                        None => false,
+                    }
+                });
 
             if !should_update_file_info {
                 return NautilusOperationResult::NautilusOperationComplete;
@@ -116,7 +378,10 @@ macro_rules! info_provider_iface {
             let my_handle_thread = my_handle.clone();
 
             let (tx, rx) = channel();
+            let worker_guard = UpdateWorkerGuard::new();
             thread::spawn(move || {
+                let _worker_guard = worker_guard;
+
                 // background thread receives arguments from channel
                 let (file_info, provider, update_complete, handle_ref) = rx.recv().unwrap();
                 $update_file_info_bg_fn(
@@ -166,30 +431,19 @@ macro_rules! info_provider_iface {
         ) {
             use crate::nautilus_ffi::nautilus_info_provider_update_complete_invoke;
 
-            match *$rust_provider.lock().unwrap() {
-                Some(ref p) => p.update_file_info(file_info.as_mut()),
-                None => (),
-            };
+            stats::timed(
+                ProviderKind::Info,
+                $index,
+                Callback::UpdateFileInfoBackground,
+                || match $rust_provider.get() {
+                    Some(p) => p.update_file_info(file_info.as_mut()),
+                    None => (),
+                },
+            );
 
             if !my_handle.lock().unwrap().skip_response {
-                let file_info = file_info.as_mut();
-                let attributes = &file_info.attributes;
+                add_attributes_to_raw(&file_info);
                 unsafe {
-                    for (attr_name, attr_value) in attributes {
-                        let attr_name_c = CString::new(attr_name.as_str()).unwrap().into_raw();
-                        let attr_value_c = CString::new(attr_value.as_str()).unwrap().into_raw();
-
-                        nautilus_file_info_add_string_attribute(
-                            file_info.raw_file_info,
-                            attr_name_c,
-                            attr_value_c,
-                        );
-
-                        // deallocate CStrings
-                        let _ = CString::from_raw(attr_name_c);
-                        let _ = CString::from_raw(attr_value_c);
-                    }
-
                     nautilus_info_provider_update_complete_invoke(
                         update_complete,
                         provider,
@@ -201,25 +455,36 @@ macro_rules! info_provider_iface {
         }
 
         pub fn $set_rust_provider(info_provider: Box<dyn InfoProvider>) {
-            *$rust_provider.lock().unwrap() = Some(info_provider);
+            *$rust_provider.lock() = Some(Arc::from(info_provider));
+        }
+
+        pub fn $shutdown_rust_provider() {
+            if let Some(provider) = $rust_provider.take_idle() {
+                provider.shutdown();
+            } else if $rust_provider.get().is_some() {
+                logging::warning(&format!(
+                    "Info provider {} is still running update_file_info, not shutting it down",
+                    $index
+                ));
+            }
         }
 
         lazy_static! {
-            static ref $rust_provider: Mutex<Option<Box<dyn InfoProvider>>> = Mutex::new(None);
+            static ref $rust_provider: ProviderSlot = ProviderSlot::default();
         }
     };
 }
 
-#[rustfmt::skip] info_provider_iface!(info_provider_iface_init_0, info_provider_update_file_info_0, info_provider_update_file_info_bg_0, info_provider_cancel_update_0, INFO_PROVIDER_0, set_info_provider_0);
-#[rustfmt::skip] info_provider_iface!(info_provider_iface_init_1, info_provider_update_file_info_1, info_provider_update_file_info_bg_1, info_provider_cancel_update_1, INFO_PROVIDER_1, set_info_provider_1);
-#[rustfmt::skip] info_provider_iface!(info_provider_iface_init_2, info_provider_update_file_info_2, info_provider_update_file_info_bg_2, info_provider_cancel_update_2, INFO_PROVIDER_2, set_info_provider_2);
-#[rustfmt::skip] info_provider_iface!(info_provider_iface_init_3, info_provider_update_file_info_3, info_provider_update_file_info_bg_3, info_provider_cancel_update_3, INFO_PROVIDER_3, set_info_provider_3);
-#[rustfmt::skip] info_provider_iface!(info_provider_iface_init_4, info_provider_update_file_info_4, info_provider_update_file_info_bg_4, info_provider_cancel_update_4, INFO_PROVIDER_4, set_info_provider_4);
-#[rustfmt::skip] info_provider_iface!(info_provider_iface_init_5, info_provider_update_file_info_5, info_provider_update_file_info_bg_5, info_provider_cancel_update_5, INFO_PROVIDER_5, set_info_provider_5);
-#[rustfmt::skip] info_provider_iface!(info_provider_iface_init_6, info_provider_update_file_info_6, info_provider_update_file_info_bg_6, info_provider_cancel_update_6, INFO_PROVIDER_6, set_info_provider_6);
-#[rustfmt::skip] info_provider_iface!(info_provider_iface_init_7, info_provider_update_file_info_7, info_provider_update_file_info_bg_7, info_provider_cancel_update_7, INFO_PROVIDER_7, set_info_provider_7);
-#[rustfmt::skip] info_provider_iface!(info_provider_iface_init_8, info_provider_update_file_info_8, info_provider_update_file_info_bg_8, info_provider_cancel_update_8, INFO_PROVIDER_8, set_info_provider_8);
-#[rustfmt::skip] info_provider_iface!(info_provider_iface_init_9, info_provider_update_file_info_9, info_provider_update_file_info_bg_9, info_provider_cancel_update_9, INFO_PROVIDER_9, set_info_provider_9);
+#[rustfmt::skip] info_provider_iface!(0, info_provider_iface_init_0, info_provider_update_file_info_0, info_provider_update_file_info_bg_0, info_provider_cancel_update_0, INFO_PROVIDER_0, set_info_provider_0, shutdown_info_provider_0);
+#[rustfmt::skip] info_provider_iface!(1, info_provider_iface_init_1, info_provider_update_file_info_1, info_provider_update_file_info_bg_1, info_provider_cancel_update_1, INFO_PROVIDER_1, set_info_provider_1, shutdown_info_provider_1);
+#[rustfmt::skip] info_provider_iface!(2, info_provider_iface_init_2, info_provider_update_file_info_2, info_provider_update_file_info_bg_2, info_provider_cancel_update_2, INFO_PROVIDER_2, set_info_provider_2, shutdown_info_provider_2);
+#[rustfmt::skip] info_provider_iface!(3, info_provider_iface_init_3, info_provider_update_file_info_3, info_provider_update_file_info_bg_3, info_provider_cancel_update_3, INFO_PROVIDER_3, set_info_provider_3, shutdown_info_provider_3);
+#[rustfmt::skip] info_provider_iface!(4, info_provider_iface_init_4, info_provider_update_file_info_4, info_provider_update_file_info_bg_4, info_provider_cancel_update_4, INFO_PROVIDER_4, set_info_provider_4, shutdown_info_provider_4);
+#[rustfmt::skip] info_provider_iface!(5, info_provider_iface_init_5, info_provider_update_file_info_5, info_provider_update_file_info_bg_5, info_provider_cancel_update_5, INFO_PROVIDER_5, set_info_provider_5, shutdown_info_provider_5);
+#[rustfmt::skip] info_provider_iface!(6, info_provider_iface_init_6, info_provider_update_file_info_6, info_provider_update_file_info_bg_6, info_provider_cancel_update_6, INFO_PROVIDER_6, set_info_provider_6, shutdown_info_provider_6);
+#[rustfmt::skip] info_provider_iface!(7, info_provider_iface_init_7, info_provider_update_file_info_7, info_provider_update_file_info_bg_7, info_provider_cancel_update_7, INFO_PROVIDER_7, set_info_provider_7, shutdown_info_provider_7);
+#[rustfmt::skip] info_provider_iface!(8, info_provider_iface_init_8, info_provider_update_file_info_8, info_provider_update_file_info_bg_8, info_provider_cancel_update_8, INFO_PROVIDER_8, set_info_provider_8, shutdown_info_provider_8);
+#[rustfmt::skip] info_provider_iface!(9, info_provider_iface_init_9, info_provider_update_file_info_9, info_provider_update_file_info_bg_9, info_provider_cancel_update_9, INFO_PROVIDER_9, set_info_provider_9, shutdown_info_provider_9);
 
 pub fn info_provider_iface_externs() -> Vec<unsafe extern "C" fn(gpointer, gpointer)> {
     vec![
@@ -251,8 +516,69 @@ pub fn rust_info_provider_setters() -> Vec<fn(Box<dyn InfoProvider>)> {
     ]
 }
 
+pub fn rust_info_provider_shutdowns() -> Vec<fn()> {
+    vec![
+        shutdown_info_provider_0,
+        shutdown_info_provider_1,
+        shutdown_info_provider_2,
+        shutdown_info_provider_3,
+        shutdown_info_provider_4,
+        shutdown_info_provider_5,
+        shutdown_info_provider_6,
+        shutdown_info_provider_7,
+        shutdown_info_provider_8,
+        shutdown_info_provider_9,
+    ]
+}
+
 static NEXT_INFO_PROVIDER_IFACE_INDEX: AtomicUsize = AtomicUsize::new(0);
 
 pub fn take_next_info_provider_iface_index() -> usize {
     NEXT_INFO_PROVIDER_IFACE_INDEX.fetch_add(1, Ordering::SeqCst)
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use crate::mock::MockFile;
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+    use std::path::PathBuf;
+
+    #[test]
+    fn keeps_non_utf8_names() {
+        let file = MockFile::new("file:///tmp/caf%E9.txt");
+        let file = file.file_info();
+        let name = OsString::from_vec(b"caf\xe9.txt".to_vec());
+        assert_eq!(file.name(), Some(name.clone()));
+        assert_eq!(file.path(), Some(PathBuf::from("/tmp").join(&name)));
+
+        let remote = MockFile::new("sftp://host/notes.txt");
+        let remote = remote.file_info();
+        assert_eq!(remote.path(), None);
+        assert_eq!(remote.name(), Some(OsString::from("notes.txt")));
+    }
+
+    #[cfg(not(feature = "gtk-rs"))]
+    #[test]
+    fn releases_locations() {
+        use crate::gio_ffi::g_file_get_uri;
+        use crate::gobject_ffi::{g_object_add_weak_pointer, GObject};
+
+        let file = MockFile::new("file:///tmp/notes.txt");
+        let location = file.file_info().as_gfile().unwrap();
+        let uri = unsafe { super::take_bytes(g_file_get_uri(location.as_ptr())) };
+        assert_eq!(uri.as_deref(), Some(&b"file:///tmp/notes.txt"[..]));
+
+        let mut raw = location.as_ptr() as *mut GObject;
+        unsafe {
+            g_object_add_weak_pointer(raw, &mut raw as *mut *mut GObject as *mut _);
+        }
+        let copy = location.clone();
+        drop(location);
+        assert!(!raw.is_null());
+        let uri = unsafe { super::take_bytes(g_file_get_uri(copy.as_ptr())) };
+        assert_eq!(uri.as_deref(), Some(&b"file:///tmp/notes.txt"[..]));
+        drop(copy);
+        assert!(raw.is_null());
+    }
+}
@@ -1,8 +1,13 @@
 #![deny(bare_trait_objects)]
 
+// `gtk-rs` types the GTK 3 widgets of Nautilus 3, and Nautilus 4 has none
+#[cfg(all(feature = "gtk-rs", feature = "nautilus4"))]
+compile_error!("feature `gtk-rs` cannot be enabled together with `nautilus4`");
+
 extern crate gio_sys as gio_ffi;
 extern crate glib_sys as glib_ffi;
 extern crate gobject_sys as gobject_ffi;
+#[cfg(not(nautilus_43))]
 extern crate gtk_sys as gtk_ffi;
 #[macro_use]
 extern crate lazy_static;
@@ -10,46 +15,100 @@ extern crate libc;
 extern crate nautilus_extension_sys as nautilus_ffi;
 
 pub use crate::column_provider::{Column, ColumnProvider};
-pub use crate::info_provider::{FileInfo, InfoProvider};
-pub use crate::menu_provider::{Menu, MenuItem, MenuProvider};
-pub use crate::nautilus_module::NautilusModule;
+pub use crate::command::{Command, CommandError};
+pub use crate::condition::Condition;
+#[cfg(feature = "declarative")]
+pub use crate::declarative::{DeclarativeExtension, DefinitionError};
+pub use crate::file_manager_actions::FileManagerActions;
+pub use crate::glib_ffi::GType;
+pub use crate::gobject_ffi::GTypeModule;
+#[cfg(not(feature = "gtk-rs"))]
+pub use crate::info_provider::GFileRef;
+pub use crate::info_provider::{FileInfo, FileType, InfoProvider};
+pub use crate::menu_provider::{Menu, MenuItem, MenuProvider, MenuProviderHandle};
+pub use crate::nautilus_module::{ModuleTypes, NautilusModule, DEFAULT_SHUTDOWN_TIMEOUT};
+#[cfg(nautilus_43)]
+pub use crate::properties_model_provider::{
+    PropertiesModel, PropertiesModelProvider, PropertiesModelUpdater,
+};
+#[cfg(not(nautilus_43))]
 pub use crate::property_page_provider::{PropertyPage, PropertyPageProvider};
+#[cfg(feature = "scripting")]
+pub use crate::script::{ScriptError, ScriptPermissions, ScriptProvider};
+pub use crate::selection::Selection;
+pub use crate::stats::{set_slow_callback_budget, ModuleStats};
+#[cfg(feature = "wasm")]
+pub use crate::wasm::{WasmError, WasmLimits, WasmProvider};
+#[cfg(feature = "subclass")]
+pub use glib;
+#[cfg(feature = "gtk-rs")]
+pub use gtk;
 pub use lazy_static::lazy_static;
+#[cfg(feature = "macros")]
+pub use nautilus_extension_macros::{
+    menu_background_activate, menu_item_activate, nautilus_extension, ColumnProvider,
+};
 
 pub mod column_provider;
+pub mod command;
+pub mod condition;
+#[cfg(feature = "declarative")]
+pub mod declarative;
+mod desktop_file;
+pub mod file_manager_actions;
 pub mod info_provider;
+mod logging;
+mod main_loop;
 pub mod menu_provider;
+#[cfg(feature = "mock")]
+pub mod mock;
 mod nautilus_module;
+mod nemo_action;
+#[cfg(nautilus_43)]
+pub mod properties_model_provider;
+#[cfg(not(nautilus_43))]
 pub mod property_page_provider;
+pub mod render;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod selection;
+mod service_menu;
+pub mod stats;
+#[cfg(feature = "subclass")]
+pub mod subclass;
 mod translate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::glib_ffi::{gpointer, GType};
+    pub use crate::gobject_ffi::{GObject, GTypeModule};
+    pub use crate::nautilus_module::{add_module_types, list_module_types};
+    pub use libc::c_int;
+}
 
 #[macro_export]
 macro_rules! nautilus_module {
-    ($register_fn:ident) => {
-        use std::sync::Mutex as NautilusExtensionMutex;
-        use nautilus_extension::lazy_static as nautilus_extension_lazy_static;
-        nautilus_extension_lazy_static! {
-            static ref MODULE_TYPE_LIST: NautilusExtensionMutex<Vec<GType>> = NautilusExtensionMutex::new(Vec::new());
-        }
-
+    ($($register_fn:ident),+ $(,)?) => {
         #[no_mangle]
-        pub extern "C" fn nautilus_module_initialize(module: *mut GTypeModule) {
-            let module_type: GType = $register_fn(module);
-            MODULE_TYPE_LIST.lock().unwrap().push(module_type);
+        pub extern "C" fn nautilus_module_initialize(module: *mut $crate::__private::GTypeModule) {
+            $(
+                $crate::__private::add_module_types($register_fn(module));
+            )+
         }
 
         #[no_mangle]
-        pub unsafe extern "C" fn nautilus_module_list_types(types: *mut *const GType, num_types: *mut c_int) {
-            let list_ptr = MODULE_TYPE_LIST.lock().unwrap().as_ptr();
-            let list_len = MODULE_TYPE_LIST.lock().unwrap().len() as c_int;
-            unsafe {
-                *types = list_ptr;
-                *num_types = list_len;
-            }
+        pub unsafe extern "C" fn nautilus_module_list_types(
+            types: *mut *const $crate::__private::GType,
+            num_types: *mut $crate::__private::c_int,
+        ) {
+            $crate::__private::list_module_types(types, num_types);
         }
 
         #[no_mangle]
         pub extern "C" fn nautilus_module_shutdown() {
+            $crate::NautilusModule::shutdown();
         }
     }
 }
@@ -58,7 +117,10 @@ macro_rules! nautilus_module {
 macro_rules! nautilus_menu_item_activate_cb {
     ($extern_fn:ident, $safe_fn:ident) => {
         #[no_mangle]
-        pub unsafe extern "C" fn $extern_fn(_nautilusmenuitem: *mut GObject, user_data: gpointer) {
+        pub unsafe extern "C" fn $extern_fn(
+            _nautilusmenuitem: *mut $crate::__private::GObject,
+            user_data: $crate::__private::gpointer,
+        ) {
             use std::mem;
             use $crate::info_provider::FileInfo;
 
@@ -72,7 +134,10 @@ macro_rules! nautilus_menu_item_activate_cb {
 macro_rules! nautilus_menu_background_activate_cb {
     ($extern_fn:ident, $safe_fn:ident) => {
         #[no_mangle]
-        pub unsafe extern "C" fn $extern_fn(_nautilusmenuitem: *mut GObject, user_data: gpointer) {
+        pub unsafe extern "C" fn $extern_fn(
+            _nautilusmenuitem: *mut $crate::__private::GObject,
+            user_data: $crate::__private::gpointer,
+        ) {
             use std::mem;
             use $crate::info_provider::FileInfo;
 
@@ -0,0 +1,67 @@
+#[cfg(feature = "scripting")]
+use crate::glib_ffi::G_LOG_LEVEL_MESSAGE;
+use crate::glib_ffi::{g_log, GLogLevelFlags, G_LOG_LEVEL_WARNING};
+use libc::c_char;
+use std::ffi::CString;
+
+const LOG_DOMAIN: &[u8] = b"nautilus-extension-rs\0";
+const FORMAT: &[u8] = b"%s\0";
+
+pub fn warning(message: &str) {
+    log(G_LOG_LEVEL_WARNING, message);
+}
+
+#[cfg(feature = "scripting")]
+pub fn message(message: &str) {
+    log(G_LOG_LEVEL_MESSAGE, message);
+}
+
+fn log(level: GLogLevelFlags, message: &str) {
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    unsafe {
+        g_log(
+            LOG_DOMAIN.as_ptr() as *const c_char,
+            level,
+            FORMAT.as_ptr() as *const c_char,
+            message.as_ptr(),
+        );
+    }
+}
+
+/// The warnings logged while `f` runs, by any thread. Warnings are not printed meanwhile.
+#[cfg(test)]
+pub fn capture_warnings<F: FnOnce()>(f: F) -> Vec<String> {
+    use crate::glib_ffi::{g_log_remove_handler, g_log_set_handler, gpointer};
+    use std::ffi::CStr;
+    use std::sync::Mutex;
+
+    lazy_static! {
+        /// Serializes captures, as the handler catches the warnings of every thread.
+        static ref CAPTURING: Mutex<()> = Mutex::new(());
+    }
+
+    unsafe extern "C" fn handler(
+        _domain: *const c_char,
+        _level: GLogLevelFlags,
+        message: *const c_char,
+        warnings: gpointer,
+    ) {
+        let warnings = &*(warnings as *const Mutex<Vec<String>>);
+        let message = CStr::from_ptr(message).to_string_lossy().into_owned();
+        warnings.lock().unwrap().push(message);
+    }
+
+    let _capturing = CAPTURING.lock().unwrap_or_else(|error| error.into_inner());
+    let warnings: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    unsafe {
+        let handler_id = g_log_set_handler(
+            LOG_DOMAIN.as_ptr() as *const c_char,
+            G_LOG_LEVEL_WARNING,
+            Some(handler),
+            &warnings as *const Mutex<Vec<String>> as gpointer,
+        );
+        f();
+        g_log_remove_handler(LOG_DOMAIN.as_ptr() as *const c_char, handler_id);
+    }
+    warnings.into_inner().unwrap()
+}
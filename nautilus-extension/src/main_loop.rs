@@ -0,0 +1,25 @@
+use crate::glib_ffi::{g_main_context_invoke, gboolean, gpointer, GFALSE};
+use std::ptr;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Run `job` on the thread owning the default main context, where Nautilus runs its UI.
+///
+/// Runs `job` right away when called from that thread, or when no main loop is running, as in
+/// tests. Otherwise it is queued and runs on the next main loop iteration.
+pub(crate) fn invoke<F: FnOnce() + Send + 'static>(job: F) {
+    let job: Box<Job> = Box::new(Box::new(job));
+    unsafe {
+        g_main_context_invoke(
+            ptr::null_mut(),
+            Some(run_job),
+            Box::into_raw(job) as gpointer,
+        );
+    }
+}
+
+unsafe extern "C" fn run_job(data: gpointer) -> gboolean {
+    let job = Box::from_raw(data as *mut Job);
+    job();
+    GFALSE
+}
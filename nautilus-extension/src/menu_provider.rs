@@ -1,29 +1,85 @@
-use crate::glib_ffi::{g_list_append, gpointer, GList};
-use crate::gobject_ffi::{g_signal_connect_data, GObject};
+use crate::command::{connect_command, Command};
+use crate::condition::Condition;
+use crate::glib_ffi::{g_list_append, gboolean, gpointer, GList, GType};
+use crate::gobject_ffi::{
+    g_object_ref, g_object_set, g_object_unref, g_object_weak_ref, g_signal_connect_data, GClosure,
+    GObject, GTypeClass, GTypeInstance,
+};
+#[cfg(not(nautilus_43))]
 use crate::gtk_ffi::GtkWidget;
 use crate::info_provider::FileInfo;
+use crate::main_loop;
 use crate::nautilus_ffi::{
     nautilus_menu_append_item, nautilus_menu_item_new, nautilus_menu_item_set_submenu,
-    nautilus_menu_new,
+    nautilus_menu_new, nautilus_menu_provider_emit_items_updated_signal, NautilusMenuProvider,
 };
 use crate::nautilus_ffi::{
     NautilusFileInfo, NautilusMenu, NautilusMenuItem, NautilusMenuProviderIface,
 };
+use crate::render::{self, RenderItem};
+use crate::selection::Selection;
+use crate::stats::{self, Callback, ProviderKind};
 use crate::translate::file_info_vec_from_g_list;
-use libc::c_void;
+#[cfg(feature = "gtk-rs")]
+use gtk::glib::translate::from_glib_none;
+#[cfg(feature = "gtk-rs")]
+use gtk::glib::Cast;
+use libc::{c_char, c_void};
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::ffi::CString;
+use std::fmt;
 use std::mem;
 use std::ptr;
+use std::rc::Rc;
+use std::slice;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 pub trait MenuProvider: Send + Sync {
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
     #[allow(unused_variables)]
     fn get_file_items(&self, window: *mut GtkWidget, files: &[FileInfo]) -> Vec<MenuItem> {
         Vec::new()
     }
 
+    /// `window` is `None` when Nautilus passes no window, as the mock does.
+    #[cfg(feature = "gtk-rs")]
+    #[allow(unused_variables)]
+    fn get_file_items(&self, window: Option<&gtk::Window>, files: &[FileInfo]) -> Vec<MenuItem> {
+        Vec::new()
+    }
+
+    /// Nautilus 4 no longer passes the window.
+    #[cfg(nautilus_43)]
+    #[allow(unused_variables)]
+    fn get_file_items(&self, files: &[FileInfo]) -> Vec<MenuItem> {
+        Vec::new()
+    }
+
+    /// Called by the glue for each menu request, with the files as a `Selection` whose summary
+    /// is gathered on first use. Calls `get_file_items()` unless overridden.
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    fn get_selection_items(&self, window: *mut GtkWidget, selection: &Selection) -> Vec<MenuItem> {
+        self.get_file_items(window, selection.files())
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    fn get_selection_items(
+        &self,
+        window: Option<&gtk::Window>,
+        selection: &Selection,
+    ) -> Vec<MenuItem> {
+        self.get_file_items(window, selection.files())
+    }
+
+    #[cfg(nautilus_43)]
+    fn get_selection_items(&self, selection: &Selection) -> Vec<MenuItem> {
+        self.get_file_items(selection.files())
+    }
+
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
     #[allow(unused_variables)]
     fn get_background_items(
         &self,
@@ -32,9 +88,29 @@ pub trait MenuProvider: Send + Sync {
     ) -> Vec<MenuItem> {
         Vec::new()
     }
+
+    #[cfg(feature = "gtk-rs")]
+    #[allow(unused_variables)]
+    fn get_background_items(
+        &self,
+        window: Option<&gtk::Window>,
+        current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        Vec::new()
+    }
+
+    /// Nautilus 4 no longer passes the window.
+    #[cfg(nautilus_43)]
+    #[allow(unused_variables)]
+    fn get_background_items(&self, current_folder: &FileInfo) -> Vec<MenuItem> {
+        Vec::new()
+    }
+
+    /// Called from `nautilus_module_shutdown()`, right before the provider is dropped.
+    fn shutdown(&self) {}
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Menu {
     menu_items: Vec<MenuItem>,
 }
@@ -46,7 +122,27 @@ impl Menu {
         }
     }
 
-    fn to_g_list(&self, files_user_data: *mut c_void) -> *mut GList {
+    pub fn items(&self) -> &[MenuItem] {
+        &self.menu_items
+    }
+
+    /// Render the menu tree as text, one item per line. See `render::items_to_text()`.
+    pub fn to_text(&self) -> String {
+        render::items_to_text(&self.menu_items)
+    }
+
+    /// Render the menu tree as a JSON array. See `render::items_to_json()`.
+    pub fn to_json(&self) -> String {
+        render::items_to_json(&self.menu_items)
+    }
+
+    /// `files` are the files of the menu request, or the current folder for background items.
+    pub(crate) fn to_g_list(
+        &self,
+        files_user_data: *mut c_void,
+        files: &[FileInfo],
+        background: bool,
+    ) -> *mut GList {
         let mut raw_file_items: *mut GList = ptr::null_mut();
 
         for menu_item in &self.menu_items {
@@ -60,11 +156,14 @@ impl Menu {
 
             unsafe {
                 let raw_menuitem = nautilus_menu_item_new(raw_name, raw_label, raw_tip, raw_icon);
+                set_item_properties(raw_menuitem, menu_item);
                 raw_file_items = g_list_append(raw_file_items, raw_menuitem as *mut c_void);
 
                 let submenu = &menu_item.submenu;
                 match *submenu {
-                    Some(ref submenu) => process_submenu(raw_menuitem, submenu, files_user_data),
+                    Some(ref submenu) => {
+                        process_submenu(raw_menuitem, submenu, files_user_data, files, background)
+                    }
                     None => (),
                 }
 
@@ -75,6 +174,14 @@ impl Menu {
                     None => (),
                 }
 
+                if let Some(ref command) = menu_item.command {
+                    connect_command(raw_menuitem, command, files, background);
+                }
+
+                if let Some(ref handler) = menu_item.handler {
+                    connect_handler(raw_menuitem, handler, files);
+                }
+
                 // deallocate CStrings
                 let _ = CString::from_raw(raw_name);
                 let _ = CString::from_raw(raw_label);
@@ -88,7 +195,12 @@ impl Menu {
         raw_file_items
     }
 
-    fn to_raw(&self, files_user_data: *mut c_void) -> *mut NautilusMenu {
+    fn to_raw(
+        &self,
+        files_user_data: *mut c_void,
+        files: &[FileInfo],
+        background: bool,
+    ) -> *mut NautilusMenu {
         let raw_menu = unsafe { nautilus_menu_new() };
 
         let menu_items = &self.menu_items;
@@ -104,11 +216,14 @@ impl Menu {
 
             unsafe {
                 let raw_menuitem = nautilus_menu_item_new(raw_name, raw_label, raw_tip, raw_icon);
+                set_item_properties(raw_menuitem, menu_item);
                 nautilus_menu_append_item(raw_menu, raw_menuitem);
 
                 let submenu = &menu_item.submenu;
                 match *submenu {
-                    Some(ref submenu) => process_submenu(raw_menuitem, submenu, files_user_data),
+                    Some(ref submenu) => {
+                        process_submenu(raw_menuitem, submenu, files_user_data, files, background)
+                    }
                     None => (),
                 }
 
@@ -119,6 +234,14 @@ impl Menu {
                     None => (),
                 }
 
+                if let Some(ref command) = menu_item.command {
+                    connect_command(raw_menuitem, command, files, background);
+                }
+
+                if let Some(ref handler) = menu_item.handler {
+                    connect_handler(raw_menuitem, handler, files);
+                }
+
                 // deallocate CStrings
                 let _ = CString::from_raw(raw_name);
                 let _ = CString::from_raw(raw_label);
@@ -133,16 +256,39 @@ impl Menu {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct MenuItem {
     name: Cow<'static, str>,
     label: Cow<'static, str>,
     tip: Cow<'static, str>,
     icon: Option<Cow<'static, str>>,
+    sensitive: bool,
+    priority: bool,
+    condition: Option<Condition>,
+    command: Option<Command>,
+    handler: Option<Handler>,
     submenu: Option<Menu>,
     activate_fn: Option<unsafe extern "C" fn(*mut GObject, gpointer)>,
 }
 
+type ActivateHandler = dyn Fn(&[FileInfo]) + Send + Sync;
+
+#[derive(Clone)]
+struct Handler(Arc<ActivateHandler>);
+
+impl fmt::Debug for Handler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Handler")
+    }
+}
+
+impl PartialEq for Handler {
+    /// Handlers compare by address.
+    fn eq(&self, other: &Handler) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
 impl MenuItem {
     pub fn new<S: Into<Cow<'static, str>>>(name: S, label: S, tip: S, icon: Option<S>) -> MenuItem {
         let icon = icon.map(|s| s.into());
@@ -151,6 +297,11 @@ impl MenuItem {
             label: label.into(),
             tip: tip.into(),
             icon,
+            sensitive: true,
+            priority: true,
+            condition: None,
+            command: None,
+            handler: None,
             submenu: None,
             activate_fn: None,
         }
@@ -161,6 +312,42 @@ impl MenuItem {
         self
     }
 
+    /// Insensitive items are shown greyed out and cannot be activated. Use the tip to say why.
+    /// Defaults to `true`.
+    pub fn set_sensitive(&mut self, sensitive: bool) -> &mut MenuItem {
+        self.sensitive = sensitive;
+        self
+    }
+
+    /// Whether the label is shown when the item appears in a toolbar. Defaults to `true`.
+    pub fn set_priority(&mut self, priority: bool) -> &mut MenuItem {
+        self.priority = priority;
+        self
+    }
+
+    /// Only show the item when `condition` holds for the files it is offered for.
+    pub fn show_when(&mut self, condition: Condition) -> &mut MenuItem {
+        self.condition = Some(condition);
+        self
+    }
+
+    /// Run `command` on the files when the item is activated, in addition to any activate
+    /// callback.
+    pub fn set_command(&mut self, command: Command) -> &mut MenuItem {
+        self.command = Some(command);
+        self
+    }
+
+    /// Call `handler` with the files when the item is activated, in addition to any activate
+    /// callback or command. Unlike `set_activate_cb()`, the handler may keep state of its own.
+    pub fn on_activate<F>(&mut self, handler: F) -> &mut MenuItem
+    where
+        F: Fn(&[FileInfo]) + Send + Sync + 'static,
+    {
+        self.handler = Some(Handler(Arc::new(handler)));
+        self
+    }
+
     pub fn set_activate_cb(
         &mut self,
         activate_cb: unsafe extern "C" fn(*mut GObject, gpointer),
@@ -168,10 +355,174 @@ impl MenuItem {
         self.activate_fn = Some(activate_cb);
         self
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn tip(&self) -> &str {
+        &self.tip
+    }
+
+    pub fn icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+
+    pub fn sensitive(&self) -> bool {
+        self.sensitive
+    }
+
+    pub fn priority(&self) -> bool {
+        self.priority
+    }
+
+    pub fn condition(&self) -> Option<&Condition> {
+        self.condition.as_ref()
+    }
+
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+
+    pub fn submenu(&self) -> Option<&Menu> {
+        self.submenu.as_ref()
+    }
+
+    pub fn activate_cb(&self) -> Option<unsafe extern "C" fn(*mut GObject, gpointer)> {
+        self.activate_fn
+    }
+}
+
+impl PartialEq for MenuItem {
+    /// Activate callbacks compare by address.
+    fn eq(&self, other: &MenuItem) -> bool {
+        self.name == other.name
+            && self.label == other.label
+            && self.tip == other.tip
+            && self.icon == other.icon
+            && self.sensitive == other.sensitive
+            && self.priority == other.priority
+            && self.condition == other.condition
+            && self.command == other.command
+            && self.handler == other.handler
+            && self.submenu == other.submenu
+            && self.activate_fn.map(|f| f as usize) == other.activate_fn.map(|f| f as usize)
+    }
+}
+
+impl RenderItem for MenuItem {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn tip(&self) -> &str {
+        &self.tip
+    }
+
+    fn icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+
+    fn sensitive(&self) -> bool {
+        self.sensitive
+    }
+
+    fn priority(&self) -> bool {
+        self.priority
+    }
+
+    fn has_activate_handler(&self) -> bool {
+        self.activate_fn.is_some() || self.command.is_some() || self.handler.is_some()
+    }
+
+    fn submenu(&self) -> &[MenuItem] {
+        match self.submenu {
+            Some(ref submenu) => &submenu.menu_items,
+            None => &[],
+        }
+    }
+}
+
+/// Tells Nautilus that the menu items of a `NautilusModule`'s type have changed, so open views
+/// ask its menu providers for them again. Get one from `NautilusModule::menu_provider_handle()`.
+#[derive(Clone, Debug, Default)]
+pub struct MenuProviderHandle {
+    module_type: Arc<AtomicUsize>,
+}
+
+impl MenuProviderHandle {
+    pub(crate) fn new(module_type: Arc<AtomicUsize>) -> MenuProviderHandle {
+        MenuProviderHandle { module_type }
+    }
+
+    /// Emit `items-updated` on the instances of the type. Can be called from any thread; the
+    /// signal is emitted from the main loop. Does nothing before the type is registered.
+    pub fn notify_items_updated(&self) {
+        let module_type = self.module_type.load(Ordering::SeqCst);
+        if module_type == 0 {
+            return;
+        }
+
+        main_loop::invoke(move || {
+            let instances: Vec<usize> = MENU_PROVIDER_INSTANCES
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|&&(instance_type, _)| instance_type == module_type)
+                .map(|&(_, instance)| unsafe { g_object_ref(instance as *mut GObject) } as usize)
+                .collect();
+
+            for instance in instances {
+                unsafe {
+                    nautilus_menu_provider_emit_items_updated_signal(
+                        instance as *mut NautilusMenuProvider,
+                    );
+                    g_object_unref(instance as *mut GObject);
+                }
+            }
+        });
+    }
+}
+
+lazy_static! {
+    /// Live instances of types with menu providers, by type. Nautilus creates one per type.
+    static ref MENU_PROVIDER_INSTANCES: Mutex<Vec<(GType, usize)>> = Mutex::new(Vec::new());
+}
+
+/// `instance_init` of types with menu providers, so `MenuProviderHandle` can find them.
+pub(crate) unsafe extern "C" fn track_menu_provider_instance(
+    instance: *mut GTypeInstance,
+    class: gpointer,
+) {
+    let instance_type = (*(class as *mut GTypeClass)).g_type;
+    MENU_PROVIDER_INSTANCES
+        .lock()
+        .unwrap()
+        .push((instance_type, instance as usize));
+    g_object_weak_ref(
+        instance as *mut GObject,
+        Some(untrack_menu_provider_instance),
+        ptr::null_mut(),
+    );
+}
+
+unsafe extern "C" fn untrack_menu_provider_instance(_data: gpointer, instance: *mut GObject) {
+    MENU_PROVIDER_INSTANCES
+        .lock()
+        .unwrap()
+        .retain(|&(_, tracked)| tracked != instance as usize);
 }
 
 macro_rules! menu_provider_iface {
-    ($iface_init_fn:ident, $get_file_items_fn:ident, $get_background_items_fn:ident, $rust_provider:ident, $set_rust_provider:ident) => {
+    ($index:expr, $iface_init_fn:ident, $get_file_items_fn:ident, $get_background_items_fn:ident, $rust_provider:ident, $set_rust_provider:ident, $shutdown_rust_provider:ident) => {
         /// # Safety
         ///
         /// This generated function is used as a Nautilus callback. Do not call directly.
@@ -183,72 +534,262 @@ macro_rules! menu_provider_iface {
             (*iface_struct).get_background_items = Some($get_background_items_fn);
         }
 
+        #[cfg(not(nautilus_43))]
+        #[cfg_attr(feature = "gtk-rs", allow(clippy::not_unsafe_ptr_arg_deref))]
         #[no_mangle]
         pub extern "C" fn $get_file_items_fn(
             _provider: *mut c_void,
             window: *mut GtkWidget,
             files: *mut GList,
         ) -> *mut GList {
-            if files.is_null() {
-                return ptr::null_mut() as *mut GList;
-            }
-
-            let files_vec = file_info_vec_from_g_list(files);
-
-            let file_items: Vec<MenuItem> = match *$rust_provider.lock().unwrap() {
-                Some(ref p) => p.get_file_items(window, &files_vec),
-                None => Vec::new(),
-            };
-
-            // dummy top-level Menu for easy recursion
-            let top_menu = Menu {
-                menu_items: file_items,
-            };
+            #[cfg(feature = "gtk-rs")]
+            let window = unsafe { window_from_raw(window) };
+            #[cfg(feature = "gtk-rs")]
+            let window = window.as_ref();
+
+            file_items_to_g_list($index, files, |selection| {
+                match *$rust_provider.lock().unwrap() {
+                    Some(ref p) => p.get_selection_items(window, selection),
+                    None => Vec::new(),
+                }
+            })
+        }
 
-            top_menu.to_g_list(Box::into_raw(Box::new(files_vec)) as *mut c_void)
+        #[cfg(nautilus_43)]
+        #[no_mangle]
+        pub extern "C" fn $get_file_items_fn(
+            _provider: *mut c_void,
+            files: *mut GList,
+        ) -> *mut GList {
+            file_items_to_g_list($index, files, |selection| {
+                match *$rust_provider.lock().unwrap() {
+                    Some(ref p) => p.get_selection_items(selection),
+                    None => Vec::new(),
+                }
+            })
         }
 
+        #[cfg(not(nautilus_43))]
+        #[cfg_attr(feature = "gtk-rs", allow(clippy::not_unsafe_ptr_arg_deref))]
         #[no_mangle]
         pub extern "C" fn $get_background_items_fn(
             _provider: *mut c_void,
             window: *mut GtkWidget,
             current_folder: *mut NautilusFileInfo,
         ) -> *mut GList {
-            if current_folder.is_null() {
-                return ptr::null_mut() as *mut GList;
-            }
-
-            let file_info = FileInfo::new(current_folder);
-
-            let file_items: Vec<MenuItem> = match *$rust_provider.lock().unwrap() {
-                Some(ref p) => p.get_background_items(window, &file_info),
-                None => Vec::new(),
-            };
-
-            // dummy top-level Menu for easy recursion
-            let top_menu = Menu {
-                menu_items: file_items,
-            };
+            #[cfg(feature = "gtk-rs")]
+            let window = unsafe { window_from_raw(window) };
+            #[cfg(feature = "gtk-rs")]
+            let window = window.as_ref();
+
+            background_items_to_g_list($index, current_folder, |folder| {
+                match *$rust_provider.lock().unwrap() {
+                    Some(ref p) => p.get_background_items(window, folder),
+                    None => Vec::new(),
+                }
+            })
+        }
 
-            top_menu.to_g_list(Box::into_raw(Box::new(file_info)) as *mut c_void)
+        #[cfg(nautilus_43)]
+        #[no_mangle]
+        pub extern "C" fn $get_background_items_fn(
+            _provider: *mut c_void,
+            current_folder: *mut NautilusFileInfo,
+        ) -> *mut GList {
+            background_items_to_g_list($index, current_folder, |folder| {
+                match *$rust_provider.lock().unwrap() {
+                    Some(ref p) => p.get_background_items(folder),
+                    None => Vec::new(),
+                }
+            })
         }
 
         pub fn $set_rust_provider(menu_provider: Box<dyn MenuProvider>) {
             *$rust_provider.lock().unwrap() = Some(menu_provider);
         }
 
+        pub fn $shutdown_rust_provider() {
+            let provider = $rust_provider.lock().unwrap().take();
+            if let Some(provider) = provider {
+                provider.shutdown();
+            }
+        }
+
         lazy_static! {
-            static ref $rust_provider: Mutex<Option<Box<dyn MenuProvider>>> = Mutex::new(None);
+            static ref $rust_provider: RustMenuProvider = Mutex::new(None);
         }
     };
 }
 
+type RustMenuProvider = Mutex<Option<Box<dyn MenuProvider>>>;
+
+/// Nautilus passes the `NautilusWindow` the menu is for, which is a `GtkWindow`.
+#[cfg(feature = "gtk-rs")]
+pub(crate) unsafe fn window_from_raw(window: *mut GtkWidget) -> Option<gtk::Window> {
+    if window.is_null() {
+        return None;
+    }
+    let widget: gtk::Widget = from_glib_none(window);
+    widget.downcast().ok()
+}
+
+/// The items `get_file_items` returns for `files`, timed as menu provider `index`, as a list
+/// for Nautilus.
+pub(crate) fn file_items_to_g_list<F>(
+    index: usize,
+    files: *mut GList,
+    get_file_items: F,
+) -> *mut GList
+where
+    F: FnOnce(&Selection) -> Vec<MenuItem>,
+{
+    if files.is_null() {
+        return ptr::null_mut();
+    }
+
+    let files_vec = file_info_vec_from_g_list(files);
+    let selection = shared_selection(files, &files_vec);
+
+    let file_items: Vec<MenuItem> =
+        stats::timed(ProviderKind::Menu, index, Callback::GetFileItems, || {
+            get_file_items(&selection)
+        });
+
+    // dummy top-level Menu for easy recursion
+    let top_menu = Menu {
+        menu_items: shown_items(file_items, &files_vec),
+    };
+
+    let files_user_data = Box::into_raw(Box::new(files_vec));
+    top_menu.to_g_list(
+        files_user_data as *mut c_void,
+        unsafe { &*files_user_data },
+        false,
+    )
+}
+
+/// How long the `Selection` of a menu request is reused by the next provider called with the
+/// same list of files.
+const SELECTION_REUSE: Duration = Duration::from_millis(500);
+
+struct SharedSelection {
+    list: usize,
+    files: Vec<usize>,
+    built: Instant,
+    selection: Rc<Selection>,
+}
+
+thread_local! {
+    /// The `Selection` of the last menu request on this thread, kept until the next one.
+    static LAST_SELECTION: RefCell<Option<SharedSelection>> = const { RefCell::new(None) };
+}
+
+/// The `Selection` of `files_vec`, shared by the providers of one menu request. Nautilus calls
+/// them one after the other with the same list, which is freed after the request, so the list
+/// must hold the same files and have been seen shortly before to be the same request.
+fn shared_selection(files: *mut GList, files_vec: &[FileInfo]) -> Rc<Selection> {
+    let raw_files: Vec<usize> = files_vec
+        .iter()
+        .map(|file| file.raw_file_info as usize)
+        .collect();
+
+    LAST_SELECTION.with(|last| {
+        let mut last = last.borrow_mut();
+        if let Some(ref shared) = *last {
+            if shared.list == files as usize
+                && shared.files == raw_files
+                && shared.built.elapsed() < SELECTION_REUSE
+            {
+                return shared.selection.clone();
+            }
+        }
+
+        let selection = Rc::new(Selection::new(files_vec));
+        *last = Some(SharedSelection {
+            list: files as usize,
+            files: raw_files,
+            built: Instant::now(),
+            selection: selection.clone(),
+        });
+        selection
+    })
+}
+
+/// The items `get_background_items` returns for `current_folder`, timed as menu provider
+/// `index`, as a list for Nautilus.
+pub(crate) fn background_items_to_g_list<F>(
+    index: usize,
+    current_folder: *mut NautilusFileInfo,
+    get_background_items: F,
+) -> *mut GList
+where
+    F: FnOnce(&FileInfo) -> Vec<MenuItem>,
+{
+    if current_folder.is_null() {
+        return ptr::null_mut();
+    }
+
+    let file_info = FileInfo::new(current_folder);
+
+    let file_items: Vec<MenuItem> = stats::timed(
+        ProviderKind::Menu,
+        index,
+        Callback::GetBackgroundItems,
+        || get_background_items(&file_info),
+    );
+
+    // dummy top-level Menu for easy recursion
+    let top_menu = Menu {
+        menu_items: shown_items(file_items, slice::from_ref(&file_info)),
+    };
+
+    let files_user_data = Box::into_raw(Box::new(file_info));
+    top_menu.to_g_list(
+        files_user_data as *mut c_void,
+        slice::from_ref(unsafe { &*files_user_data }),
+        true,
+    )
+}
+
+/// The items whose condition holds for `files`, with their submenus filtered the same way.
+pub(crate) fn shown_items(items: Vec<MenuItem>, files: &[FileInfo]) -> Vec<MenuItem> {
+    items
+        .into_iter()
+        .filter(|item| item.condition.iter().all(|c| c.matches(files)))
+        .map(|mut item| {
+            if let Some(submenu) = item.submenu.take() {
+                item.submenu = Some(Menu {
+                    menu_items: shown_items(submenu.menu_items, files),
+                });
+            }
+            item
+        })
+        .collect()
+}
+
+/// Apply the properties `nautilus_menu_item_new()` does not take.
+unsafe fn set_item_properties(raw_menuitem: *mut NautilusMenuItem, menu_item: &MenuItem) {
+    let sensitive_name = CString::new("sensitive").unwrap();
+    let priority_name = CString::new("priority").unwrap();
+
+    g_object_set(
+        raw_menuitem as *mut GObject,
+        sensitive_name.as_ptr(),
+        menu_item.sensitive as gboolean,
+        priority_name.as_ptr(),
+        menu_item.priority as gboolean,
+        ptr::null::<c_char>(),
+    );
+}
+
 fn process_submenu(
     raw_menuitem: *mut NautilusMenuItem,
     submenu: &Menu,
     files_user_data: *mut c_void,
+    files: &[FileInfo],
+    background: bool,
 ) {
-    let raw_submenu = submenu.to_raw(files_user_data);
+    let raw_submenu = submenu.to_raw(files_user_data, files, background);
     unsafe {
         nautilus_menu_item_set_submenu(raw_menuitem, raw_submenu);
     }
@@ -276,16 +817,56 @@ fn connect_activate_signal(
     }
 }
 
-#[rustfmt::skip] menu_provider_iface!(menu_provider_iface_init_0, menu_provider_get_file_items_0, menu_provider_get_background_items_0, MENU_PROVIDER_0, set_menu_provider_0);
-#[rustfmt::skip] menu_provider_iface!(menu_provider_iface_init_1, menu_provider_get_file_items_1, menu_provider_get_background_items_1, MENU_PROVIDER_1, set_menu_provider_1);
-#[rustfmt::skip] menu_provider_iface!(menu_provider_iface_init_2, menu_provider_get_file_items_2, menu_provider_get_background_items_2, MENU_PROVIDER_2, set_menu_provider_2);
-#[rustfmt::skip] menu_provider_iface!(menu_provider_iface_init_3, menu_provider_get_file_items_3, menu_provider_get_background_items_3, MENU_PROVIDER_3, set_menu_provider_3);
-#[rustfmt::skip] menu_provider_iface!(menu_provider_iface_init_4, menu_provider_get_file_items_4, menu_provider_get_background_items_4, MENU_PROVIDER_4, set_menu_provider_4);
-#[rustfmt::skip] menu_provider_iface!(menu_provider_iface_init_5, menu_provider_get_file_items_5, menu_provider_get_background_items_5, MENU_PROVIDER_5, set_menu_provider_5);
-#[rustfmt::skip] menu_provider_iface!(menu_provider_iface_init_6, menu_provider_get_file_items_6, menu_provider_get_background_items_6, MENU_PROVIDER_6, set_menu_provider_6);
-#[rustfmt::skip] menu_provider_iface!(menu_provider_iface_init_7, menu_provider_get_file_items_7, menu_provider_get_background_items_7, MENU_PROVIDER_7, set_menu_provider_7);
-#[rustfmt::skip] menu_provider_iface!(menu_provider_iface_init_8, menu_provider_get_file_items_8, menu_provider_get_background_items_8, MENU_PROVIDER_8, set_menu_provider_8);
-#[rustfmt::skip] menu_provider_iface!(menu_provider_iface_init_9, menu_provider_get_file_items_9, menu_provider_get_background_items_9, MENU_PROVIDER_9, set_menu_provider_9);
+/// A handler's files, kept until its menu item is finalized.
+struct HandlerActivation {
+    handler: Handler,
+    files: Selection,
+}
+
+/// Call `handler` with `files` when `raw_menuitem` is activated.
+unsafe fn connect_handler(
+    raw_menuitem: *mut NautilusMenuItem,
+    handler: &Handler,
+    files: &[FileInfo],
+) {
+    let activation = Box::new(HandlerActivation {
+        handler: handler.clone(),
+        files: Selection::new(files),
+    });
+
+    let activate_name = CString::new("activate").unwrap();
+    g_signal_connect_data(
+        raw_menuitem as *mut GObject,
+        activate_name.as_ptr(),
+        Some(mem::transmute::<
+            unsafe extern "C" fn(*mut GObject, gpointer),
+            unsafe extern "C" fn(),
+        >(activate_handler)),
+        Box::into_raw(activation) as gpointer,
+        Some(free_handler_activation),
+        0,
+    );
+}
+
+unsafe extern "C" fn activate_handler(_menuitem: *mut GObject, user_data: gpointer) {
+    let activation = &*(user_data as *const HandlerActivation);
+    (activation.handler.0)(activation.files.files());
+}
+
+unsafe extern "C" fn free_handler_activation(data: gpointer, _closure: *mut GClosure) {
+    drop(Box::from_raw(data as *mut HandlerActivation));
+}
+
+#[rustfmt::skip] menu_provider_iface!(0, menu_provider_iface_init_0, menu_provider_get_file_items_0, menu_provider_get_background_items_0, MENU_PROVIDER_0, set_menu_provider_0, shutdown_menu_provider_0);
+#[rustfmt::skip] menu_provider_iface!(1, menu_provider_iface_init_1, menu_provider_get_file_items_1, menu_provider_get_background_items_1, MENU_PROVIDER_1, set_menu_provider_1, shutdown_menu_provider_1);
+#[rustfmt::skip] menu_provider_iface!(2, menu_provider_iface_init_2, menu_provider_get_file_items_2, menu_provider_get_background_items_2, MENU_PROVIDER_2, set_menu_provider_2, shutdown_menu_provider_2);
+#[rustfmt::skip] menu_provider_iface!(3, menu_provider_iface_init_3, menu_provider_get_file_items_3, menu_provider_get_background_items_3, MENU_PROVIDER_3, set_menu_provider_3, shutdown_menu_provider_3);
+#[rustfmt::skip] menu_provider_iface!(4, menu_provider_iface_init_4, menu_provider_get_file_items_4, menu_provider_get_background_items_4, MENU_PROVIDER_4, set_menu_provider_4, shutdown_menu_provider_4);
+#[rustfmt::skip] menu_provider_iface!(5, menu_provider_iface_init_5, menu_provider_get_file_items_5, menu_provider_get_background_items_5, MENU_PROVIDER_5, set_menu_provider_5, shutdown_menu_provider_5);
+#[rustfmt::skip] menu_provider_iface!(6, menu_provider_iface_init_6, menu_provider_get_file_items_6, menu_provider_get_background_items_6, MENU_PROVIDER_6, set_menu_provider_6, shutdown_menu_provider_6);
+#[rustfmt::skip] menu_provider_iface!(7, menu_provider_iface_init_7, menu_provider_get_file_items_7, menu_provider_get_background_items_7, MENU_PROVIDER_7, set_menu_provider_7, shutdown_menu_provider_7);
+#[rustfmt::skip] menu_provider_iface!(8, menu_provider_iface_init_8, menu_provider_get_file_items_8, menu_provider_get_background_items_8, MENU_PROVIDER_8, set_menu_provider_8, shutdown_menu_provider_8);
+#[rustfmt::skip] menu_provider_iface!(9, menu_provider_iface_init_9, menu_provider_get_file_items_9, menu_provider_get_background_items_9, MENU_PROVIDER_9, set_menu_provider_9, shutdown_menu_provider_9);
 
 pub fn menu_provider_iface_externs() -> Vec<unsafe extern "C" fn(gpointer, gpointer)> {
     vec![
@@ -317,8 +898,123 @@ pub fn rust_menu_provider_setters() -> Vec<fn(Box<dyn MenuProvider>)> {
     ]
 }
 
+pub fn rust_menu_provider_shutdowns() -> Vec<fn()> {
+    vec![
+        shutdown_menu_provider_0,
+        shutdown_menu_provider_1,
+        shutdown_menu_provider_2,
+        shutdown_menu_provider_3,
+        shutdown_menu_provider_4,
+        shutdown_menu_provider_5,
+        shutdown_menu_provider_6,
+        shutdown_menu_provider_7,
+        shutdown_menu_provider_8,
+        shutdown_menu_provider_9,
+    ]
+}
+
 static NEXT_MENU_PROVIDER_IFACE_INDEX: AtomicUsize = AtomicUsize::new(0);
 
 pub fn take_next_menu_provider_iface_index() -> usize {
     NEXT_MENU_PROVIDER_IFACE_INDEX.fetch_add(1, Ordering::SeqCst)
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::{shared_selection, Menu, MenuItem, MenuProvider};
+    use crate::glib_ffi::{g_list_append, g_list_free};
+    use crate::info_provider::FileInfo;
+    use crate::mock::{self, MockFile};
+    use crate::translate::file_info_vec_from_g_list;
+    use std::ptr;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    /// Offers a `Notes::count` item, which records the files it is activated for, in a submenu.
+    struct Counter {
+        activated: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Counter {
+        fn items(&self) -> Vec<MenuItem> {
+            let activated = self.activated.clone();
+            let mut count = MenuItem::new("Notes::count", "Count", "", None);
+            count.on_activate(move |files| {
+                let uris = files.iter().map(FileInfo::get_uri);
+                activated.lock().unwrap().extend(uris);
+            });
+            let mut tools = MenuItem::new("Notes::tools", "Tools", "", None);
+            tools.set_submenu(&Menu::new(&[count]));
+            vec![tools]
+        }
+    }
+
+    impl MenuProvider for Counter {
+        #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+        fn get_file_items(
+            &self,
+            _window: *mut crate::gtk_ffi::GtkWidget,
+            _files: &[FileInfo],
+        ) -> Vec<MenuItem> {
+            self.items()
+        }
+
+        #[cfg(feature = "gtk-rs")]
+        fn get_file_items(
+            &self,
+            _window: Option<&crate::gtk::Window>,
+            _files: &[FileInfo],
+        ) -> Vec<MenuItem> {
+            self.items()
+        }
+
+        #[cfg(nautilus_43)]
+        fn get_file_items(&self, _files: &[FileInfo]) -> Vec<MenuItem> {
+            self.items()
+        }
+    }
+
+    #[test]
+    fn calls_activate_handlers() {
+        let counter = Counter {
+            activated: Arc::new(Mutex::new(Vec::new())),
+        };
+        let notes = MockFile::new("file:///tmp/notes.txt");
+
+        let items = mock::get_file_items(&counter, &[notes]);
+        let count = &items[0].submenu[0];
+        assert!(count.has_activate_handler);
+        assert!(counter.activated.lock().unwrap().is_empty());
+        count.activate();
+        assert_eq!(
+            *counter.activated.lock().unwrap(),
+            ["file:///tmp/notes.txt"]
+        );
+    }
+
+    #[test]
+    fn shares_the_selection_of_a_request() {
+        let photo = MockFile::new("file:///tmp/photo.jpg");
+        let notes = MockFile::new("file:///tmp/notes.txt");
+
+        unsafe {
+            let files = g_list_append(ptr::null_mut(), photo.file_info().raw_file_info as *mut _);
+            let files_vec = file_info_vec_from_g_list(files);
+            let selection = shared_selection(files, &files_vec);
+            assert!(Rc::ptr_eq(&selection, &shared_selection(files, &files_vec)));
+
+            let other_files =
+                g_list_append(ptr::null_mut(), notes.file_info().raw_file_info as *mut _);
+            let other_files_vec = file_info_vec_from_g_list(other_files);
+            let other_selection = shared_selection(other_files, &other_files_vec);
+            assert!(!Rc::ptr_eq(&selection, &other_selection));
+            assert_eq!(
+                other_selection.files()[0].get_uri(),
+                "file:///tmp/notes.txt"
+            );
+
+            g_list_free(files);
+            g_list_free(other_files);
+        }
+    }
+}
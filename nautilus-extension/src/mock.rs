@@ -0,0 +1,823 @@
+//! Drive providers against the in-process mock of libnautilus-extension, without Nautilus.
+//!
+//! Enabled by the `mock` feature. Files are created with `MockFile`, and the `get_*` and
+//! `update_file_info` functions run a provider through the same glue Nautilus goes through,
+//! then read the resulting menu items, columns, property pages or models and attributes back.
+//!
+//! ```ignore
+//! let file = MockFile::new("file:///home/user/photo.jpg").mime_type("image/jpeg");
+//! let items = mock::get_file_items(&MyMenuProvider, &[file.clone()]);
+//! assert_eq!(items[0].label, "Rotate");
+//! ```
+
+use crate::column_provider::{columns_to_g_list, ColumnProvider};
+#[cfg(nautilus_43)]
+use crate::gio_ffi::{g_list_model_get_item, g_list_model_get_n_items};
+use crate::gio_ffi::{G_FILE_TYPE_DIRECTORY, G_FILE_TYPE_REGULAR};
+use crate::glib_ffi::{g_list_free, GList, GType, GFALSE};
+#[cfg(not(nautilus_43))]
+use crate::gobject_ffi::G_TYPE_OBJECT;
+use crate::gobject_ffi::{
+    g_object_get_property, g_object_ref, g_object_unref, g_signal_has_handler_pending,
+    g_signal_lookup, g_value_get_boolean, g_value_get_object, g_value_get_string, g_value_init,
+    g_value_unset, GObject, GValue, G_TYPE_BOOLEAN, G_TYPE_STRING,
+};
+#[cfg(not(nautilus_43))]
+use crate::gtk_ffi::GtkWidget;
+use crate::info_provider::{add_attributes_to_raw, FileInfo, InfoProvider};
+use crate::menu_provider::{shown_items, Menu, MenuProvider};
+use crate::nautilus_ffi::mock::{
+    file_info_new, nautilus_menu_get_type, nautilus_menu_item_get_type, with_file_data,
+    MockFileData,
+};
+use crate::nautilus_ffi::{
+    nautilus_menu_get_items, nautilus_menu_item_activate, nautilus_menu_item_list_free,
+    NautilusFileInfo, NautilusMenu, NautilusMenuItem,
+};
+#[cfg(nautilus_43)]
+use crate::nautilus_ffi::{
+    nautilus_properties_item_get_name, nautilus_properties_item_get_value,
+    nautilus_properties_model_get_model, nautilus_properties_model_get_title,
+    NautilusPropertiesItem, NautilusPropertiesModel,
+};
+#[cfg(nautilus_43)]
+use crate::properties_model_provider::{models_to_g_list, PropertiesModelProvider};
+#[cfg(not(nautilus_43))]
+use crate::property_page_provider::{pages_to_g_list, PropertyPageProvider};
+use crate::render::{self, RenderItem};
+use crate::selection::Selection;
+use libc::{c_char, c_void};
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::mem;
+#[cfg(not(nautilus_43))]
+use std::ptr;
+use std::slice;
+
+/// A file implementing `NautilusFileInfo`, with the URI, MIME type and flags given to the builder.
+pub struct MockFile {
+    raw_file_info: *mut NautilusFileInfo,
+}
+
+unsafe impl Send for MockFile {}
+unsafe impl Sync for MockFile {}
+
+impl MockFile {
+    /// A regular, writable file of type `application/octet-stream`.
+    pub fn new(uri: &str) -> MockFile {
+        MockFile {
+            raw_file_info: unsafe { file_info_new(MockFileData::new(uri)) },
+        }
+    }
+
+    pub fn mime_type(self, mime_type: &str) -> MockFile {
+        self.with_data(|data| data.mime_type = mime_type.to_string());
+        self
+    }
+
+    pub fn directory(self, is_directory: bool) -> MockFile {
+        self.with_data(|data| {
+            data.file_type = if is_directory {
+                G_FILE_TYPE_DIRECTORY
+            } else {
+                G_FILE_TYPE_REGULAR
+            }
+        });
+        self
+    }
+
+    pub fn can_write(self, can_write: bool) -> MockFile {
+        self.with_data(|data| data.can_write = can_write);
+        self
+    }
+
+    /// The `FileInfo` a provider would receive for this file.
+    pub fn file_info(&self) -> FileInfo {
+        FileInfo::new(self.raw_file_info)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<String> {
+        self.with_data(|data| data.attributes.get(name).cloned())
+    }
+
+    /// Attributes added to the file so far, by any provider.
+    pub fn attributes(&self) -> BTreeMap<String, String> {
+        self.with_data(|data| data.attributes.clone())
+    }
+
+    pub fn emblems(&self) -> Vec<String> {
+        self.with_data(|data| data.emblems.clone())
+    }
+
+    /// Number of times `FileInfo::invalidate_extension_info()` was called on the file.
+    pub fn invalidations(&self) -> u32 {
+        self.with_data(|data| data.invalidations)
+    }
+
+    fn with_data<R, F: FnOnce(&mut MockFileData) -> R>(&self, f: F) -> R {
+        unsafe { with_file_data(self.raw_file_info, f) }
+    }
+}
+
+impl Clone for MockFile {
+    /// Another reference to the same file, sharing its attributes.
+    fn clone(&self) -> MockFile {
+        unsafe {
+            g_object_ref(self.raw_file_info as *mut GObject);
+        }
+        MockFile {
+            raw_file_info: self.raw_file_info,
+        }
+    }
+}
+
+impl Drop for MockFile {
+    fn drop(&mut self) {
+        unsafe {
+            g_object_unref(self.raw_file_info as *mut GObject);
+        }
+    }
+}
+
+/// A menu item as Nautilus received it from a `MenuProvider`.
+#[derive(Debug)]
+pub struct MockMenuItem {
+    pub name: String,
+    pub label: String,
+    pub tip: String,
+    pub icon: Option<String>,
+    pub sensitive: bool,
+    pub priority: bool,
+    pub submenu: Vec<MockMenuItem>,
+    pub has_activate_handler: bool,
+    raw_menu_item: *mut NautilusMenuItem,
+}
+
+impl MockMenuItem {
+    /// Emit the `activate` signal, as Nautilus does when the item is clicked.
+    ///
+    /// The handlers connected by `MenuItem::set_activate_cb()` take ownership of the files of the
+    /// menu request, so activate at most one item per `get_file_items()` or
+    /// `get_background_items()` call.
+    pub fn activate(&self) {
+        unsafe {
+            nautilus_menu_item_activate(self.raw_menu_item);
+        }
+    }
+
+    unsafe fn from_raw(raw_menu_item: *mut NautilusMenuItem) -> MockMenuItem {
+        let object = raw_menu_item as *mut GObject;
+        g_object_ref(object);
+
+        let submenu = match object_property(object, b"menu\0", nautilus_menu_get_type()) {
+            Some(raw_menu) => {
+                let items =
+                    menu_items_from_g_list(nautilus_menu_get_items(raw_menu as *mut NautilusMenu));
+                g_object_unref(raw_menu);
+                items
+            }
+            None => Vec::new(),
+        };
+
+        let activate_name = CString::new("activate").unwrap();
+        let activate_signal =
+            g_signal_lookup(activate_name.as_ptr(), nautilus_menu_item_get_type());
+
+        MockMenuItem {
+            name: string_property(object, b"name\0").unwrap_or_default(),
+            label: string_property(object, b"label\0").unwrap_or_default(),
+            tip: string_property(object, b"tip\0").unwrap_or_default(),
+            icon: string_property(object, b"icon\0"),
+            sensitive: boolean_property(object, b"sensitive\0"),
+            priority: boolean_property(object, b"priority\0"),
+            submenu,
+            has_activate_handler: g_signal_has_handler_pending(object, activate_signal, 0, GFALSE)
+                != GFALSE,
+            raw_menu_item,
+        }
+    }
+}
+
+impl RenderItem for MockMenuItem {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn tip(&self) -> &str {
+        &self.tip
+    }
+
+    fn icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+
+    fn sensitive(&self) -> bool {
+        self.sensitive
+    }
+
+    fn priority(&self) -> bool {
+        self.priority
+    }
+
+    fn has_activate_handler(&self) -> bool {
+        self.has_activate_handler
+    }
+
+    fn submenu(&self) -> &[MockMenuItem] {
+        &self.submenu
+    }
+}
+
+impl Drop for MockMenuItem {
+    fn drop(&mut self) {
+        unsafe {
+            g_object_unref(self.raw_menu_item as *mut GObject);
+        }
+    }
+}
+
+/// Render the items like `Menu::to_text()`, so a menu built in Rust and the same menu received by
+/// Nautilus produce the same text.
+pub fn menu_to_text(items: &[MockMenuItem]) -> String {
+    render::items_to_text(items)
+}
+
+/// Render the items like `Menu::to_json()`.
+pub fn menu_to_json(items: &[MockMenuItem]) -> String {
+    render::items_to_json(items)
+}
+
+/// A column as Nautilus received it from a `ColumnProvider`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockColumn {
+    pub name: String,
+    pub attribute: String,
+    pub label: String,
+    pub description: String,
+}
+
+/// A property page as Nautilus received it from a `PropertyPageProvider`. The widgets stay
+/// valid while the value, which holds a reference to the page, exists.
+#[cfg(not(nautilus_43))]
+#[derive(Debug, PartialEq, Eq)]
+pub struct MockPropertyPage {
+    pub name: String,
+    pub raw_label: *mut GtkWidget,
+    pub raw_page: *mut GtkWidget,
+    raw_property_page: *mut GObject,
+}
+
+#[cfg(not(nautilus_43))]
+impl Clone for MockPropertyPage {
+    /// Another reference to the same page and widgets.
+    fn clone(&self) -> MockPropertyPage {
+        unsafe {
+            g_object_ref(self.raw_property_page);
+        }
+        MockPropertyPage {
+            name: self.name.clone(),
+            raw_label: self.raw_label,
+            raw_page: self.raw_page,
+            raw_property_page: self.raw_property_page,
+        }
+    }
+}
+
+#[cfg(not(nautilus_43))]
+impl Drop for MockPropertyPage {
+    fn drop(&mut self) {
+        unsafe {
+            g_object_unref(self.raw_property_page);
+        }
+    }
+}
+
+/// A properties model as Nautilus received it from a `PropertiesModelProvider`. The title and
+/// items are read from the model on each call, so they reflect later updates.
+#[cfg(nautilus_43)]
+pub struct MockPropertiesModel {
+    raw_model: *mut NautilusPropertiesModel,
+}
+
+#[cfg(nautilus_43)]
+unsafe impl Send for MockPropertiesModel {}
+
+#[cfg(nautilus_43)]
+impl MockPropertiesModel {
+    pub fn title(&self) -> String {
+        unsafe { string_or_empty(nautilus_properties_model_get_title(self.raw_model)) }
+    }
+
+    pub fn items(&self) -> Vec<(String, String)> {
+        unsafe {
+            let list_model = nautilus_properties_model_get_model(self.raw_model);
+            (0..g_list_model_get_n_items(list_model))
+                .map(|position| {
+                    let item =
+                        g_list_model_get_item(list_model, position) as *mut NautilusPropertiesItem;
+                    let pair = (
+                        string_or_empty(nautilus_properties_item_get_name(item)),
+                        string_or_empty(nautilus_properties_item_get_value(item)),
+                    );
+                    g_object_unref(item as *mut GObject);
+                    pair
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(nautilus_43)]
+impl Drop for MockPropertiesModel {
+    fn drop(&mut self) {
+        unsafe {
+            g_object_unref(self.raw_model as *mut GObject);
+        }
+    }
+}
+
+pub fn get_file_items<P: MenuProvider + ?Sized>(
+    menu_provider: &P,
+    files: &[MockFile],
+) -> Vec<MockMenuItem> {
+    let files_vec: Vec<FileInfo> = files.iter().map(MockFile::file_info).collect();
+    let selection = Selection::new(&files_vec);
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    let menu_items = menu_provider.get_selection_items(ptr::null_mut(), &selection);
+    #[cfg(feature = "gtk-rs")]
+    let menu_items = menu_provider.get_selection_items(None, &selection);
+    #[cfg(nautilus_43)]
+    let menu_items = menu_provider.get_selection_items(&selection);
+
+    let menu = Menu::new(&shown_items(menu_items, &files_vec));
+    let files_user_data = Box::into_raw(Box::new(files_vec));
+    let items_g_list = menu.to_g_list(
+        files_user_data as *mut c_void,
+        unsafe { &*files_user_data },
+        false,
+    );
+    unsafe { menu_items_from_g_list(items_g_list) }
+}
+
+pub fn get_background_items<P: MenuProvider + ?Sized>(
+    menu_provider: &P,
+    current_folder: &MockFile,
+) -> Vec<MockMenuItem> {
+    let file_info = current_folder.file_info();
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    let menu_items = menu_provider.get_background_items(ptr::null_mut(), &file_info);
+    #[cfg(feature = "gtk-rs")]
+    let menu_items = menu_provider.get_background_items(None, &file_info);
+    #[cfg(nautilus_43)]
+    let menu_items = menu_provider.get_background_items(&file_info);
+
+    let menu = Menu::new(&shown_items(menu_items, slice::from_ref(&file_info)));
+    let files_user_data = Box::into_raw(Box::new(file_info));
+    let items_g_list = menu.to_g_list(
+        files_user_data as *mut c_void,
+        slice::from_ref(unsafe { &*files_user_data }),
+        true,
+    );
+    unsafe { menu_items_from_g_list(items_g_list) }
+}
+
+pub fn get_columns<P: ColumnProvider + ?Sized>(column_provider: &P) -> Vec<MockColumn> {
+    unsafe { columns_from_g_list(columns_to_g_list(column_provider.get_columns())) }
+}
+
+#[cfg(not(nautilus_43))]
+pub fn get_pages<P: PropertyPageProvider + ?Sized>(
+    property_page_provider: &P,
+    files: &[MockFile],
+) -> Vec<MockPropertyPage> {
+    let files_vec: Vec<FileInfo> = files.iter().map(MockFile::file_info).collect();
+    unsafe {
+        pages_from_g_list(pages_to_g_list(
+            property_page_provider.get_pages(&files_vec),
+        ))
+    }
+}
+
+#[cfg(nautilus_43)]
+pub fn get_models<P: PropertiesModelProvider + ?Sized>(
+    properties_model_provider: &P,
+    files: &[MockFile],
+) -> Vec<MockPropertiesModel> {
+    let files_vec: Vec<FileInfo> = files.iter().map(MockFile::file_info).collect();
+    unsafe {
+        models_from_g_list(models_to_g_list(
+            properties_model_provider.get_models(&files_vec),
+        ))
+    }
+}
+
+/// Run `should_update_file_info()` and, if it returns `true`, `update_file_info()` on the calling
+/// thread, then add the resulting attributes to `file`. Returns whether the file was updated.
+pub fn update_file_info<P: InfoProvider + ?Sized>(info_provider: &P, file: &MockFile) -> bool {
+    let mut file_info = file.file_info();
+    if !info_provider.should_update_file_info(&file_info) {
+        return false;
+    }
+
+    info_provider.update_file_info(&mut file_info);
+    add_attributes_to_raw(&file_info);
+    true
+}
+
+/// Convert the list returned by `get_file_items` or `get_background_items`.
+///
+/// # Safety
+///
+/// `items_g_list` must be a list of `NautilusMenuItem`s owned by the caller. The list and its
+/// references to the items are released.
+pub unsafe fn menu_items_from_g_list(items_g_list: *mut GList) -> Vec<MockMenuItem> {
+    let items = objects_from_g_list(items_g_list)
+        .into_iter()
+        .map(|object| MockMenuItem::from_raw(object as *mut NautilusMenuItem))
+        .collect();
+    nautilus_menu_item_list_free(items_g_list);
+    items
+}
+
+/// Convert the list returned by `get_columns`.
+///
+/// # Safety
+///
+/// `columns_g_list` must be a list of `NautilusColumn`s owned by the caller. The list and the
+/// columns are released.
+pub unsafe fn columns_from_g_list(columns_g_list: *mut GList) -> Vec<MockColumn> {
+    let columns = objects_from_g_list(columns_g_list)
+        .into_iter()
+        .map(|object| {
+            let column = MockColumn {
+                name: string_property(object, b"name\0").unwrap_or_default(),
+                attribute: string_property(object, b"attribute\0").unwrap_or_default(),
+                label: string_property(object, b"label\0").unwrap_or_default(),
+                description: string_property(object, b"description\0").unwrap_or_default(),
+            };
+            g_object_unref(object);
+            column
+        })
+        .collect();
+    g_list_free(columns_g_list);
+    columns
+}
+
+/// Convert the list returned by `get_pages`.
+///
+/// # Safety
+///
+/// `pages_g_list` must be a list of `NautilusPropertyPage`s owned by the caller. The list is
+/// released and the pages are owned by the returned values.
+#[cfg(not(nautilus_43))]
+pub unsafe fn pages_from_g_list(pages_g_list: *mut GList) -> Vec<MockPropertyPage> {
+    let pages = objects_from_g_list(pages_g_list)
+        .into_iter()
+        .map(|object| {
+            let raw_label = object_property(object, b"label\0", G_TYPE_OBJECT);
+            let raw_page = object_property(object, b"page\0", G_TYPE_OBJECT);
+            let page = MockPropertyPage {
+                name: string_property(object, b"name\0").unwrap_or_default(),
+                raw_label: raw_label.unwrap_or(ptr::null_mut()) as *mut GtkWidget,
+                raw_page: raw_page.unwrap_or(ptr::null_mut()) as *mut GtkWidget,
+                raw_property_page: object,
+            };
+            // the page, kept until the value is dropped, holds its own references to the widgets
+            raw_label
+                .into_iter()
+                .chain(raw_page)
+                .for_each(|w| g_object_unref(w));
+            page
+        })
+        .collect();
+    g_list_free(pages_g_list);
+    pages
+}
+
+/// Convert the list returned by `get_models`.
+///
+/// # Safety
+///
+/// `models_g_list` must be a list of `NautilusPropertiesModel`s owned by the caller. The list is
+/// released and the models are owned by the returned values.
+#[cfg(nautilus_43)]
+pub unsafe fn models_from_g_list(models_g_list: *mut GList) -> Vec<MockPropertiesModel> {
+    let models = objects_from_g_list(models_g_list)
+        .into_iter()
+        .map(|object| MockPropertiesModel {
+            raw_model: object as *mut NautilusPropertiesModel,
+        })
+        .collect();
+    g_list_free(models_g_list);
+    models
+}
+
+#[cfg(nautilus_43)]
+unsafe fn string_or_empty(raw_string: *const c_char) -> String {
+    if raw_string.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(raw_string).to_string_lossy().into_owned()
+    }
+}
+
+unsafe fn objects_from_g_list(list: *mut GList) -> Vec<*mut GObject> {
+    let mut objects = Vec::new();
+    let mut node = list;
+    while !node.is_null() {
+        objects.push((*node).data as *mut GObject);
+        node = (*node).next;
+    }
+    objects
+}
+
+unsafe fn string_property(object: *mut GObject, name: &[u8]) -> Option<String> {
+    let mut value: GValue = mem::zeroed();
+    g_value_init(&mut value, G_TYPE_STRING);
+    g_object_get_property(object, name.as_ptr() as *const c_char, &mut value);
+
+    let raw_string = g_value_get_string(&value);
+    let string = if raw_string.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(raw_string).to_string_lossy().into_owned())
+    };
+
+    g_value_unset(&mut value);
+    string
+}
+
+unsafe fn boolean_property(object: *mut GObject, name: &[u8]) -> bool {
+    let mut value: GValue = mem::zeroed();
+    g_value_init(&mut value, G_TYPE_BOOLEAN);
+    g_object_get_property(object, name.as_ptr() as *const c_char, &mut value);
+
+    let boolean = g_value_get_boolean(&value) != GFALSE;
+
+    g_value_unset(&mut value);
+    boolean
+}
+
+/// Returns a new reference to the object stored in the property, if any.
+unsafe fn object_property(
+    object: *mut GObject,
+    name: &[u8],
+    object_type: GType,
+) -> Option<*mut GObject> {
+    let mut value: GValue = mem::zeroed();
+    g_value_init(&mut value, object_type);
+    g_object_get_property(object, name.as_ptr() as *const c_char, &mut value);
+
+    let property_object = g_value_get_object(&value);
+    let property_object = if property_object.is_null() {
+        None
+    } else {
+        Some(g_object_ref(property_object))
+    };
+
+    g_value_unset(&mut value);
+    property_object
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Column, MenuItem};
+
+    struct TestProvider;
+
+    impl TestProvider {
+        fn file_items(&self, files: &[FileInfo]) -> Vec<MenuItem> {
+            let mut open_with = MenuItem::new(
+                "Test::open_with",
+                "Open With",
+                "No applications",
+                Some("document-open"),
+            );
+            open_with.set_sensitive(false);
+            let mut submenu_item = MenuItem::new("Test::open", "Open", "Open the files", None);
+            submenu_item.set_submenu(&Menu::new(&[open_with]));
+            let mut count_item = MenuItem::new(
+                "Test::count".to_string(),
+                format!("{} files", files.len()),
+                String::new(),
+                None,
+            );
+            count_item.set_priority(false);
+            vec![count_item, submenu_item]
+        }
+    }
+
+    impl MenuProvider for TestProvider {
+        #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+        fn get_file_items(&self, _window: *mut GtkWidget, files: &[FileInfo]) -> Vec<MenuItem> {
+            self.file_items(files)
+        }
+
+        #[cfg(feature = "gtk-rs")]
+        fn get_file_items(
+            &self,
+            _window: Option<&crate::gtk::Window>,
+            files: &[FileInfo],
+        ) -> Vec<MenuItem> {
+            self.file_items(files)
+        }
+
+        #[cfg(nautilus_43)]
+        fn get_file_items(&self, files: &[FileInfo]) -> Vec<MenuItem> {
+            self.file_items(files)
+        }
+    }
+
+    impl ColumnProvider for TestProvider {
+        fn get_columns(&self) -> Vec<Column> {
+            vec![Column::new("Test::uri", "test_uri", "URI", "")]
+        }
+    }
+
+    impl InfoProvider for TestProvider {
+        fn should_update_file_info(&self, file_info: &FileInfo) -> bool {
+            file_info.get_uri_scheme() == "file"
+        }
+
+        fn update_file_info(&self, file_info: &mut FileInfo) {
+            let uri = file_info.get_uri();
+            file_info.add_attribute("test_uri", &uri);
+        }
+    }
+
+    #[test]
+    fn drives_providers() {
+        let file = MockFile::new("file:///tmp/photo.jpg").mime_type("image/jpeg");
+        let remote = MockFile::new("sftp://host/photo.jpg");
+
+        let items = get_file_items(&TestProvider, &[file.clone(), remote.clone()]);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].label, "2 files");
+        assert_eq!(items[1].submenu[0].icon.as_deref(), Some("document-open"));
+        assert!(!items[0].has_activate_handler);
+        assert!(!items[0].priority && items[0].sensitive);
+        assert!(!items[1].submenu[0].sensitive);
+        assert_eq!(
+            menu_to_text(&items),
+            Menu::new(&TestProvider.file_items(&[file.file_info(), remote.file_info()])).to_text()
+        );
+
+        assert_eq!(get_columns(&TestProvider)[0].attribute, "test_uri");
+
+        assert!(update_file_info(&TestProvider, &file));
+        assert!(!update_file_info(&TestProvider, &remote));
+        assert_eq!(
+            file.attribute("test_uri").as_deref(),
+            Some("file:///tmp/photo.jpg")
+        );
+        assert!(remote.attributes().is_empty());
+    }
+
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    #[test]
+    fn keeps_page_widgets_alive() {
+        use crate::gobject_ffi::{g_object_add_weak_pointer, g_object_new};
+        use crate::PropertyPage;
+
+        // plain objects stand in for the widgets, which the mock stores as objects
+        struct PageProvider {
+            label: usize,
+            page: usize,
+        }
+
+        impl PropertyPageProvider for PageProvider {
+            fn get_pages(&self, _files: &[FileInfo]) -> Vec<PropertyPage> {
+                vec![PropertyPage::new(
+                    "Test::page",
+                    self.label as *mut GtkWidget,
+                    self.page as *mut GtkWidget,
+                )]
+            }
+        }
+
+        unsafe {
+            let mut label = g_object_new(G_TYPE_OBJECT, ptr::null());
+            let mut page = g_object_new(G_TYPE_OBJECT, ptr::null());
+            g_object_add_weak_pointer(label, &mut label as *mut *mut GObject as *mut _);
+            g_object_add_weak_pointer(page, &mut page as *mut *mut GObject as *mut _);
+
+            let provider = PageProvider {
+                label: label as usize,
+                page: page as usize,
+            };
+            let pages = get_pages(&provider, &[MockFile::new("file:///tmp/photo.jpg")]);
+            g_object_unref(label);
+            g_object_unref(page);
+
+            let copy = pages[0].clone();
+            drop(pages);
+            assert_eq!(copy.name, "Test::page");
+            assert_eq!(copy.raw_label as *mut GObject, label);
+            assert_eq!(copy.raw_page as *mut GObject, page);
+
+            drop(copy);
+            assert!(label.is_null() && page.is_null());
+        }
+    }
+
+    #[cfg(nautilus_43)]
+    #[test]
+    fn updates_properties_models() {
+        use crate::properties_model_provider::{PropertiesModel, PropertiesModelUpdater};
+        use std::sync::Mutex;
+        use std::thread;
+
+        struct ModelProvider {
+            updater: Mutex<Option<PropertiesModelUpdater>>,
+        }
+
+        impl PropertiesModelProvider for ModelProvider {
+            fn get_models(&self, files: &[FileInfo]) -> Vec<PropertiesModel> {
+                let model =
+                    PropertiesModel::new("Test", vec![("URI".to_string(), files[0].get_uri())]);
+                model.updater().append_item("Size", "…");
+                *self.updater.lock().unwrap() = Some(model.updater());
+                vec![model]
+            }
+        }
+
+        let provider = ModelProvider {
+            updater: Mutex::new(None),
+        };
+        let models = get_models(&provider, &[MockFile::new("file:///tmp/photo.jpg")]);
+        assert_eq!(models[0].title(), "Test");
+        assert_eq!(
+            models[0].items(),
+            vec![
+                ("URI".to_string(), "file:///tmp/photo.jpg".to_string()),
+                ("Size".to_string(), "…".to_string()),
+            ]
+        );
+
+        let updater = provider.updater.lock().unwrap().take().unwrap();
+        thread::spawn(move || {
+            updater.set_title("Photo");
+            updater.set_items(vec![("Size".to_string(), "42 kB".to_string())]);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(models[0].title(), "Photo");
+        assert_eq!(
+            models[0].items(),
+            vec![("Size".to_string(), "42 kB".to_string())]
+        );
+    }
+
+    #[cfg(nautilus_43)]
+    #[test]
+    fn keeps_model_stores_alive() {
+        use crate::gobject_ffi::g_object_add_weak_pointer;
+        use crate::nautilus_ffi::nautilus_properties_model_get_model;
+        use crate::properties_model_provider::{PropertiesModel, PropertiesModelUpdater};
+        use std::sync::Mutex;
+
+        struct ModelProvider {
+            keep_updater: bool,
+            updater: Mutex<Option<PropertiesModelUpdater>>,
+        }
+
+        impl PropertiesModelProvider for ModelProvider {
+            fn get_models(&self, _files: &[FileInfo]) -> Vec<PropertiesModel> {
+                let model =
+                    PropertiesModel::new("Test", vec![("Size".to_string(), "…".to_string())]);
+                if self.keep_updater {
+                    *self.updater.lock().unwrap() = Some(model.updater());
+                }
+                vec![model]
+            }
+        }
+
+        for &keep_updater in &[false, true] {
+            let provider = ModelProvider {
+                keep_updater,
+                updater: Mutex::new(None),
+            };
+            let models = get_models(&provider, &[MockFile::new("file:///tmp/photo.jpg")]);
+            unsafe {
+                let mut store =
+                    nautilus_properties_model_get_model(models[0].raw_model) as *mut GObject;
+                g_object_add_weak_pointer(store, &mut store as *mut *mut GObject as *mut _);
+
+                drop(provider.updater.lock().unwrap().take());
+                assert!(!store.is_null());
+                assert_eq!(
+                    models[0].items(),
+                    vec![("Size".to_string(), "…".to_string())]
+                );
+
+                drop(models);
+                assert!(store.is_null());
+            }
+        }
+    }
+}
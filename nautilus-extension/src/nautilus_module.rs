@@ -1,7 +1,9 @@
 use crate::column_provider::{
-    column_provider_iface_externs, rust_column_provider_setters,
+    column_provider_iface_externs, rust_column_provider_setters, rust_column_provider_shutdowns,
     take_next_column_provider_iface_index, ColumnProvider,
 };
+#[cfg(feature = "declarative")]
+use crate::declarative::DeclarativeExtension;
 use crate::glib_ffi::GType;
 use crate::gobject_ffi::G_TYPE_OBJECT;
 use crate::gobject_ffi::{g_type_module_add_interface, g_type_module_register_type, g_type_query};
@@ -9,26 +11,51 @@ use crate::gobject_ffi::{
     GInterfaceInfo, GObjectClass, GTypeInfo, GTypeModule, GTypeQuery, GTypeValueTable,
 };
 use crate::info_provider::{
-    info_provider_iface_externs, rust_info_provider_setters, take_next_info_provider_iface_index,
-    InfoProvider,
+    info_provider_iface_externs, join_update_workers, rust_info_provider_setters,
+    rust_info_provider_shutdowns, take_next_info_provider_iface_index, InfoProvider,
 };
+use crate::logging;
 use crate::menu_provider::{
-    menu_provider_iface_externs, rust_menu_provider_setters, take_next_menu_provider_iface_index,
-    MenuProvider,
+    menu_provider_iface_externs, rust_menu_provider_setters, rust_menu_provider_shutdowns,
+    take_next_menu_provider_iface_index, track_menu_provider_instance, MenuProvider,
+    MenuProviderHandle,
 };
+#[cfg(nautilus_43)]
+use crate::nautilus_ffi::nautilus_properties_model_provider_get_type;
+#[cfg(not(nautilus_43))]
+use crate::nautilus_ffi::nautilus_property_page_provider_get_type;
 use crate::nautilus_ffi::{
     nautilus_column_provider_get_type, nautilus_info_provider_get_type,
-    nautilus_menu_provider_get_type, nautilus_property_page_provider_get_type,
+    nautilus_menu_provider_get_type,
 };
+#[cfg(nautilus_43)]
+use crate::properties_model_provider::{
+    properties_model_provider_iface_externs, rust_properties_model_provider_setters,
+    rust_properties_model_provider_shutdowns, take_next_properties_model_provider_iface_index,
+    PropertiesModelProvider,
+};
+#[cfg(not(nautilus_43))]
 use crate::property_page_provider::{
     property_page_provider_iface_externs, rust_property_page_provider_setters,
-    take_next_property_page_provider_iface_index, PropertyPageProvider,
+    rust_property_page_provider_shutdowns, take_next_property_page_provider_iface_index,
+    PropertyPageProvider,
 };
-use libc::c_char;
+#[cfg(feature = "scripting")]
+use crate::script::ScriptProvider;
+use crate::stats::{self, ModuleStats, ProviderKind};
+#[cfg(feature = "wasm")]
+use crate::wasm::WasmProvider;
+use libc::{c_char, c_int};
 use std::borrow::Cow;
 use std::ffi::CString;
 use std::mem;
 use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long `NautilusModule::shutdown()` waits for pending `update_file_info` calls.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[repr(C)]
 struct NautilusExtensionClass {
@@ -52,7 +79,12 @@ pub struct NautilusModule {
     column_provider_iface_infos: Vec<GInterfaceInfo>,
     info_provider_iface_infos: Vec<GInterfaceInfo>,
     menu_provider_iface_infos: Vec<GInterfaceInfo>,
+    #[cfg(not(nautilus_43))]
     property_page_provider_iface_infos: Vec<GInterfaceInfo>,
+    #[cfg(nautilus_43)]
+    properties_model_provider_iface_infos: Vec<GInterfaceInfo>,
+    provider_slots: Vec<(ProviderKind, usize)>,
+    module_type: Arc<AtomicUsize>,
 }
 
 impl NautilusModule {
@@ -63,7 +95,12 @@ impl NautilusModule {
             column_provider_iface_infos: Vec::new(),
             info_provider_iface_infos: Vec::new(),
             menu_provider_iface_infos: Vec::new(),
+            #[cfg(not(nautilus_43))]
             property_page_provider_iface_infos: Vec::new(),
+            #[cfg(nautilus_43)]
+            properties_model_provider_iface_infos: Vec::new(),
+            provider_slots: Vec::new(),
+            module_type: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -85,6 +122,7 @@ impl NautilusModule {
 
         self.column_provider_iface_infos
             .push(column_provider_iface_info);
+        self.provider_slots.push((ProviderKind::Column, index));
 
         self
     }
@@ -107,6 +145,7 @@ impl NautilusModule {
 
         self.info_provider_iface_infos
             .push(info_provider_iface_info);
+        self.provider_slots.push((ProviderKind::Info, index));
 
         self
     }
@@ -129,10 +168,13 @@ impl NautilusModule {
 
         self.menu_provider_iface_infos
             .push(menu_provider_iface_info);
+        self.provider_slots.push((ProviderKind::Menu, index));
 
         self
     }
 
+    /// Property pages were removed in Nautilus 4.
+    #[cfg(not(nautilus_43))]
     pub fn add_property_page_provider<T: PropertyPageProvider + 'static>(
         &mut self,
         property_page_provider: T,
@@ -151,10 +193,77 @@ impl NautilusModule {
 
         self.property_page_provider_iface_infos
             .push(property_page_provider_iface_info);
+        self.provider_slots
+            .push((ProviderKind::PropertyPage, index));
 
         self
     }
 
+    /// Properties models replace property pages in Nautilus 43 and later.
+    #[cfg(nautilus_43)]
+    pub fn add_properties_model_provider<T: PropertiesModelProvider + 'static>(
+        &mut self,
+        properties_model_provider: T,
+    ) -> &mut NautilusModule {
+        let index = take_next_properties_model_provider_iface_index();
+        let iface_init_fn = properties_model_provider_iface_externs()[index];
+        let rust_provider_setter = &rust_properties_model_provider_setters()[index];
+
+        let properties_model_provider_iface_info = GInterfaceInfo {
+            interface_init: Some(iface_init_fn),
+            interface_finalize: None,
+            interface_data: ptr::null_mut(),
+        };
+
+        rust_provider_setter(Box::new(properties_model_provider));
+
+        self.properties_model_provider_iface_infos
+            .push(properties_model_provider_iface_info);
+        self.provider_slots
+            .push((ProviderKind::PropertiesModel, index));
+
+        self
+    }
+
+    /// Add the menu items, columns and info rules of `extension` as three providers sharing
+    /// its definition.
+    #[cfg(feature = "declarative")]
+    pub fn add_declarative_extension(
+        &mut self,
+        extension: DeclarativeExtension,
+    ) -> &mut NautilusModule {
+        self.add_menu_provider(extension.clone())
+            .add_column_provider(extension.clone())
+            .add_info_provider(extension)
+    }
+
+    /// Add the functions of `script` as menu, column and info providers sharing the script.
+    #[cfg(feature = "scripting")]
+    pub fn add_script_provider(&mut self, script: ScriptProvider) -> &mut NautilusModule {
+        self.add_menu_provider(script.clone())
+            .add_column_provider(script.clone())
+            .add_info_provider(script)
+    }
+
+    /// Add the component of `provider` as menu, column and info providers sharing its instance.
+    #[cfg(feature = "wasm")]
+    pub fn add_wasm_provider(&mut self, provider: WasmProvider) -> &mut NautilusModule {
+        self.add_menu_provider(provider.clone())
+            .add_column_provider(provider.clone())
+            .add_info_provider(provider)
+    }
+
+    /// A handle for refreshing the menu items of this module's type, for example from a
+    /// provider that watches external state. It can be taken before the type is registered.
+    pub fn menu_provider_handle(&self) -> MenuProviderHandle {
+        MenuProviderHandle::new(self.module_type.clone())
+    }
+
+    /// Call counts and latency histograms of the providers added to this module, in the order they were added.
+    pub fn stats(&self) -> ModuleStats {
+        stats::snapshot(&self.provider_slots)
+    }
+
     pub fn register(&self) -> GType {
         let name = CString::new(&self.name as &str).unwrap();
 
@@ -167,7 +276,11 @@ impl NautilusModule {
             class_data: ptr::null(),
             instance_size: g_object_instance_size(),
             n_preallocs: 0,
-            instance_init: None,
+            instance_init: if self.menu_provider_iface_infos.is_empty() {
+                None
+            } else {
+                Some(track_menu_provider_instance)
+            },
             value_table: &EMPTY_VALUE_TABLE,
         };
 
@@ -202,6 +315,7 @@ impl NautilusModule {
                 );
             }
 
+            #[cfg(not(nautilus_43))]
             for property_page_provider_iface_info in &self.property_page_provider_iface_infos {
                 g_type_module_add_interface(
                     self.module,
@@ -211,9 +325,123 @@ impl NautilusModule {
                 );
             }
 
+            #[cfg(nautilus_43)]
+            for properties_model_provider_iface_info in &self.properties_model_provider_iface_infos
+            {
+                g_type_module_add_interface(
+                    self.module,
+                    module_type,
+                    nautilus_properties_model_provider_get_type(),
+                    properties_model_provider_iface_info,
+                );
+            }
+
+            self.module_type.store(module_type, Ordering::SeqCst);
             module_type
         }
     }
+
+    /// Tear down every provider added to any `NautilusModule`.
+    ///
+    /// Waits up to `DEFAULT_SHUTDOWN_TIMEOUT` for pending `update_file_info` calls, then calls
+    /// each provider's `shutdown()` hook and drops it. Called by the `nautilus_module_shutdown()`
+    /// that `nautilus_module!` generates.
+    pub fn shutdown() {
+        NautilusModule::shutdown_within(DEFAULT_SHUTDOWN_TIMEOUT);
+    }
+
+    /// Like `shutdown()`, but waits at most `timeout` for pending `update_file_info` calls, so a
+    /// hung provider cannot block Nautilus from exiting. Info providers whose calls are still
+    /// running are left alone.
+    pub fn shutdown_within(timeout: Duration) {
+        let running = join_update_workers(timeout);
+        if running > 0 {
+            logging::warning(&format!(
+                "{} update_file_info calls still running after {} ms of shutdown",
+                running,
+                timeout.as_millis()
+            ));
+        }
+
+        let shutdowns = rust_column_provider_shutdowns()
+            .into_iter()
+            .chain(rust_info_provider_shutdowns())
+            .chain(rust_menu_provider_shutdowns());
+        #[cfg(not(nautilus_43))]
+        let shutdowns = shutdowns.chain(rust_property_page_provider_shutdowns());
+        #[cfg(nautilus_43)]
+        let shutdowns = shutdowns.chain(rust_properties_model_provider_shutdowns());
+
+        for shutdown in shutdowns {
+            shutdown();
+        }
+    }
+}
+
+/// Return types accepted from the register functions passed to `nautilus_module!`.
+///
+/// A register function can return the single `GType` from `NautilusModule::register()`, an
+/// `Option<GType>` to skip an extension that is disabled, or a `Vec<GType>` when it registers
+/// several extension types at once. The same goes for a configured `NautilusModule`, which is
+/// registered on the way.
+pub trait ModuleTypes {
+    fn into_types(self) -> Vec<GType>;
+}
+
+impl ModuleTypes for GType {
+    fn into_types(self) -> Vec<GType> {
+        vec![self]
+    }
+}
+
+impl ModuleTypes for Option<GType> {
+    fn into_types(self) -> Vec<GType> {
+        self.into_iter().collect()
+    }
+}
+
+impl ModuleTypes for Vec<GType> {
+    fn into_types(self) -> Vec<GType> {
+        self
+    }
+}
+
+impl ModuleTypes for NautilusModule {
+    fn into_types(self) -> Vec<GType> {
+        vec![self.register()]
+    }
+}
+
+impl ModuleTypes for Option<NautilusModule> {
+    fn into_types(self) -> Vec<GType> {
+        self.iter().map(NautilusModule::register).collect()
+    }
+}
+
+impl ModuleTypes for Vec<NautilusModule> {
+    fn into_types(self) -> Vec<GType> {
+        self.iter().map(NautilusModule::register).collect()
+    }
+}
+
+lazy_static! {
+    static ref MODULE_TYPE_LIST: Mutex<Vec<GType>> = Mutex::new(Vec::new());
+}
+
+pub fn add_module_types<T: ModuleTypes>(module_types: T) {
+    MODULE_TYPE_LIST
+        .lock()
+        .unwrap()
+        .extend(module_types.into_types());
+}
+
+/// # Safety
+///
+/// Implements `nautilus_module_list_types()`. Both pointers must be valid for writes.
+pub unsafe fn list_module_types(types: *mut *const GType, num_types: *mut c_int) {
+    let module_type_list = MODULE_TYPE_LIST.lock().unwrap();
+    *types = module_type_list.as_ptr();
+    *num_types = module_type_list.len() as c_int;
 }
 
 fn g_object_instance_size() -> u16 {
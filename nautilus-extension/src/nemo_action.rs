@@ -0,0 +1,163 @@
+//! Actions of the Nemo file manager, `.nemo_action` files with a `[Nemo Action]` group.
+//!
+//! `Selection`, `Extensions`, `Mimetypes` and `Dependencies` are supported. An action with
+//! both `Extensions` and `Mimetypes` is shown when either matches, as in Nemo. `Conditions`
+//! are ignored. The `Exec` codes `%U %F %P %f %N` are supported. Unlike Nemo, which runs the
+//! command once with the name of the first file for `%f` and `%N` and the current folder for
+//! `%P`, they run the command once per file, with its name or folder. That only differs for
+//! actions that take several files, since the default `Selection` is a single file. The other
+//! Nemo codes, `%p %D %e %X %i`, expand to nothing, and any other `%` is kept. An action whose
+//! translated `Exec` line fails `Command::check()` is dropped with a warning.
+
+use crate::command::Command;
+use crate::desktop_file::DesktopFile;
+use crate::file_manager_actions::{Entry, Kind, Pattern, Profile};
+use crate::logging;
+use std::env;
+use std::path::Path;
+
+/// The action of `file`, read from `path`, or `None` if it is inactive or invalid.
+pub(crate) fn parse_entry(path: &Path, file: &DesktopFile) -> Option<Entry> {
+    let group = file.group("Nemo Action")?;
+    if !group.boolean("Active").unwrap_or(true) {
+        return None;
+    }
+    let dependencies = group.list("Dependencies").unwrap_or_default();
+    if !dependencies.iter().all(|program| is_installed(program)) {
+        return None;
+    }
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let exec = translate_exec(dir, &group.string("Exec")?);
+    if let Err(error) = Command::new(exec.clone()).check() {
+        logging::warning(&format!("{}: {}", path.display(), error));
+        return None;
+    }
+    let selection = group
+        .string("Selection")
+        .unwrap_or_else(|| String::from("s"))
+        .to_lowercase();
+    let (selection_counts, files, background) = match selection.as_str() {
+        "s" | "single" => (vec![('=', 1)], true, false),
+        "m" | "multiple" => (vec![('>', 1)], true, false),
+        "any" => (vec![], true, false),
+        "notnone" => (vec![('>', 0)], true, false),
+        "none" => (vec![], false, true),
+        count => (vec![('=', count.parse().ok()?)], true, false),
+    };
+
+    let mut profiles = Vec::new();
+    if let Some(extensions) = group.list("Extensions") {
+        let mut profile = Profile::new(Command::new(exec.clone()));
+        profile.selection_counts = selection_counts.clone();
+        profile.match_case = false;
+        for extension in extensions {
+            match extension.to_lowercase().as_str() {
+                "any" => {}
+                "dir" => profile.mime_types.push(Pattern::new("inode/directory")),
+                "nodirs" => profile.mime_types.push(Pattern::new("all/allfiles")),
+                "none" => profile.basenames.push(Pattern::new("!*.*")),
+                extension => profile
+                    .basenames
+                    .push(Pattern::new(&format!("*.{}", extension))),
+            }
+        }
+        profiles.push(profile);
+    }
+    if let Some(mime_types) = group.list("Mimetypes") {
+        let mut profile = Profile::new(Command::new(exec));
+        profile.selection_counts = selection_counts;
+        profile.mime_types = Pattern::list(&mime_types);
+        profiles.push(profile);
+    }
+
+    Some(Entry {
+        label: group.locale_string("Name")?,
+        tip: group.locale_string("Comment").unwrap_or_default(),
+        icon: group
+            .locale_string("Icon-Name")
+            .filter(|icon| !icon.is_empty()),
+        files,
+        background,
+        kind: Kind::Action(profiles),
+    })
+}
+
+/// Translate the codes of a Nemo `Exec` line to those of `Command`. A program in `<>` is in
+/// the directory of the action, whose `%` are not codes.
+fn translate_exec(dir: &Path, exec: &str) -> String {
+    let exec = exec.trim();
+    let (mut translated, exec) = match (exec.strip_prefix('<'), exec.find('>')) {
+        (Some(rest), Some(end)) => (
+            quote(&format!("{}/", dir.display())).replace('%', "%%"),
+            format!("{}{}", &rest[..end - 1], &rest[end..]),
+        ),
+        _ => (String::new(), exec.to_string()),
+    };
+
+    translated.reserve(exec.len());
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            translated.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('U') => translated.push_str("%U"),
+            Some('F') => translated.push_str("%F"),
+            Some('P') => translated.push_str("%d"),
+            Some('f') | Some('N') => translated.push_str("%n"),
+            Some('%') => translated.push_str("%%"),
+            Some(c) if "pDeXi".contains(c) => {}
+            // not a code, so the `%` is kept
+            Some(c) => {
+                translated.push_str("%%");
+                translated.push(c);
+            }
+            None => translated.push_str("%%"),
+        }
+    }
+    translated
+}
+
+/// `value` quoted for a command line, `'` included.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn is_installed(program: &str) -> bool {
+    let program = program.trim_start_matches("path:");
+    if program.contains('/') {
+        return Path::new(program).exists();
+    }
+    match env::var_os("PATH") {
+        Some(path) => env::split_paths(&path).any(|dir| dir.join(program).exists()),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_exec;
+    use std::path::Path;
+
+    #[test]
+    fn translates_exec_codes() {
+        let dir = Path::new("/home/user/.local/share/nemo/actions");
+        assert_eq!(
+            translate_exec(dir, "<checksum.sh> %F"),
+            "'/home/user/.local/share/nemo/actions/'checksum.sh %F"
+        );
+        assert_eq!(translate_exec(dir, "gimp %f %N %P %U"), "gimp %n %n %d %U");
+        assert_eq!(translate_exec(dir, "notify %e %i"), "notify  ");
+        assert_eq!(
+            translate_exec(dir, "convert -resize 50%x50% %F"),
+            "convert -resize 50%%x50%% %F"
+        );
+        assert_eq!(translate_exec(dir, "printf 100%"), "printf 100%%");
+        assert_eq!(
+            translate_exec(Path::new("/tmp/100%f"), "<run.sh> %F"),
+            "'/tmp/100%%f/'run.sh %F"
+        );
+    }
+}
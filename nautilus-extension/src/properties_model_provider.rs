@@ -0,0 +1,317 @@
+use crate::gio_ffi::{
+    g_list_model_get_n_items, g_list_store_append, g_list_store_new, g_list_store_splice,
+    GListModel, GListStore,
+};
+use crate::glib_ffi::{g_list_append, gpointer, GList};
+use crate::gobject_ffi::{g_object_ref, g_object_unref, GObject};
+use crate::info_provider::FileInfo;
+use crate::main_loop;
+use crate::nautilus_ffi::{
+    nautilus_properties_item_get_type, nautilus_properties_item_new, nautilus_properties_model_new,
+    nautilus_properties_model_set_title, NautilusPropertiesModel, NautilusPropertiesModelProvider,
+};
+use crate::stats::{self, Callback, ProviderKind};
+use crate::translate::file_info_vec_from_g_list;
+use libc::c_void;
+use std::ffi::CString;
+use std::fmt;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A titled list of name/value pairs shown in the file's Properties window.
+#[derive(Clone, Debug)]
+pub struct PropertiesModel {
+    pub title: String,
+    pub items: Vec<(String, String)>,
+    updater: PropertiesModelUpdater,
+}
+
+impl PropertiesModel {
+    pub fn new<S: Into<String>>(title: S, items: Vec<(String, String)>) -> PropertiesModel {
+        PropertiesModel {
+            title: title.into(),
+            items,
+            updater: PropertiesModelUpdater::default(),
+        }
+    }
+
+    /// A handle to change the title and items after the model has been returned from
+    /// `get_models()`, e.g. from a thread computing slow values.
+    pub fn updater(&self) -> PropertiesModelUpdater {
+        self.updater.clone()
+    }
+
+    pub(crate) fn to_raw(&self) -> *mut NautilusPropertiesModel {
+        let title = CString::new(self.title.as_str()).unwrap();
+
+        unsafe {
+            let store = g_list_store_new(nautilus_properties_item_get_type());
+            splice_items(store, &self.items);
+            // the model takes the store's reference, and the updater keeps its own
+            g_object_ref(store as *mut GObject);
+            let model = nautilus_properties_model_new(title.as_ptr(), store as *mut GListModel);
+
+            let mut state = self.updater.state.lock().unwrap();
+            for update in state.pending.drain(..) {
+                update.apply(model, store);
+            }
+            g_object_ref(model as *mut GObject);
+            state.raw = Some(RawModel { model, store });
+
+            model
+        }
+    }
+}
+
+/// Changes a `PropertiesModel` after it has been returned to Nautilus. Cheap to clone and usable
+/// from any thread. Changes are applied on the main loop; changes made before the model is
+/// returned are applied when it is.
+#[derive(Clone, Default)]
+pub struct PropertiesModelUpdater {
+    state: Arc<Mutex<UpdaterState>>,
+}
+
+impl PropertiesModelUpdater {
+    pub fn set_title<S: Into<String>>(&self, title: S) {
+        self.update(Update::Title(title.into()));
+    }
+
+    /// Replace all items.
+    pub fn set_items(&self, items: Vec<(String, String)>) {
+        self.update(Update::Items(items));
+    }
+
+    pub fn append_item<S: Into<String>, T: Into<String>>(&self, name: S, value: T) {
+        self.update(Update::Append(name.into(), value.into()));
+    }
+
+    fn update(&self, update: Update) {
+        let mut state = self.state.lock().unwrap();
+        if state.raw.is_none() {
+            state.pending.push(update);
+            return;
+        }
+        drop(state);
+
+        let state = self.state.clone();
+        main_loop::invoke(move || {
+            if let Some(ref raw) = state.lock().unwrap().raw {
+                unsafe { update.apply(raw.model, raw.store) };
+            }
+        });
+    }
+}
+
+impl fmt::Debug for PropertiesModelUpdater {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("PropertiesModelUpdater")
+            .field("returned", &state.raw.is_some())
+            .field("pending", &state.pending.len())
+            .finish()
+    }
+}
+
+#[derive(Default)]
+struct UpdaterState {
+    raw: Option<RawModel>,
+    pending: Vec<Update>,
+}
+
+struct RawModel {
+    model: *mut NautilusPropertiesModel,
+    store: *mut GListStore,
+}
+
+unsafe impl Send for RawModel {}
+
+impl Drop for RawModel {
+    fn drop(&mut self) {
+        unsafe {
+            g_object_unref(self.model as *mut GObject);
+            g_object_unref(self.store as *mut GObject);
+        }
+    }
+}
+
+enum Update {
+    Title(String),
+    Items(Vec<(String, String)>),
+    Append(String, String),
+}
+
+impl Update {
+    unsafe fn apply(&self, model: *mut NautilusPropertiesModel, store: *mut GListStore) {
+        match *self {
+            Update::Title(ref title) => {
+                let title = CString::new(title.as_str()).unwrap();
+                nautilus_properties_model_set_title(model, title.as_ptr());
+            }
+            Update::Items(ref items) => splice_items(store, items),
+            Update::Append(ref name, ref value) => {
+                let item = new_item(name, value);
+                g_list_store_append(store, item);
+                g_object_unref(item);
+            }
+        }
+    }
+}
+
+unsafe fn new_item(name: &str, value: &str) -> *mut GObject {
+    let name = CString::new(name).unwrap();
+    let value = CString::new(value).unwrap();
+    nautilus_properties_item_new(name.as_ptr(), value.as_ptr()) as *mut GObject
+}
+
+/// Replace the contents of `store` in one `items-changed` emission.
+unsafe fn splice_items(store: *mut GListStore, items: &[(String, String)]) {
+    let mut new_items: Vec<*mut GObject> = items
+        .iter()
+        .map(|(name, value)| new_item(name, value))
+        .collect();
+    let n_removals = g_list_model_get_n_items(store as *mut GListModel);
+
+    g_list_store_splice(
+        store,
+        0,
+        n_removals,
+        new_items.as_mut_ptr(),
+        new_items.len() as u32,
+    );
+
+    for item in new_items {
+        g_object_unref(item);
+    }
+}
+
+pub trait PropertiesModelProvider: Send {
+    fn get_models(&self, files: &[FileInfo]) -> Vec<PropertiesModel>;
+
+    /// Called from `nautilus_module_shutdown()`, right before the provider is dropped.
+    fn shutdown(&self) {}
+}
+
+pub(crate) fn models_to_g_list(models: Vec<PropertiesModel>) -> *mut GList {
+    let mut models_g_list = ptr::null_mut();
+
+    for model in models {
+        unsafe {
+            models_g_list = g_list_append(models_g_list, model.to_raw() as *mut c_void);
+        }
+    }
+
+    models_g_list
+}
+
+macro_rules! properties_model_provider_iface {
+    ($index:expr, $iface_init_fn:ident, $get_models_fn:ident, $rust_provider:ident, $set_rust_provider:ident, $shutdown_rust_provider:ident) => {
+        /// # Safety
+        ///
+        /// This generated function is used as a Nautilus callback. Do not call directly.
+        /// Use `NautilusModule.add_properties_model_provider()` instead.
+        #[no_mangle]
+        pub unsafe extern "C" fn $iface_init_fn(iface: gpointer, _: gpointer) {
+            use crate::nautilus_ffi::NautilusPropertiesModelProviderIface;
+
+            let iface_struct = iface as *mut NautilusPropertiesModelProviderIface;
+            (*iface_struct).get_models = Some($get_models_fn);
+        }
+
+        #[no_mangle]
+        pub extern "C" fn $get_models_fn(
+            _provider: *mut NautilusPropertiesModelProvider,
+            raw_files: *mut GList,
+        ) -> *mut GList {
+            let models = stats::timed(
+                ProviderKind::PropertiesModel,
+                $index,
+                Callback::GetModels,
+                || match *$rust_provider.lock().unwrap() {
+                    Some(ref p) => p.get_models(&file_info_vec_from_g_list(raw_files)),
+                    None => Vec::new(),
+                },
+            );
+
+            models_to_g_list(models)
+        }
+
+        pub fn $set_rust_provider(models_provider: Box<dyn PropertiesModelProvider>) {
+            *$rust_provider.lock().unwrap() = Some(models_provider);
+        }
+
+        pub fn $shutdown_rust_provider() {
+            let provider = $rust_provider.lock().unwrap().take();
+            if let Some(provider) = provider {
+                provider.shutdown();
+            }
+        }
+
+        lazy_static! {
+            static ref $rust_provider: Mutex<Option<Box<dyn PropertiesModelProvider>>> =
+                Mutex::new(None);
+        }
+    };
+}
+
+#[rustfmt::skip] properties_model_provider_iface!(0, properties_model_provider_iface_init_0, properties_model_provider_get_models_0, PROPERTIES_MODEL_PROVIDER_0, set_properties_model_provider_0, shutdown_properties_model_provider_0);
+#[rustfmt::skip] properties_model_provider_iface!(1, properties_model_provider_iface_init_1, properties_model_provider_get_models_1, PROPERTIES_MODEL_PROVIDER_1, set_properties_model_provider_1, shutdown_properties_model_provider_1);
+#[rustfmt::skip] properties_model_provider_iface!(2, properties_model_provider_iface_init_2, properties_model_provider_get_models_2, PROPERTIES_MODEL_PROVIDER_2, set_properties_model_provider_2, shutdown_properties_model_provider_2);
+#[rustfmt::skip] properties_model_provider_iface!(3, properties_model_provider_iface_init_3, properties_model_provider_get_models_3, PROPERTIES_MODEL_PROVIDER_3, set_properties_model_provider_3, shutdown_properties_model_provider_3);
+#[rustfmt::skip] properties_model_provider_iface!(4, properties_model_provider_iface_init_4, properties_model_provider_get_models_4, PROPERTIES_MODEL_PROVIDER_4, set_properties_model_provider_4, shutdown_properties_model_provider_4);
+#[rustfmt::skip] properties_model_provider_iface!(5, properties_model_provider_iface_init_5, properties_model_provider_get_models_5, PROPERTIES_MODEL_PROVIDER_5, set_properties_model_provider_5, shutdown_properties_model_provider_5);
+#[rustfmt::skip] properties_model_provider_iface!(6, properties_model_provider_iface_init_6, properties_model_provider_get_models_6, PROPERTIES_MODEL_PROVIDER_6, set_properties_model_provider_6, shutdown_properties_model_provider_6);
+#[rustfmt::skip] properties_model_provider_iface!(7, properties_model_provider_iface_init_7, properties_model_provider_get_models_7, PROPERTIES_MODEL_PROVIDER_7, set_properties_model_provider_7, shutdown_properties_model_provider_7);
+#[rustfmt::skip] properties_model_provider_iface!(8, properties_model_provider_iface_init_8, properties_model_provider_get_models_8, PROPERTIES_MODEL_PROVIDER_8, set_properties_model_provider_8, shutdown_properties_model_provider_8);
+#[rustfmt::skip] properties_model_provider_iface!(9, properties_model_provider_iface_init_9, properties_model_provider_get_models_9, PROPERTIES_MODEL_PROVIDER_9, set_properties_model_provider_9, shutdown_properties_model_provider_9);
+
+pub fn properties_model_provider_iface_externs() -> Vec<unsafe extern "C" fn(gpointer, gpointer)> {
+    vec![
+        properties_model_provider_iface_init_0,
+        properties_model_provider_iface_init_1,
+        properties_model_provider_iface_init_2,
+        properties_model_provider_iface_init_3,
+        properties_model_provider_iface_init_4,
+        properties_model_provider_iface_init_5,
+        properties_model_provider_iface_init_6,
+        properties_model_provider_iface_init_7,
+        properties_model_provider_iface_init_8,
+        properties_model_provider_iface_init_9,
+    ]
+}
+
+pub fn rust_properties_model_provider_setters() -> Vec<fn(Box<dyn PropertiesModelProvider>)> {
+    vec![
+        set_properties_model_provider_0,
+        set_properties_model_provider_1,
+        set_properties_model_provider_2,
+        set_properties_model_provider_3,
+        set_properties_model_provider_4,
+        set_properties_model_provider_5,
+        set_properties_model_provider_6,
+        set_properties_model_provider_7,
+        set_properties_model_provider_8,
+        set_properties_model_provider_9,
+    ]
+}
+
+pub fn rust_properties_model_provider_shutdowns() -> Vec<fn()> {
+    vec![
+        shutdown_properties_model_provider_0,
+        shutdown_properties_model_provider_1,
+        shutdown_properties_model_provider_2,
+        shutdown_properties_model_provider_3,
+        shutdown_properties_model_provider_4,
+        shutdown_properties_model_provider_5,
+        shutdown_properties_model_provider_6,
+        shutdown_properties_model_provider_7,
+        shutdown_properties_model_provider_8,
+        shutdown_properties_model_provider_9,
+    ]
+}
+
+static NEXT_PROPERTIES_MODEL_PROVIDER_IFACE_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+pub fn take_next_properties_model_provider_iface_index() -> usize {
+    NEXT_PROPERTIES_MODEL_PROVIDER_IFACE_INDEX.fetch_add(1, Ordering::SeqCst)
+}
@@ -1,8 +1,14 @@
 use crate::glib_ffi::{g_list_append, gpointer, GList};
+#[cfg(not(feature = "gtk-rs"))]
 use crate::gtk_ffi::GtkWidget;
 use crate::info_provider::FileInfo;
 use crate::nautilus_ffi::{nautilus_property_page_new, NautilusPropertyPageProvider};
+use crate::stats::{self, Callback, ProviderKind};
 use crate::translate::file_info_vec_from_g_list;
+#[cfg(feature = "gtk-rs")]
+use gtk::glib::translate::ToGlibPtr;
+#[cfg(feature = "gtk-rs")]
+use gtk::glib::{Cast, IsA};
 use libc::c_void;
 use std::borrow::Cow;
 use std::ffi::CString;
@@ -10,12 +16,24 @@ use std::ptr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Mutex;
 
+#[cfg(not(feature = "gtk-rs"))]
 pub struct PropertyPage {
     pub name: Cow<'static, str>,
     pub raw_label: *mut GtkWidget,
     pub raw_page: *mut GtkWidget,
 }
 
+/// A page whose widgets are owned by gtk-rs. They can be built like any other gtk-rs widgets;
+/// `nautilus_property_page_new()` takes its own references, so nothing has to be leaked or
+/// left floating.
+#[cfg(feature = "gtk-rs")]
+pub struct PropertyPage {
+    pub name: Cow<'static, str>,
+    pub label: gtk::Widget,
+    pub page: gtk::Widget,
+}
+
+#[cfg(not(feature = "gtk-rs"))]
 impl PropertyPage {
     pub fn new<S: Into<Cow<'static, str>>>(
         name: S,
@@ -30,12 +48,57 @@ impl PropertyPage {
     }
 }
 
+#[cfg(feature = "gtk-rs")]
+impl PropertyPage {
+    pub fn new<S, L, P>(name: S, label: &L, page: &P) -> PropertyPage
+    where
+        S: Into<Cow<'static, str>>,
+        L: IsA<gtk::Widget>,
+        P: IsA<gtk::Widget>,
+    {
+        PropertyPage {
+            name: name.into(),
+            label: label.clone().upcast(),
+            page: page.clone().upcast(),
+        }
+    }
+}
+
 pub trait PropertyPageProvider: Send {
     fn get_pages(&self, files: &[FileInfo]) -> Vec<PropertyPage>;
+
+    /// Called from `nautilus_module_shutdown()`, right before the provider is dropped.
+    fn shutdown(&self) {}
+}
+
+pub(crate) fn pages_to_g_list(pages: Vec<PropertyPage>) -> *mut GList {
+    let mut pages_g_list = ptr::null_mut();
+
+    for page in pages {
+        let name = CString::new(&page.name as &str).unwrap().into_raw();
+        #[cfg(not(feature = "gtk-rs"))]
+        let (label, page_widget) = (page.raw_label, page.raw_page);
+        // gtk-rs sinks floating references when it wraps a widget, so these are strong
+        // references. The NautilusPropertyPage refs both widgets, and ours are released when
+        // `page` is dropped at the end of the loop, leaving the page and the properties
+        // window as the owners.
+        #[cfg(feature = "gtk-rs")]
+        let (label, page_widget) = (page.label.to_glib_none().0, page.page.to_glib_none().0);
+
+        unsafe {
+            let page_c = nautilus_property_page_new(name, label, page_widget);
+            pages_g_list = g_list_append(pages_g_list, page_c as *mut c_void);
+
+            // deallocate CStrings
+            let _ = CString::from_raw(name);
+        }
+    }
+
+    pages_g_list
 }
 
 macro_rules! property_page_provider_iface {
-    ($iface_init_fn:ident, $get_pages_fn:ident, $rust_provider:ident, $set_rust_provider:ident) => {
+    ($index:expr, $iface_init_fn:ident, $get_pages_fn:ident, $rust_provider:ident, $set_rust_provider:ident, $shutdown_rust_provider:ident) => {
         /// # Safety
         ///
         /// This generated function is used as a Nautilus callback. Do not call directly.
@@ -53,34 +116,30 @@ macro_rules! property_page_provider_iface {
             _provider: *mut NautilusPropertyPageProvider,
             raw_files: *mut GList,
         ) -> *mut GList {
-            let mut pages_g_list = ptr::null_mut();
-
-            let pages = match *$rust_provider.lock().unwrap() {
-                Some(ref p) => p.get_pages(&file_info_vec_from_g_list(raw_files)),
-                None => Vec::new(),
-            };
-
-            for page in pages {
-                let name = CString::new(&page.name as &str).unwrap().into_raw();
-                let label = page.raw_label;
-                let page_widget = page.raw_page;
-
-                unsafe {
-                    let page_c = nautilus_property_page_new(name, label, page_widget);
-                    pages_g_list = g_list_append(pages_g_list, page_c as *mut c_void);
-
-                    // deallocate CStrings
-                    let _ = CString::from_raw(name);
-                }
-            }
-
-            pages_g_list
+            let pages = stats::timed(
+                ProviderKind::PropertyPage,
+                $index,
+                Callback::GetPages,
+                || match *$rust_provider.lock().unwrap() {
+                    Some(ref p) => p.get_pages(&file_info_vec_from_g_list(raw_files)),
+                    None => Vec::new(),
+                },
+            );
+
+            pages_to_g_list(pages)
         }
 
         pub fn $set_rust_provider(page_provider: Box<dyn PropertyPageProvider>) {
             *$rust_provider.lock().unwrap() = Some(page_provider);
         }
 
+        pub fn $shutdown_rust_provider() {
+            let provider = $rust_provider.lock().unwrap().take();
+            if let Some(provider) = provider {
+                provider.shutdown();
+            }
+        }
+
         lazy_static! {
             static ref $rust_provider: Mutex<Option<Box<dyn PropertyPageProvider>>> =
                 Mutex::new(None);
@@ -89,16 +148,16 @@ macro_rules! property_page_provider_iface {
 }
 
 // Let library consumer add up to 10 ColumnProviders, should be more than enough. Each has its own Vec of columns.
-#[rustfmt::skip] property_page_provider_iface!(property_page_provider_iface_init_0, property_page_provider_get_pages_0, PROPERTY_PAGE_PROVIDER_0, set_property_page_provider_0);
-#[rustfmt::skip] property_page_provider_iface!(property_page_provider_iface_init_1, property_page_provider_get_pages_1, PROPERTY_PAGE_PROVIDER_1, set_property_page_provider_1);
-#[rustfmt::skip] property_page_provider_iface!(property_page_provider_iface_init_2, property_page_provider_get_pages_2, PROPERTY_PAGE_PROVIDER_2, set_property_page_provider_2);
-#[rustfmt::skip] property_page_provider_iface!(property_page_provider_iface_init_3, property_page_provider_get_pages_3, PROPERTY_PAGE_PROVIDER_3, set_property_page_provider_3);
-#[rustfmt::skip] property_page_provider_iface!(property_page_provider_iface_init_4, property_page_provider_get_pages_4, PROPERTY_PAGE_PROVIDER_4, set_property_page_provider_4);
-#[rustfmt::skip] property_page_provider_iface!(property_page_provider_iface_init_5, property_page_provider_get_pages_5, PROPERTY_PAGE_PROVIDER_5, set_property_page_provider_5);
-#[rustfmt::skip] property_page_provider_iface!(property_page_provider_iface_init_6, property_page_provider_get_pages_6, PROPERTY_PAGE_PROVIDER_6, set_property_page_provider_6);
-#[rustfmt::skip] property_page_provider_iface!(property_page_provider_iface_init_7, property_page_provider_get_pages_7, PROPERTY_PAGE_PROVIDER_7, set_property_page_provider_7);
-#[rustfmt::skip] property_page_provider_iface!(property_page_provider_iface_init_8, property_page_provider_get_pages_8, PROPERTY_PAGE_PROVIDER_8, set_property_page_provider_8);
-#[rustfmt::skip] property_page_provider_iface!(property_page_provider_iface_init_9, property_page_provider_get_pages_9, PROPERTY_PAGE_PROVIDER_9, set_property_page_provider_9);
+#[rustfmt::skip] property_page_provider_iface!(0, property_page_provider_iface_init_0, property_page_provider_get_pages_0, PROPERTY_PAGE_PROVIDER_0, set_property_page_provider_0, shutdown_property_page_provider_0);
+#[rustfmt::skip] property_page_provider_iface!(1, property_page_provider_iface_init_1, property_page_provider_get_pages_1, PROPERTY_PAGE_PROVIDER_1, set_property_page_provider_1, shutdown_property_page_provider_1);
+#[rustfmt::skip] property_page_provider_iface!(2, property_page_provider_iface_init_2, property_page_provider_get_pages_2, PROPERTY_PAGE_PROVIDER_2, set_property_page_provider_2, shutdown_property_page_provider_2);
+#[rustfmt::skip] property_page_provider_iface!(3, property_page_provider_iface_init_3, property_page_provider_get_pages_3, PROPERTY_PAGE_PROVIDER_3, set_property_page_provider_3, shutdown_property_page_provider_3);
+#[rustfmt::skip] property_page_provider_iface!(4, property_page_provider_iface_init_4, property_page_provider_get_pages_4, PROPERTY_PAGE_PROVIDER_4, set_property_page_provider_4, shutdown_property_page_provider_4);
+#[rustfmt::skip] property_page_provider_iface!(5, property_page_provider_iface_init_5, property_page_provider_get_pages_5, PROPERTY_PAGE_PROVIDER_5, set_property_page_provider_5, shutdown_property_page_provider_5);
+#[rustfmt::skip] property_page_provider_iface!(6, property_page_provider_iface_init_6, property_page_provider_get_pages_6, PROPERTY_PAGE_PROVIDER_6, set_property_page_provider_6, shutdown_property_page_provider_6);
+#[rustfmt::skip] property_page_provider_iface!(7, property_page_provider_iface_init_7, property_page_provider_get_pages_7, PROPERTY_PAGE_PROVIDER_7, set_property_page_provider_7, shutdown_property_page_provider_7);
+#[rustfmt::skip] property_page_provider_iface!(8, property_page_provider_iface_init_8, property_page_provider_get_pages_8, PROPERTY_PAGE_PROVIDER_8, set_property_page_provider_8, shutdown_property_page_provider_8);
+#[rustfmt::skip] property_page_provider_iface!(9, property_page_provider_iface_init_9, property_page_provider_get_pages_9, PROPERTY_PAGE_PROVIDER_9, set_property_page_provider_9, shutdown_property_page_provider_9);
 
 pub fn property_page_provider_iface_externs() -> Vec<unsafe extern "C" fn(gpointer, gpointer)> {
     vec![
@@ -130,6 +189,21 @@ pub fn rust_property_page_provider_setters() -> Vec<fn(Box<dyn PropertyPageProvi
     ]
 }
 
+pub fn rust_property_page_provider_shutdowns() -> Vec<fn()> {
+    vec![
+        shutdown_property_page_provider_0,
+        shutdown_property_page_provider_1,
+        shutdown_property_page_provider_2,
+        shutdown_property_page_provider_3,
+        shutdown_property_page_provider_4,
+        shutdown_property_page_provider_5,
+        shutdown_property_page_provider_6,
+        shutdown_property_page_provider_7,
+        shutdown_property_page_provider_8,
+        shutdown_property_page_provider_9,
+    ]
+}
+
 static NEXT_PROPERTY_PAGE_PROVIDER_IFACE_INDEX: AtomicUsize = AtomicUsize::new(0);
 
 pub fn take_next_property_page_provider_iface_index() -> usize {
@@ -0,0 +1,123 @@
+//! Text and JSON rendering of menu trees, shared by `Menu` and the mock menu items so both
+//! render identically.
+
+use std::fmt::Write;
+
+pub trait RenderItem: Sized {
+    fn name(&self) -> &str;
+    fn label(&self) -> &str;
+    fn tip(&self) -> &str;
+    fn icon(&self) -> Option<&str>;
+    fn sensitive(&self) -> bool;
+    fn priority(&self) -> bool;
+    fn has_activate_handler(&self) -> bool;
+    fn submenu(&self) -> &[Self];
+}
+
+/// One line per item, indented by two spaces per submenu level. Strings are quoted as in JSON:
+///
+/// ```text
+/// Tmsu::tags label="Tags" tip="Edit tags" icon="tag" activate
+///   Tmsu::tag_photo label="photo" tip="" insensitive no-priority
+/// ```
+pub fn items_to_text<T: RenderItem>(items: &[T]) -> String {
+    let mut text = String::new();
+    write_text(&mut text, items, 0);
+    text
+}
+
+fn write_text<T: RenderItem>(text: &mut String, items: &[T], depth: usize) {
+    for item in items {
+        write!(
+            text,
+            "{:indent$}{} label={} tip={}",
+            "",
+            item.name(),
+            json_string(item.label()),
+            json_string(item.tip()),
+            indent = depth * 2
+        )
+        .unwrap();
+        if let Some(icon) = item.icon() {
+            write!(text, " icon={}", json_string(icon)).unwrap();
+        }
+        if !item.sensitive() {
+            text.push_str(" insensitive");
+        }
+        if !item.priority() {
+            text.push_str(" no-priority");
+        }
+        if item.has_activate_handler() {
+            text.push_str(" activate");
+        }
+        text.push('\n');
+
+        write_text(text, item.submenu(), depth + 1);
+    }
+}
+
+/// A JSON array with one object per item. Keys are always present and in a fixed order, and
+/// `icon` is `null` when unset.
+pub fn items_to_json<T: RenderItem>(items: &[T]) -> String {
+    let items: Vec<String> = items
+        .iter()
+        .map(|item| {
+            format!(
+                "{{\"name\":{},\"label\":{},\"tip\":{},\"icon\":{},\"sensitive\":{},\"priority\":{},\"has_activate_handler\":{},\"submenu\":{}}}",
+                json_string(item.name()),
+                json_string(item.label()),
+                json_string(item.tip()),
+                item.icon().map_or(String::from("null"), json_string),
+                item.sensitive(),
+                item.priority(),
+                item.has_activate_handler(),
+                items_to_json(item.submenu())
+            )
+        })
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::menu_provider::{Menu, MenuItem};
+
+    #[test]
+    fn renders_menu_tree() {
+        let mut tags = MenuItem::new("Tmsu::tags", "Tags", "Edit \"tags\"", Some("tag"));
+        tags.set_submenu(&Menu::new(&[MenuItem::new(
+            "Tmsu::photo",
+            "photo",
+            "",
+            None,
+        )]));
+        let menu = Menu::new(&[tags]);
+
+        assert_eq!(
+            menu.to_text(),
+            "Tmsu::tags label=\"Tags\" tip=\"Edit \\\"tags\\\"\" icon=\"tag\"\n  Tmsu::photo label=\"photo\" tip=\"\"\n"
+        );
+        assert_eq!(
+            menu.to_json(),
+            "[{\"name\":\"Tmsu::tags\",\"label\":\"Tags\",\"tip\":\"Edit \\\"tags\\\"\",\"icon\":\"tag\",\"sensitive\":true,\"priority\":true,\"has_activate_handler\":false,\"submenu\":[{\"name\":\"Tmsu::photo\",\"label\":\"photo\",\"tip\":\"\",\"icon\":null,\"sensitive\":true,\"priority\":true,\"has_activate_handler\":false,\"submenu\":[]}]}]"
+        );
+    }
+}
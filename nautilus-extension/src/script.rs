@@ -0,0 +1,772 @@
+//! Providers written as [Rhai](https://rhai.rs) scripts, with the `scripting` feature:
+//!
+//! ```rhai
+//! fn get_file_items(files) {
+//!     if files.len() != 1 || !files[0].mime.starts_with("image/") {
+//!         return [];
+//!     }
+//!     [#{
+//!         name: "Photos::rotate",
+//!         label: "Rotate",
+//!         icon: "object-rotate-right",
+//!         command: "mogrify -rotate 90 %f",
+//!     }]
+//! }
+//!
+//! fn get_columns() {
+//!     [#{ name: "Photos::frame", attribute: "frame", label: "Frame" }]
+//! }
+//!
+//! fn update_file_info(file) {
+//!     if file.name.starts_with("IMG_") {
+//!         #{ frame: file.name.sub_string(4, 4) }
+//!     }
+//! }
+//! ```
+//!
+//! ```ignore
+//! fn register_provider(module: *mut GTypeModule) -> GType {
+//!     let permissions = ScriptPermissions::default();
+//!     let script = ScriptProvider::load("/usr/share/photos/extension.rhai", permissions).unwrap();
+//!     NautilusModule::new(module, "Photos")
+//!         .add_script_provider(script)
+//!         .register()
+//! }
+//! ```
+//!
+//! A script defines any of these functions, and the provider calls the ones it finds:
+//!
+//! * `get_file_items(files)` and `get_background_items(folder)` return an array of menu items.
+//!   An item is a map with a `name` and `label`, an optional `tip`, `icon`, `sensitive` and
+//!   `priority`, and either a `command`, an `Exec` line as taken by `Command`, or the `items` of
+//!   a submenu.
+//! * `get_columns()` returns an array of maps with a `name`, `attribute` and `label`, and an
+//!   optional `description`.
+//! * `update_file_info(file)` returns a map of the attributes to set, or nothing.
+//!
+//! Files are passed as `File` values with the read-only properties `uri`, `scheme`, `name`,
+//! `mime`, `is_directory` and `attributes`. Only the functions are called: statements outside
+//! them are not run.
+//!
+//! Scripts cannot import modules, use `eval`, read files or run commands. `ScriptPermissions`
+//! grants, with `read_dirs`, `read_file(path)`, which returns the contents of a file below one
+//! of the directories, and with `run_commands`, `run(exec)`, which runs an `Exec` line and
+//! returns its output, and menu items with a `command`. A call that runs longer than the time
+//! limit is stopped, and a command it runs is killed when the limit is reached. Strings, arrays,
+//! maps and the depth of function calls are limited too, so a script cannot use up the memory
+//! or stack of Nautilus. Errors in a call are logged as warnings, and the call returns nothing.
+
+use crate::column_provider::{Column, ColumnProvider};
+use crate::command::{self, Command};
+#[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+use crate::gtk_ffi::GtkWidget;
+use crate::info_provider::{FileInfo, InfoProvider};
+use crate::logging;
+use crate::menu_provider::{Menu, MenuItem, MenuProvider};
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Map, Scope, AST};
+use std::cell::Cell;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const ITEM_KEYS: [&str; 8] = [
+    "name",
+    "label",
+    "tip",
+    "icon",
+    "command",
+    "items",
+    "sensitive",
+    "priority",
+];
+const COLUMN_KEYS: [&str; 4] = ["name", "attribute", "label", "description"];
+
+/// How many operations a script runs between checks of its time limit.
+const OPERATIONS_PER_CHECK: u64 = 256;
+
+/// The longest string a script may build, in bytes.
+const MAX_STRING_SIZE: usize = 1 << 20;
+/// The most items of an array or map a script may build.
+const MAX_COLLECTION_SIZE: usize = 10_000;
+/// How deeply script functions may call each other.
+const MAX_CALL_LEVELS: usize = 32;
+
+thread_local! {
+    /// When the script call running on this thread must stop.
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// What a script may do beyond computing values.
+#[derive(Clone, Debug)]
+pub struct ScriptPermissions {
+    /// Define `read_file(path)` for the files below these directories, once symbolic links and
+    /// `..` are resolved.
+    pub read_dirs: Vec<PathBuf>,
+    /// Define `run(exec)`, and accept menu items with a `command`.
+    pub run_commands: bool,
+    /// How long a single call of a script function may run.
+    pub time_limit: Duration,
+}
+
+impl Default for ScriptPermissions {
+    /// No files, no commands, and 100 ms per call.
+    fn default() -> ScriptPermissions {
+        ScriptPermissions {
+            read_dirs: Vec::new(),
+            run_commands: false,
+            time_limit: Duration::from_millis(100),
+        }
+    }
+}
+
+/// A menu, column and info provider calling the functions of a Rhai script. Clones share the
+/// script.
+#[derive(Clone)]
+pub struct ScriptProvider {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    engine: Engine,
+    ast: AST,
+    time_limit: Duration,
+    run_commands: bool,
+}
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(io::Error),
+    /// The script does not compile.
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScriptError::Io(ref error) => write!(f, "cannot read script: {}", error),
+            ScriptError::Parse {
+                line,
+                column,
+                ref message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+        }
+    }
+}
+
+impl error::Error for ScriptError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ScriptError::Io(ref error) => Some(error),
+            ScriptError::Parse { .. } => None,
+        }
+    }
+}
+
+/// The view of a `FileInfo` given to scripts.
+#[derive(Clone)]
+struct ScriptFile {
+    uri: String,
+    scheme: String,
+    name: String,
+    mime: String,
+    is_directory: bool,
+    attributes: Map,
+}
+
+impl ScriptFile {
+    fn new(file_info: &FileInfo) -> ScriptFile {
+        ScriptFile {
+            uri: file_info.get_uri(),
+            scheme: file_info.get_uri_scheme(),
+            name: file_info
+                .name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            mime: file_info.get_mime_type(),
+            is_directory: file_info.is_directory(),
+            attributes: file_info
+                .attributes
+                .iter()
+                .map(|(name, value)| (name.as_str().into(), Dynamic::from(value.clone())))
+                .collect(),
+        }
+    }
+}
+
+impl ScriptProvider {
+    /// The provider defined by the script at `path`.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        permissions: ScriptPermissions,
+    ) -> Result<ScriptProvider, ScriptError> {
+        let source = fs::read_to_string(path).map_err(ScriptError::Io)?;
+        ScriptProvider::parse(&source, permissions)
+    }
+
+    /// The provider defined by `source`, e.g. a script included with `include_str!`.
+    pub fn parse(
+        source: &str,
+        permissions: ScriptPermissions,
+    ) -> Result<ScriptProvider, ScriptError> {
+        let engine = new_engine(&permissions);
+        let ast = engine.compile(source).map_err(|error| ScriptError::Parse {
+            line: error.1.line().unwrap_or(1),
+            column: error.1.position().unwrap_or(1),
+            message: error.0.to_string(),
+        })?;
+        Ok(ScriptProvider {
+            inner: Arc::new(Inner {
+                engine,
+                ast,
+                time_limit: permissions.time_limit,
+                run_commands: permissions.run_commands,
+            }),
+        })
+    }
+
+    fn defines(&self, function: &str, arity: usize) -> bool {
+        self.inner
+            .ast
+            .iter_functions()
+            .any(|f| f.name == function && f.params.len() == arity)
+    }
+
+    /// The result of the script's `function`, or `None` if it is not defined or fails.
+    fn call<A: FuncArgs>(&self, function: &str, arity: usize, args: A) -> Option<Dynamic> {
+        if !self.defines(function, arity) {
+            return None;
+        }
+
+        DEADLINE.with(|deadline| deadline.set(Some(Instant::now() + self.inner.time_limit)));
+        let result = self.inner.engine.call_fn_with_options::<Dynamic>(
+            CallFnOptions::new().eval_ast(false),
+            &mut Scope::new(),
+            &self.inner.ast,
+            function,
+            args,
+        );
+        DEADLINE.with(|deadline| deadline.set(None));
+
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                let message = match *error {
+                    EvalAltResult::ErrorTerminated(..) => format!(
+                        "stopped after the time limit of {} ms",
+                        self.inner.time_limit.as_millis()
+                    ),
+                    ref error => error.to_string(),
+                };
+                logging::warning(&format!("{}(): {}", function, message));
+                None
+            }
+        }
+    }
+
+    /// The result of `function`, converted with `convert` if it is an array, or an empty vector.
+    fn call_list<A: FuncArgs, T, F: Fn(Dynamic) -> Result<T, String>>(
+        &self,
+        function: &str,
+        arity: usize,
+        args: A,
+        convert: F,
+    ) -> Vec<T> {
+        let value = match self.call(function, arity, args) {
+            Some(value) => value,
+            None => return Vec::new(),
+        };
+        let result = value
+            .into_array()
+            .map_err(|type_name| format!("expected an array, found {}", type_name))
+            .and_then(|array| array.into_iter().map(&convert).collect());
+        result.unwrap_or_else(|message| {
+            logging::warning(&format!("{}(): {}", function, message));
+            Vec::new()
+        })
+    }
+
+    fn file_items(&self, files: &[FileInfo]) -> Vec<MenuItem> {
+        let files: Array = files
+            .iter()
+            .map(|file| Dynamic::from(ScriptFile::new(file)))
+            .collect();
+        self.call_list("get_file_items", 1, (files,), |value| {
+            menu_item(value, self.inner.run_commands)
+        })
+    }
+
+    fn background_items(&self, current_folder: &FileInfo) -> Vec<MenuItem> {
+        let folder = ScriptFile::new(current_folder);
+        self.call_list("get_background_items", 1, (folder,), |value| {
+            menu_item(value, self.inner.run_commands)
+        })
+    }
+}
+
+impl MenuProvider for ScriptProvider {
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    fn get_file_items(&self, _window: *mut GtkWidget, files: &[FileInfo]) -> Vec<MenuItem> {
+        self.file_items(files)
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    fn get_file_items(
+        &self,
+        _window: Option<&crate::gtk::Window>,
+        files: &[FileInfo],
+    ) -> Vec<MenuItem> {
+        self.file_items(files)
+    }
+
+    #[cfg(nautilus_43)]
+    fn get_file_items(&self, files: &[FileInfo]) -> Vec<MenuItem> {
+        self.file_items(files)
+    }
+
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    fn get_background_items(
+        &self,
+        _window: *mut GtkWidget,
+        current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        self.background_items(current_folder)
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    fn get_background_items(
+        &self,
+        _window: Option<&crate::gtk::Window>,
+        current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        self.background_items(current_folder)
+    }
+
+    #[cfg(nautilus_43)]
+    fn get_background_items(&self, current_folder: &FileInfo) -> Vec<MenuItem> {
+        self.background_items(current_folder)
+    }
+}
+
+impl ColumnProvider for ScriptProvider {
+    fn get_columns(&self) -> Vec<Column> {
+        self.call_list("get_columns", 0, (), |value| {
+            let fields = Fields::new(value, "a column", &COLUMN_KEYS)?;
+            Ok(Column::new(
+                fields.required_string("name")?,
+                fields.required_string("attribute")?,
+                fields.required_string("label")?,
+                fields.string("description")?.unwrap_or_default(),
+            ))
+        })
+    }
+}
+
+impl InfoProvider for ScriptProvider {
+    fn should_update_file_info(&self, _file_info: &FileInfo) -> bool {
+        self.defines("update_file_info", 1)
+    }
+
+    fn update_file_info(&self, file_info: &mut FileInfo) {
+        let value = match self.call("update_file_info", 1, (ScriptFile::new(file_info),)) {
+            Some(value) if !value.is_unit() => value,
+            _ => return,
+        };
+        let attributes = match value.try_cast::<Map>() {
+            Some(attributes) => attributes,
+            None => {
+                logging::warning("update_file_info(): expected a map of attributes");
+                return;
+            }
+        };
+        for (name, value) in attributes {
+            file_info.add_attribute(&name, &value.to_string());
+        }
+    }
+}
+
+/// A map returned by a script, whose keys have been checked.
+struct Fields {
+    map: Map,
+}
+
+impl Fields {
+    fn new(value: Dynamic, what: &str, keys: &[&str]) -> Result<Fields, String> {
+        let type_name = value.type_name();
+        let map = value
+            .try_cast::<Map>()
+            .ok_or_else(|| format!("expected {} map, found {}", what, type_name))?;
+        if let Some(key) = map.keys().find(|key| !keys.contains(&key.as_str())) {
+            return Err(format!("unknown key `{}` in {}", key, what));
+        }
+        Ok(Fields { map })
+    }
+
+    fn string(&self, key: &str) -> Result<Option<String>, String> {
+        match self.map.get(key) {
+            Some(value) => value
+                .clone()
+                .into_string()
+                .map(Some)
+                .map_err(|type_name| expected(key, "a string", type_name)),
+            None => Ok(None),
+        }
+    }
+
+    fn required_string(&self, key: &str) -> Result<String, String> {
+        self.string(key)?
+            .ok_or_else(|| format!("missing key `{}`", key))
+    }
+
+    fn boolean(&self, key: &str) -> Result<Option<bool>, String> {
+        match self.map.get(key) {
+            Some(value) => value
+                .as_bool()
+                .map(Some)
+                .map_err(|type_name| expected(key, "a boolean", type_name)),
+            None => Ok(None),
+        }
+    }
+
+    fn array(&self, key: &str) -> Result<Option<Array>, String> {
+        match self.map.get(key) {
+            Some(value) => value
+                .clone()
+                .into_array()
+                .map(Some)
+                .map_err(|type_name| expected(key, "an array", type_name)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn expected(key: &str, expected: &str, type_name: &str) -> String {
+    format!("`{}`: expected {}, found {}", key, expected, type_name)
+}
+
+/// The menu item of `value`. Items with a `command` are only accepted with `run_commands`.
+fn menu_item(value: Dynamic, run_commands: bool) -> Result<MenuItem, String> {
+    let fields = Fields::new(value, "a menu item", &ITEM_KEYS)?;
+    let mut item = MenuItem::new(
+        fields.required_string("name")?,
+        fields.required_string("label")?,
+        fields.string("tip")?.unwrap_or_default(),
+        fields.string("icon")?,
+    );
+    if let Some(sensitive) = fields.boolean("sensitive")? {
+        item.set_sensitive(sensitive);
+    }
+    if let Some(priority) = fields.boolean("priority")? {
+        item.set_priority(priority);
+    }
+
+    match (fields.string("command")?, fields.array("items")?) {
+        (Some(_), Some(_)) => {
+            return Err(format!(
+                "`{}`: an item with a submenu has no `command`",
+                item.name()
+            ));
+        }
+        (Some(_), None) if !run_commands => {
+            return Err(format!(
+                "`{}`: a `command` needs the `run_commands` permission",
+                item.name()
+            ));
+        }
+        (Some(exec), None) => {
+            let command = Command::new(exec);
+            command.expand(&[]).map_err(|error| error.to_string())?;
+            item.set_command(command);
+        }
+        (None, Some(items)) => {
+            let items = items
+                .into_iter()
+                .map(|value| menu_item(value, run_commands))
+                .collect::<Result<Vec<MenuItem>, String>>()?;
+            item.set_submenu(&Menu::new(&items));
+        }
+        (None, None) => {
+            return Err(format!(
+                "`{}`: an item needs a `command` or the `items` of a submenu",
+                item.name()
+            ));
+        }
+    }
+    Ok(item)
+}
+
+fn new_engine(permissions: &ScriptPermissions) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_COLLECTION_SIZE)
+        .set_max_map_size(MAX_COLLECTION_SIZE)
+        .set_max_call_levels(MAX_CALL_LEVELS);
+    engine.on_print(logging::message);
+    engine
+        .on_debug(|text, _source, position| logging::message(&format!("{:?}: {}", position, text)));
+    engine.on_progress(|operations| {
+        if operations % OPERATIONS_PER_CHECK != 0 {
+            return None;
+        }
+        DEADLINE.with(|deadline| match deadline.get() {
+            Some(deadline) if Instant::now() > deadline => Some(Dynamic::UNIT),
+            _ => None,
+        })
+    });
+
+    engine
+        .register_type_with_name::<ScriptFile>("File")
+        .register_get("uri", |file: &mut ScriptFile| file.uri.clone())
+        .register_get("scheme", |file: &mut ScriptFile| file.scheme.clone())
+        .register_get("name", |file: &mut ScriptFile| file.name.clone())
+        .register_get("mime", |file: &mut ScriptFile| file.mime.clone())
+        .register_get("is_directory", |file: &mut ScriptFile| file.is_directory)
+        .register_get("attributes", |file: &mut ScriptFile| {
+            file.attributes.clone()
+        });
+
+    if !permissions.read_dirs.is_empty() {
+        let dirs = permissions.read_dirs.clone();
+        engine.register_fn("read_file", move |path: &str| read_file(&dirs, path));
+    }
+    if permissions.run_commands {
+        engine.register_fn("run", run);
+    }
+    engine
+}
+
+/// The contents of the file at `path`, if it is below one of `dirs`.
+fn read_file(dirs: &[PathBuf], path: &str) -> Result<String, Box<EvalAltResult>> {
+    let cannot_read = |error: io::Error| format!("cannot read {}: {}", path, error);
+    let real_path = fs::canonicalize(path).map_err(cannot_read)?;
+    let readable = dirs
+        .iter()
+        .filter_map(|dir| fs::canonicalize(dir).ok())
+        .any(|dir| real_path.starts_with(dir));
+    if !readable {
+        return Err(format!("cannot read {}: not below a readable directory", path).into());
+    }
+    fs::read_to_string(&real_path).map_err(|error| cannot_read(error).into())
+}
+
+/// The output of the `Exec` line `exec`, killed when the time limit of the call is reached.
+fn run(exec: &str) -> Result<String, Box<EvalAltResult>> {
+    let command = Command::new(exec.to_string());
+    let command_line = command
+        .expand(&[])
+        .map_err(|error| error.to_string())?
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    let (program, args) = command_line
+        .split_first()
+        .ok_or_else(|| String::from("empty command"))?;
+
+    let timeout = DEADLINE.with(Cell::get).map_or(Duration::ZERO, |deadline| {
+        deadline.saturating_duration_since(Instant::now())
+    });
+    let mut process = process::Command::new(program);
+    process.args(args);
+    let stdout = command::output_within(exec, &mut process, timeout)?;
+    Ok(String::from_utf8_lossy(&stdout).into_owned())
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::{ScriptPermissions, ScriptProvider};
+    use crate::mock::{self, MockFile};
+    use std::env;
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn calls_script_functions() {
+        let script = ScriptProvider::parse(
+            r#"
+fn get_file_items(files) {
+    if files.len() != 1 || !files[0].mime.starts_with("image/") {
+        return [];
+    }
+    [#{
+        name: "Photos::tools",
+        label: "Photo tools",
+        items: [#{ name: "Photos::rotate", label: files[0].name, command: "mogrify %f" }],
+    }]
+}
+
+fn get_columns() {
+    [#{ name: "Photos::frame", attribute: "frame", label: "Frame" }]
+}
+
+fn update_file_info(file) {
+    if file.name.starts_with("IMG_") {
+        #{ frame: file.name.sub_string(4, 4) }
+    }
+}
+
+fn get_background_items(folder) {
+    loop {}
+}
+"#,
+            ScriptPermissions {
+                run_commands: true,
+                time_limit: Duration::from_millis(10),
+                ..ScriptPermissions::default()
+            },
+        )
+        .unwrap();
+
+        let photo = || MockFile::new("file:///tmp/IMG_0042.jpg").mime_type("image/jpeg");
+        let notes = MockFile::new("file:///tmp/notes.txt");
+        let photo_items = mock::get_file_items(&script, &[photo()]);
+        let rotate = &photo_items[0].submenu[0];
+        assert_eq!(rotate.name, "Photos::rotate");
+        assert_eq!(rotate.label, "IMG_0042.jpg");
+        assert!(rotate.has_activate_handler);
+        assert!(mock::get_file_items(&script, &[notes]).is_empty());
+
+        assert_eq!(mock::get_columns(&script)[0].attribute, "frame");
+        let photo = photo();
+        assert!(mock::update_file_info(&script, &photo));
+        assert_eq!(photo.attribute("frame").as_deref(), Some("0042"));
+
+        // stopped by the time limit
+        let folder = MockFile::new("file:///tmp").directory(true);
+        assert!(mock::get_background_items(&script, &folder).is_empty());
+
+        let error = ScriptProvider::parse("fn get_columns( {", ScriptPermissions::default())
+            .err()
+            .unwrap();
+        assert!(error.to_string().starts_with("line 1, column "));
+    }
+
+    #[test]
+    fn needs_permission_for_commands() {
+        let source = r#"
+fn get_file_items(files) {
+    [#{ name: "Notes::count", label: "Count", command: "wc -l %F" }]
+}
+"#;
+        let notes = || MockFile::new("file:///tmp/notes.txt");
+
+        let script = ScriptProvider::parse(source, ScriptPermissions::default()).unwrap();
+        assert!(mock::get_file_items(&script, &[notes()]).is_empty());
+
+        let permissions = ScriptPermissions {
+            run_commands: true,
+            ..ScriptPermissions::default()
+        };
+        let script = ScriptProvider::parse(source, permissions).unwrap();
+        assert_eq!(mock::get_file_items(&script, &[notes()]).len(), 1);
+    }
+
+    #[test]
+    fn kills_commands_at_the_time_limit() {
+        let script = ScriptProvider::parse(
+            r#"
+fn get_columns() {
+    run("sleep 10");
+    [#{ name: "Notes::lines", attribute: "lines", label: "Lines" }]
+}
+"#,
+            ScriptPermissions {
+                run_commands: true,
+                time_limit: Duration::from_millis(100),
+                ..ScriptPermissions::default()
+            },
+        )
+        .unwrap();
+
+        let start = Instant::now();
+        assert!(mock::get_columns(&script).is_empty());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn reads_files_below_readable_dirs() {
+        let dir = env::temp_dir().join(format!("script-files-{}", std::process::id()));
+        let readable = dir.join("readable");
+        fs::create_dir_all(&readable).unwrap();
+        fs::write(readable.join("label.txt"), "Frame").unwrap();
+        fs::write(dir.join("secret.txt"), "Secret").unwrap();
+
+        let label = |path: &str| {
+            let source = format!(
+                r#"
+fn get_columns() {{
+    [#{{ name: "Photos::frame", attribute: "frame", label: read_file("{}") }}]
+}}
+"#,
+                path
+            );
+            let permissions = ScriptPermissions {
+                read_dirs: vec![readable.clone()],
+                ..ScriptPermissions::default()
+            };
+            let script = ScriptProvider::parse(&source, permissions).unwrap();
+            mock::get_columns(&script)
+                .first()
+                .map(|column| column.label.clone())
+        };
+        let path = |path: &std::path::Path| path.display().to_string();
+
+        assert_eq!(
+            label(&path(&readable.join("label.txt"))).as_deref(),
+            Some("Frame")
+        );
+        assert_eq!(label(&path(&dir.join("secret.txt"))), None);
+        assert_eq!(label(&path(&readable.join("../secret.txt"))), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn limits_memory_and_recursion() {
+        let script = ScriptProvider::parse(
+            r#"
+fn get_columns() {
+    let label = "x";
+    loop { label += label; }
+}
+
+fn deeper(depth) {
+    deeper(depth + 1)
+}
+
+fn get_background_items(folder) {
+    deeper(0)
+}
+
+fn update_file_info(file) {
+    let items = [];
+    loop { items.push(1); }
+}
+"#,
+            ScriptPermissions {
+                time_limit: Duration::from_secs(10),
+                ..ScriptPermissions::default()
+            },
+        )
+        .unwrap();
+
+        // each call fails on a limit long before the time limit
+        assert!(mock::get_columns(&script).is_empty());
+        let folder = MockFile::new("file:///tmp").directory(true);
+        assert!(mock::get_background_items(&script, &folder).is_empty());
+        let notes = MockFile::new("file:///tmp/notes.txt");
+        assert!(mock::update_file_info(&script, &notes));
+        assert!(notes.attributes().is_empty());
+    }
+}
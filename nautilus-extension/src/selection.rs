@@ -0,0 +1,180 @@
+//! Facts about a whole selection, computed once per menu request:
+//!
+//! ```ignore
+//! fn get_selection_items(&self, _window: *mut GtkWidget, selection: &Selection) -> Vec<MenuItem> {
+//!     if !selection.all_local() || !selection.same_directory() {
+//!         return vec![];
+//!     }
+//!     ...
+//! }
+//! ```
+//!
+//! The menu glue builds one `Selection` of the files of a menu request and passes it to the
+//! `MenuProvider::get_selection_items()` of every provider of the library. The facts are
+//! gathered in one pass on the first query, which makes the per-file calls into Nautilus only
+//! once per request.
+
+use crate::gobject_ffi::{g_object_ref, g_object_unref, GObject};
+use crate::info_provider::FileInfo;
+use std::cell::OnceCell;
+
+pub struct Selection {
+    files: Vec<FileInfo>,
+    summary: OnceCell<Summary>,
+}
+
+struct Summary {
+    all_local: bool,
+    same_directory: bool,
+    any_directory: bool,
+    mime_types: Vec<(String, usize)>,
+}
+
+impl Selection {
+    /// A selection of `files`, holding a reference to each of them.
+    pub fn new(files: &[FileInfo]) -> Selection {
+        for file in files {
+            unsafe {
+                g_object_ref(file.raw_file_info as *mut GObject);
+            }
+        }
+        Selection {
+            files: files
+                .iter()
+                .map(|f| FileInfo::new(f.raw_file_info))
+                .collect(),
+            summary: OnceCell::new(),
+        }
+    }
+
+    pub fn files(&self) -> &[FileInfo] {
+        &self.files
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Every file has the `file` URI scheme.
+    pub fn all_local(&self) -> bool {
+        self.summary().all_local
+    }
+
+    /// Every file is in the same folder.
+    pub fn same_directory(&self) -> bool {
+        self.summary().same_directory
+    }
+
+    /// At least one of the files is a directory.
+    pub fn any_directory(&self) -> bool {
+        self.summary().any_directory
+    }
+
+    /// The MIME types of the files and how many files have each, in the order they first
+    /// appear in the selection.
+    pub fn mime_types(&self) -> &[(String, usize)] {
+        &self.summary().mime_types
+    }
+
+    /// The number of files of MIME type `mime_type`, exactly as reported by Nautilus.
+    pub fn count_mime_type(&self, mime_type: &str) -> usize {
+        self.mime_types()
+            .iter()
+            .find(|(t, _)| t == mime_type)
+            .map_or(0, |&(_, count)| count)
+    }
+
+    fn summary(&self) -> &Summary {
+        self.summary.get_or_init(|| Summary::new(&self.files))
+    }
+}
+
+impl Drop for Selection {
+    fn drop(&mut self) {
+        for file in &self.files {
+            unsafe {
+                g_object_unref(file.raw_file_info as *mut GObject);
+            }
+        }
+    }
+}
+
+impl Summary {
+    fn new(files: &[FileInfo]) -> Summary {
+        let mut summary = Summary {
+            all_local: true,
+            same_directory: true,
+            any_directory: false,
+            mime_types: Vec::new(),
+        };
+        let mut directory: Option<String> = None;
+
+        for file in files {
+            summary.all_local &= file.get_uri_scheme() == "file";
+            summary.any_directory |= file.is_directory();
+
+            let mime_type = file.get_mime_type();
+            match summary
+                .mime_types
+                .iter_mut()
+                .find(|&&mut (ref t, _)| *t == mime_type)
+            {
+                Some((_, count)) => *count += 1,
+                None => summary.mime_types.push((mime_type, 1)),
+            }
+
+            let parent_uri = file.get_parent_uri();
+            match directory {
+                Some(ref directory) => summary.same_directory &= *directory == parent_uri,
+                None => directory = Some(parent_uri),
+            }
+        }
+
+        summary
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::Selection;
+    use crate::info_provider::FileInfo;
+    use crate::mock::MockFile;
+
+    #[test]
+    fn summarizes_files() {
+        let photo = MockFile::new("file:///tmp/photo.jpg").mime_type("image/jpeg");
+        let other_photo = MockFile::new("file:///tmp/other.jpg").mime_type("image/jpeg");
+        let folder = MockFile::new("file:///home/user/Music/")
+            .mime_type("inode/directory")
+            .directory(true);
+        let files: Vec<FileInfo> = vec![photo.file_info(), other_photo.file_info()];
+
+        let selection = Selection::new(&files);
+        assert_eq!(selection.len(), 2);
+        assert!(selection.all_local());
+        assert!(selection.same_directory());
+        assert!(!selection.any_directory());
+        assert_eq!(selection.count_mime_type("image/jpeg"), 2);
+        assert_eq!(selection.count_mime_type("text/plain"), 0);
+
+        let files = vec![
+            photo.file_info(),
+            folder.file_info(),
+            other_photo.file_info(),
+        ];
+        let selection = Selection::new(&files);
+        assert!(!selection.same_directory());
+        assert!(selection.any_directory());
+        assert_eq!(
+            selection.mime_types(),
+            [
+                ("image/jpeg".to_string(), 2),
+                ("inode/directory".to_string(), 1)
+            ]
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Service menus of the Dolphin file manager, `.desktop` files of `Type=Service` whose
+//! `[Desktop Action]` groups are the items.
+//!
+//! `MimeType`, `X-KDE-Protocols`, `X-KDE-MinNumberOfUrls`, `X-KDE-MaxNumberOfUrls` and
+//! `X-KDE-Submenu` are supported. An action whose `Exec` line fails `Command::check()` is
+//! dropped with a warning.
+
+use crate::command::Command;
+use crate::desktop_file::DesktopFile;
+use crate::file_manager_actions::{Entry, Kind, Pattern, Profile};
+use crate::logging;
+use std::path::Path;
+
+pub(crate) fn is_service_menu(file: &DesktopFile) -> bool {
+    file.group("Desktop Entry")
+        .and_then(|group| group.string("Type"))
+        .as_deref()
+        == Some("Service")
+}
+
+/// The actions of the service menu, with their submenu if it has one. The id of an action is
+/// that of the service menu, `/` and its own. `path` is where the service menu was read from.
+pub(crate) fn parse_entries(path: &Path, id: &str, file: &DesktopFile) -> Vec<(String, Entry)> {
+    let group = match file.group("Desktop Entry") {
+        Some(group) => group,
+        None => return Vec::new(),
+    };
+
+    let mime_types = group
+        .list("MimeType")
+        .unwrap_or_else(|| vec![String::from("all/all")]);
+    let protocols: Vec<String> = group
+        .string("X-KDE-Protocols")
+        .map(|protocols| {
+            protocols
+                .split(&[',', ';'][..])
+                .filter(|protocol| !protocol.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut selection_counts = Vec::new();
+    let count = |key: &str| {
+        group
+            .string(key)
+            .and_then(|n| n.trim().parse::<usize>().ok())
+    };
+    if let Some(min) = count("X-KDE-MinNumberOfUrls") {
+        selection_counts.push(('>', min.saturating_sub(1)));
+    }
+    if let Some(max) = count("X-KDE-MaxNumberOfUrls") {
+        selection_counts.push(('<', max + 1));
+    }
+
+    let mut entries: Vec<(String, Entry)> = group
+        .list("Actions")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|action| {
+            let action_group = file.group(&format!("Desktop Action {}", action))?;
+            if action_group.boolean("NoDisplay").unwrap_or(false) {
+                return None;
+            }
+
+            let command = Command::new(action_group.string("Exec")?);
+            if let Err(error) = command.check() {
+                logging::warning(&format!("{}: {}: {}", path.display(), action, error));
+                return None;
+            }
+
+            let mut profile = Profile::new(command);
+            profile.mime_types = Pattern::list(&mime_types);
+            profile.schemes = Pattern::list(&protocols);
+            profile.selection_counts = selection_counts.clone();
+
+            let entry = Entry {
+                label: action_group.locale_string("Name")?,
+                tip: action_group.locale_string("Comment").unwrap_or_default(),
+                icon: action_group
+                    .locale_string("Icon")
+                    .filter(|icon| !icon.is_empty()),
+                files: true,
+                background: false,
+                kind: Kind::Action(vec![profile]),
+            };
+            Some((format!("{}/{}", id, action), entry))
+        })
+        .collect();
+
+    if let Some(submenu) = group.locale_string("X-KDE-Submenu") {
+        let menu = Entry {
+            label: submenu,
+            tip: String::new(),
+            icon: group.locale_string("Icon").filter(|icon| !icon.is_empty()),
+            files: true,
+            background: false,
+            kind: Kind::Menu(entries.iter().map(|(id, _)| id.clone()).collect()),
+        };
+        entries.push((id.to_string(), menu));
+    }
+    entries
+}
@@ -0,0 +1,245 @@
+use crate::logging;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Upper bounds, in milliseconds, of the latency histogram buckets. The last bucket of
+/// `CallbackStats::histogram` counts every call slower than the last bound.
+pub const LATENCY_BUCKET_BOUNDS_MS: [u64; 9] = [1, 2, 4, 8, 16, 32, 64, 128, 256];
+
+pub const DEFAULT_SLOW_CALLBACK_BUDGET: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProviderKind {
+    Column,
+    Info,
+    Menu,
+    PropertyPage,
+    PropertiesModel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Callback {
+    GetColumns,
+    /// The part of `update_file_info` that runs on the UI thread, including `should_update_file_info`.
+    UpdateFileInfo,
+    /// The part of `update_file_info` that runs on the background thread.
+    UpdateFileInfoBackground,
+    GetFileItems,
+    GetBackgroundItems,
+    GetPages,
+    GetModels,
+}
+
+impl Callback {
+    pub fn runs_on_ui_thread(self) -> bool {
+        self != Callback::UpdateFileInfoBackground
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallbackStats {
+    pub calls: u64,
+    pub total: Duration,
+    pub max: Duration,
+    pub histogram: [u64; LATENCY_BUCKET_BOUNDS_MS.len() + 1],
+}
+
+impl CallbackStats {
+    pub fn mean(&self) -> Duration {
+        if self.calls == 0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_nanos((self.total.as_nanos() / u128::from(self.calls)) as u64)
+        }
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        self.calls += 1;
+        self.total += elapsed;
+        if elapsed > self.max {
+            self.max = elapsed;
+        }
+
+        let bucket = LATENCY_BUCKET_BOUNDS_MS
+            .iter()
+            .position(|&bound_ms| elapsed < Duration::from_millis(bound_ms))
+            .unwrap_or(LATENCY_BUCKET_BOUNDS_MS.len());
+        self.histogram[bucket] += 1;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProviderStats {
+    pub kind: ProviderKind,
+    /// Order in which the provider was added, among providers of the same kind.
+    pub index: usize,
+    pub callbacks: Vec<(Callback, CallbackStats)>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleStats {
+    pub providers: Vec<ProviderStats>,
+}
+
+lazy_static! {
+    static ref CALLBACK_STATS: Mutex<HashMap<(ProviderKind, usize, Callback), CallbackStats>> =
+        Mutex::new(HashMap::new());
+}
+
+static SLOW_CALLBACK_BUDGET_MICROS: AtomicU64 =
+    AtomicU64::new(DEFAULT_SLOW_CALLBACK_BUDGET.as_micros() as u64);
+
+/// Warn through the GLib log when a provider callback blocks the UI thread for longer than
+/// `budget`. The budget applies to every provider of the process, as the stats do.
+pub fn set_slow_callback_budget(budget: Duration) {
+    SLOW_CALLBACK_BUDGET_MICROS.store(budget.as_micros() as u64, Ordering::SeqCst);
+}
+
+pub fn slow_callback_budget() -> Duration {
+    Duration::from_micros(SLOW_CALLBACK_BUDGET_MICROS.load(Ordering::SeqCst))
+}
+
+pub fn timed<T, F: FnOnce() -> T>(kind: ProviderKind, index: usize, callback: Callback, f: F) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(kind, index, callback, start.elapsed());
+    result
+}
+
+fn record(kind: ProviderKind, index: usize, callback: Callback, elapsed: Duration) {
+    CALLBACK_STATS
+        .lock()
+        .unwrap()
+        .entry((kind, index, callback))
+        .or_default()
+        .record(elapsed);
+
+    if let Some(warning) = slow_callback_warning(kind, index, callback, elapsed) {
+        logging::warning(&warning);
+    }
+}
+
+/// The warning for a call that took `elapsed`, if it blocked the UI thread for too long.
+fn slow_callback_warning(
+    kind: ProviderKind,
+    index: usize,
+    callback: Callback,
+    elapsed: Duration,
+) -> Option<String> {
+    let budget = slow_callback_budget();
+    if !callback.runs_on_ui_thread() || elapsed <= budget {
+        return None;
+    }
+    Some(format!(
+        "{:?} provider {} took {} ms in {:?}, over the {} ms budget",
+        kind,
+        index,
+        elapsed.as_millis(),
+        callback,
+        budget.as_millis()
+    ))
+}
+
+pub fn snapshot(provider_slots: &[(ProviderKind, usize)]) -> ModuleStats {
+    let callback_stats = CALLBACK_STATS.lock().unwrap();
+
+    let providers = provider_slots
+        .iter()
+        .map(|&(kind, index)| {
+            let mut callbacks: Vec<(Callback, CallbackStats)> = callback_stats
+                .iter()
+                .filter(|&(&(k, i, _), _)| k == kind && i == index)
+                .map(|(&(_, _, callback), stats)| (callback, stats.clone()))
+                .collect();
+            callbacks.sort_by_key(|&(callback, _)| callback as u8);
+
+            ProviderStats {
+                kind,
+                index,
+                callbacks,
+            }
+        })
+        .collect();
+
+    ModuleStats { providers }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets() {
+        let mut stats = CallbackStats::default();
+        stats.record(Duration::from_micros(500));
+        stats.record(Duration::from_millis(3));
+        stats.record(Duration::from_secs(1));
+
+        assert_eq!(stats.calls, 3);
+        assert_eq!(stats.max, Duration::from_secs(1));
+        assert_eq!(stats.histogram[0], 1);
+        assert_eq!(stats.histogram[2], 1);
+        assert_eq!(stats.histogram[LATENCY_BUCKET_BOUNDS_MS.len()], 1);
+    }
+
+    #[test]
+    fn mean_of_calls() {
+        let mut stats = CallbackStats::default();
+        assert_eq!(stats.mean(), Duration::from_secs(0));
+
+        stats.record(Duration::from_millis(1));
+        stats.record(Duration::from_millis(2));
+        assert_eq!(stats.mean(), Duration::from_micros(1500));
+
+        // a count that truncates to zero as a u32
+        stats.calls = 1 << 32;
+        stats.total = Duration::from_secs(1 << 32);
+        assert_eq!(stats.mean(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn warns_about_slow_ui_callbacks() {
+        let warnings = logging::capture_warnings(|| {
+            set_slow_callback_budget(Duration::from_millis(10));
+            assert_eq!(slow_callback_budget(), Duration::from_millis(10));
+
+            // record() takes the elapsed time timed() measures, so no call has to be slow
+            let slow = Duration::from_millis(20);
+            record(ProviderKind::Menu, 7, Callback::GetFileItems, slow);
+            record(
+                ProviderKind::Info,
+                7,
+                Callback::UpdateFileInfoBackground,
+                slow,
+            );
+            record(
+                ProviderKind::Column,
+                7,
+                Callback::GetColumns,
+                Duration::from_millis(10),
+            );
+
+            set_slow_callback_budget(DEFAULT_SLOW_CALLBACK_BUDGET);
+        });
+
+        let warnings: Vec<&String> = warnings
+            .iter()
+            .filter(|warning| warning.contains("provider 7 took"))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("Menu provider 7 took "));
+        assert!(warnings[0].ends_with(" ms in GetFileItems, over the 10 ms budget"));
+
+        assert_eq!(
+            slow_callback_warning(
+                ProviderKind::Menu,
+                0,
+                Callback::GetFileItems,
+                Duration::from_millis(50)
+            ),
+            None
+        );
+    }
+}
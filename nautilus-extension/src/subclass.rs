@@ -0,0 +1,739 @@
+//! Nautilus provider interfaces for types defined with `glib::subclass`, so an extension type can
+//! have its own properties, signals and instance state.
+//!
+//! Implement the `*Impl` traits of the interfaces on the implementation struct, list them in
+//! `@implements`, and add them with a `Registration` in the register function:
+//!
+//! ```ignore
+//! use glib::subclass::prelude::*;
+//! use nautilus_extension::subclass::{self, MenuProviderImpl, MenuProviderInterface};
+//!
+//! #[derive(Default)]
+//! pub struct Tags;
+//!
+//! #[glib::object_subclass]
+//! impl ObjectSubclass for Tags {
+//!     const NAME: &'static str = "TagsExtension";
+//!     type Type = TagsExtension;
+//!     type ParentType = glib::Object;
+//! }
+//!
+//! impl ObjectImpl for Tags {}
+//!
+//! impl MenuProviderImpl for Tags {
+//!     fn get_file_items(&self, provider: &TagsExtension, window: *mut GtkWidget, files: &[FileInfo]) -> Vec<MenuItem> {
+//!         ...
+//!     }
+//! }
+//!
+//! glib::wrapper! {
+//!     pub struct TagsExtension(ObjectSubclass<Tags>) @implements MenuProviderInterface;
+//! }
+//!
+//! fn register(module: *mut GTypeModule) -> GType {
+//!     subclass::Registration::<Tags>::new(module)
+//!         .add_menu_provider()
+//!         .register()
+//! }
+//!
+//! nautilus_module!(register);
+//! ```
+//!
+//! The interfaces are added on the `GTypeModule`, like those of `NautilusModule::register()`,
+//! so do not list them in `ObjectSubclass::Interfaces` too. The type itself cannot be: glib
+//! 0.15 registers every subclass with `g_type_register_static()`, and keeps the class and
+//! instance initializers it would take to register one on a module private. `register()`
+//! therefore keeps the module in use, and Nautilus never unloads the library.
+//!
+//! The callbacks run on the main thread, so unlike the provider traits the implementations do
+//! not need to be `Send` or `Sync`. They are timed in `Registration::stats()`, and menu
+//! providers get the files as a `Selection`, as `MenuProvider`s do.
+
+use crate::column_provider::{columns_to_g_list, take_next_column_provider_iface_index, Column};
+use crate::glib_ffi::{gpointer, GList, GType};
+use crate::gobject_ffi::{
+    g_type_module_add_interface, g_type_module_use, GClosure, GInterfaceInfo, GObject, GTypeModule,
+};
+#[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+use crate::gtk_ffi::GtkWidget;
+use crate::info_provider::{add_attributes_to_raw, take_next_info_provider_iface_index, FileInfo};
+#[cfg(feature = "gtk-rs")]
+use crate::menu_provider::window_from_raw;
+use crate::menu_provider::{
+    background_items_to_g_list, file_items_to_g_list, take_next_menu_provider_iface_index, MenuItem,
+};
+use crate::nautilus_ffi;
+use crate::nautilus_ffi::{
+    NautilusFileInfo, NautilusInfoProvider, NautilusOperationHandle, NautilusOperationResult,
+};
+#[cfg(nautilus_43)]
+use crate::properties_model_provider::{
+    models_to_g_list, take_next_properties_model_provider_iface_index, PropertiesModel,
+};
+#[cfg(not(nautilus_43))]
+use crate::property_page_provider::{
+    pages_to_g_list, take_next_property_page_provider_iface_index, PropertyPage,
+};
+use crate::selection::Selection;
+use crate::stats::{self, Callback, ModuleStats, ProviderKind};
+use crate::translate::file_info_vec_from_g_list;
+use glib::subclass::prelude::*;
+use glib::translate::{from_glib_borrow, Borrowed, IntoGlib};
+use glib::{Cast, StaticType};
+use libc::c_void;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::Mutex;
+
+glib::wrapper! {
+    pub struct ColumnProviderInterface(Interface<nautilus_ffi::NautilusColumnProvider, nautilus_ffi::NautilusColumnProviderIface>);
+
+    match fn {
+        type_ => || nautilus_ffi::nautilus_column_provider_get_type(),
+    }
+}
+
+glib::wrapper! {
+    pub struct InfoProviderInterface(Interface<nautilus_ffi::NautilusInfoProvider, nautilus_ffi::NautilusInfoProviderIface>);
+
+    match fn {
+        type_ => || nautilus_ffi::nautilus_info_provider_get_type(),
+    }
+}
+
+glib::wrapper! {
+    pub struct MenuProviderInterface(Interface<nautilus_ffi::NautilusMenuProvider, nautilus_ffi::NautilusMenuProviderIface>);
+
+    match fn {
+        type_ => || nautilus_ffi::nautilus_menu_provider_get_type(),
+    }
+}
+
+#[cfg(not(nautilus_43))]
+glib::wrapper! {
+    pub struct PropertyPageProviderInterface(Interface<nautilus_ffi::NautilusPropertyPageProvider, nautilus_ffi::NautilusPropertyPageProviderIface>);
+
+    match fn {
+        type_ => || nautilus_ffi::nautilus_property_page_provider_get_type(),
+    }
+}
+
+#[cfg(nautilus_43)]
+glib::wrapper! {
+    pub struct PropertiesModelProviderInterface(Interface<nautilus_ffi::NautilusPropertiesModelProvider, nautilus_ffi::NautilusPropertiesModelProviderIface>);
+
+    match fn {
+        type_ => || nautilus_ffi::nautilus_properties_model_provider_get_type(),
+    }
+}
+
+pub trait ColumnProviderImpl: ObjectImpl {
+    fn get_columns(&self, provider: &Self::Type) -> Vec<Column>;
+}
+
+/// Unlike `InfoProvider`, the update runs on the main thread and completes before Nautilus
+/// gets the result back.
+pub trait InfoProviderImpl: ObjectImpl {
+    #[allow(unused_variables)]
+    fn should_update_file_info(&self, provider: &Self::Type, file_info: &FileInfo) -> bool {
+        true
+    }
+
+    fn update_file_info(&self, provider: &Self::Type, file_info: &mut FileInfo);
+}
+
+pub trait MenuProviderImpl: ObjectImpl {
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    #[allow(unused_variables)]
+    fn get_file_items(
+        &self,
+        provider: &Self::Type,
+        window: *mut GtkWidget,
+        files: &[FileInfo],
+    ) -> Vec<MenuItem> {
+        Vec::new()
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    #[allow(unused_variables)]
+    fn get_file_items(
+        &self,
+        provider: &Self::Type,
+        window: Option<&gtk::Window>,
+        files: &[FileInfo],
+    ) -> Vec<MenuItem> {
+        Vec::new()
+    }
+
+    #[cfg(nautilus_43)]
+    #[allow(unused_variables)]
+    fn get_file_items(&self, provider: &Self::Type, files: &[FileInfo]) -> Vec<MenuItem> {
+        Vec::new()
+    }
+
+    /// Like `MenuProvider::get_selection_items()`, calls `get_file_items()` unless overridden.
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    fn get_selection_items(
+        &self,
+        provider: &Self::Type,
+        window: *mut GtkWidget,
+        selection: &Selection,
+    ) -> Vec<MenuItem> {
+        self.get_file_items(provider, window, selection.files())
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    fn get_selection_items(
+        &self,
+        provider: &Self::Type,
+        window: Option<&gtk::Window>,
+        selection: &Selection,
+    ) -> Vec<MenuItem> {
+        self.get_file_items(provider, window, selection.files())
+    }
+
+    #[cfg(nautilus_43)]
+    fn get_selection_items(&self, provider: &Self::Type, selection: &Selection) -> Vec<MenuItem> {
+        self.get_file_items(provider, selection.files())
+    }
+
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    #[allow(unused_variables)]
+    fn get_background_items(
+        &self,
+        provider: &Self::Type,
+        window: *mut GtkWidget,
+        current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        Vec::new()
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    #[allow(unused_variables)]
+    fn get_background_items(
+        &self,
+        provider: &Self::Type,
+        window: Option<&gtk::Window>,
+        current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        Vec::new()
+    }
+
+    #[cfg(nautilus_43)]
+    #[allow(unused_variables)]
+    fn get_background_items(
+        &self,
+        provider: &Self::Type,
+        current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        Vec::new()
+    }
+}
+
+#[cfg(not(nautilus_43))]
+pub trait PropertyPageProviderImpl: ObjectImpl {
+    fn get_pages(&self, provider: &Self::Type, files: &[FileInfo]) -> Vec<PropertyPage>;
+}
+
+#[cfg(nautilus_43)]
+pub trait PropertiesModelProviderImpl: ObjectImpl {
+    fn get_models(&self, provider: &Self::Type, files: &[FileInfo]) -> Vec<PropertiesModel>;
+}
+
+lazy_static! {
+    /// The provider index of each interface added by a `Registration`, by kind and type, for
+    /// the stats of its callbacks.
+    static ref PROVIDER_INDICES: Mutex<HashMap<(ProviderKind, GType), usize>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Adds the provider interfaces of the `glib::subclass` type `T` on a `GTypeModule`, the way
+/// `NautilusModule` does for the type it creates.
+pub struct Registration<T> {
+    module: *mut GTypeModule,
+    interfaces: Vec<(GType, GInterfaceInfo)>,
+    provider_slots: Vec<(ProviderKind, usize)>,
+    subclass: PhantomData<T>,
+}
+
+impl<T: ObjectSubclass> Registration<T> {
+    pub fn new(module: *mut GTypeModule) -> Registration<T> {
+        Registration {
+            module,
+            interfaces: Vec::new(),
+            provider_slots: Vec::new(),
+            subclass: PhantomData,
+        }
+    }
+
+    pub fn add_column_provider(self) -> Registration<T>
+    where
+        T: ColumnProviderImpl,
+    {
+        self.add_interface(
+            ProviderKind::Column,
+            take_next_column_provider_iface_index(),
+            unsafe { nautilus_ffi::nautilus_column_provider_get_type() },
+            column_provider_iface_init::<T>,
+        )
+    }
+
+    pub fn add_info_provider(self) -> Registration<T>
+    where
+        T: InfoProviderImpl,
+    {
+        self.add_interface(
+            ProviderKind::Info,
+            take_next_info_provider_iface_index(),
+            unsafe { nautilus_ffi::nautilus_info_provider_get_type() },
+            info_provider_iface_init::<T>,
+        )
+    }
+
+    pub fn add_menu_provider(self) -> Registration<T>
+    where
+        T: MenuProviderImpl,
+    {
+        self.add_interface(
+            ProviderKind::Menu,
+            take_next_menu_provider_iface_index(),
+            unsafe { nautilus_ffi::nautilus_menu_provider_get_type() },
+            menu_provider_iface_init::<T>,
+        )
+    }
+
+    #[cfg(not(nautilus_43))]
+    pub fn add_property_page_provider(self) -> Registration<T>
+    where
+        T: PropertyPageProviderImpl,
+    {
+        self.add_interface(
+            ProviderKind::PropertyPage,
+            take_next_property_page_provider_iface_index(),
+            unsafe { nautilus_ffi::nautilus_property_page_provider_get_type() },
+            property_page_provider_iface_init::<T>,
+        )
+    }
+
+    #[cfg(nautilus_43)]
+    pub fn add_properties_model_provider(self) -> Registration<T>
+    where
+        T: PropertiesModelProviderImpl,
+    {
+        self.add_interface(
+            ProviderKind::PropertiesModel,
+            take_next_properties_model_provider_iface_index(),
+            unsafe { nautilus_ffi::nautilus_properties_model_provider_get_type() },
+            properties_model_provider_iface_init::<T>,
+        )
+    }
+
+    fn add_interface(
+        mut self,
+        kind: ProviderKind,
+        index: usize,
+        interface_type: GType,
+        interface_init: unsafe extern "C" fn(gpointer, gpointer),
+    ) -> Registration<T> {
+        self.interfaces.push((
+            interface_type,
+            GInterfaceInfo {
+                interface_init: Some(interface_init),
+                interface_finalize: None,
+                interface_data: ptr::null_mut(),
+            },
+        ));
+        self.provider_slots.push((kind, index));
+        self
+    }
+
+    /// Call counts and latency histograms of the interfaces added, in the order they were added.
+    pub fn stats(&self) -> ModuleStats {
+        stats::snapshot(&self.provider_slots)
+    }
+
+    /// Add the interfaces to `T` on the module, keep the module in use, and return the `GType`
+    /// of `T` for `nautilus_module!`.
+    pub fn register(&self) -> GType {
+        let subclass_type = T::Type::static_type().into_glib();
+
+        let mut provider_indices = PROVIDER_INDICES.lock().unwrap();
+        for &(kind, index) in &self.provider_slots {
+            provider_indices.insert((kind, subclass_type), index);
+        }
+        drop(provider_indices);
+
+        unsafe {
+            g_type_module_use(self.module);
+            for (interface_type, interface_info) in &self.interfaces {
+                g_type_module_add_interface(
+                    self.module,
+                    subclass_type,
+                    *interface_type,
+                    interface_info,
+                );
+            }
+        }
+        subclass_type
+    }
+}
+
+/// The index `Registration::register()` gave the `kind` interface of `T`.
+fn provider_index<T: ObjectSubclass>(kind: ProviderKind) -> usize {
+    let subclass_type = T::Type::static_type().into_glib();
+    PROVIDER_INDICES
+        .lock()
+        .unwrap()
+        .get(&(kind, subclass_type))
+        .cloned()
+        .unwrap_or_default()
+}
+
+unsafe extern "C" fn column_provider_iface_init<T: ColumnProviderImpl>(
+    iface: gpointer,
+    _: gpointer,
+) {
+    let iface = iface as *mut nautilus_ffi::NautilusColumnProviderIface;
+    (*iface).get_columns = Some(column_provider_get_columns::<T>);
+}
+
+unsafe extern "C" fn info_provider_iface_init<T: InfoProviderImpl>(iface: gpointer, _: gpointer) {
+    let iface = iface as *mut nautilus_ffi::NautilusInfoProviderIface;
+    (*iface).update_file_info = Some(info_provider_update_file_info::<T>);
+    (*iface).cancel_update = None;
+}
+
+unsafe extern "C" fn menu_provider_iface_init<T: MenuProviderImpl>(iface: gpointer, _: gpointer) {
+    let iface = iface as *mut nautilus_ffi::NautilusMenuProviderIface;
+    (*iface).get_file_items = Some(menu_provider_get_file_items::<T>);
+    (*iface).get_background_items = Some(menu_provider_get_background_items::<T>);
+}
+
+#[cfg(not(nautilus_43))]
+unsafe extern "C" fn property_page_provider_iface_init<T: PropertyPageProviderImpl>(
+    iface: gpointer,
+    _: gpointer,
+) {
+    let iface = iface as *mut nautilus_ffi::NautilusPropertyPageProviderIface;
+    (*iface).get_pages = Some(property_page_provider_get_pages::<T>);
+}
+
+#[cfg(nautilus_43)]
+unsafe extern "C" fn properties_model_provider_iface_init<T: PropertiesModelProviderImpl>(
+    iface: gpointer,
+    _: gpointer,
+) {
+    let iface = iface as *mut nautilus_ffi::NautilusPropertiesModelProviderIface;
+    (*iface).get_models = Some(properties_model_provider_get_models::<T>);
+}
+
+/// The implementation and instance behind a provider pointer Nautilus passes in.
+unsafe fn with_provider<T: ObjectSubclass, R, F>(provider: *mut c_void, f: F) -> R
+where
+    F: FnOnce(&T, &T::Type) -> R,
+{
+    let object: Borrowed<glib::Object> = from_glib_borrow(provider as *mut GObject);
+    let provider = object.unsafe_cast_ref::<T::Type>();
+    f(T::from_instance(provider), provider)
+}
+
+extern "C" fn column_provider_get_columns<T: ColumnProviderImpl>(provider: gpointer) -> *mut GList {
+    let index = provider_index::<T>(ProviderKind::Column);
+    let columns = stats::timed(
+        ProviderKind::Column,
+        index,
+        Callback::GetColumns,
+        || unsafe { with_provider(provider, T::get_columns) },
+    );
+    columns_to_g_list(columns)
+}
+
+unsafe extern "C" fn info_provider_update_file_info<T: InfoProviderImpl>(
+    provider: *mut NautilusInfoProvider,
+    file_info: *mut NautilusFileInfo,
+    _update_complete: *mut GClosure,
+    _handle: *mut *mut NautilusOperationHandle,
+) -> NautilusOperationResult {
+    let mut file_info = FileInfo::new(file_info);
+
+    let index = provider_index::<T>(ProviderKind::Info);
+    stats::timed(ProviderKind::Info, index, Callback::UpdateFileInfo, || {
+        with_provider(provider as *mut c_void, |imp: &T, provider| {
+            if imp.should_update_file_info(provider, &file_info) {
+                imp.update_file_info(provider, &mut file_info);
+                add_attributes_to_raw(&file_info);
+            }
+        })
+    });
+
+    NautilusOperationResult::NautilusOperationComplete
+}
+
+#[cfg(not(nautilus_43))]
+extern "C" fn menu_provider_get_file_items<T: MenuProviderImpl>(
+    provider: gpointer,
+    window: *mut crate::gtk_ffi::GtkWidget,
+    files: *mut GList,
+) -> *mut GList {
+    #[cfg(feature = "gtk-rs")]
+    let window = unsafe { window_from_raw(window) };
+    #[cfg(feature = "gtk-rs")]
+    let window = window.as_ref();
+
+    let index = provider_index::<T>(ProviderKind::Menu);
+    file_items_to_g_list(index, files, |selection| unsafe {
+        with_provider(provider, |imp: &T, provider| {
+            imp.get_selection_items(provider, window, selection)
+        })
+    })
+}
+
+#[cfg(nautilus_43)]
+extern "C" fn menu_provider_get_file_items<T: MenuProviderImpl>(
+    provider: gpointer,
+    files: *mut GList,
+) -> *mut GList {
+    let index = provider_index::<T>(ProviderKind::Menu);
+    file_items_to_g_list(index, files, |selection| unsafe {
+        with_provider(provider, |imp: &T, provider| {
+            imp.get_selection_items(provider, selection)
+        })
+    })
+}
+
+#[cfg(not(nautilus_43))]
+extern "C" fn menu_provider_get_background_items<T: MenuProviderImpl>(
+    provider: gpointer,
+    window: *mut crate::gtk_ffi::GtkWidget,
+    current_folder: *mut NautilusFileInfo,
+) -> *mut GList {
+    #[cfg(feature = "gtk-rs")]
+    let window = unsafe { window_from_raw(window) };
+    #[cfg(feature = "gtk-rs")]
+    let window = window.as_ref();
+
+    let index = provider_index::<T>(ProviderKind::Menu);
+    background_items_to_g_list(index, current_folder, |folder| unsafe {
+        with_provider(provider, |imp: &T, provider| {
+            imp.get_background_items(provider, window, folder)
+        })
+    })
+}
+
+#[cfg(nautilus_43)]
+extern "C" fn menu_provider_get_background_items<T: MenuProviderImpl>(
+    provider: gpointer,
+    current_folder: *mut NautilusFileInfo,
+) -> *mut GList {
+    let index = provider_index::<T>(ProviderKind::Menu);
+    background_items_to_g_list(index, current_folder, |folder| unsafe {
+        with_provider(provider, |imp: &T, provider| {
+            imp.get_background_items(provider, folder)
+        })
+    })
+}
+
+#[cfg(not(nautilus_43))]
+extern "C" fn property_page_provider_get_pages<T: PropertyPageProviderImpl>(
+    provider: *mut nautilus_ffi::NautilusPropertyPageProvider,
+    files: *mut GList,
+) -> *mut GList {
+    let files_vec = file_info_vec_from_g_list(files);
+    let index = provider_index::<T>(ProviderKind::PropertyPage);
+    let pages = stats::timed(
+        ProviderKind::PropertyPage,
+        index,
+        Callback::GetPages,
+        || unsafe {
+            with_provider(provider as *mut c_void, |imp: &T, provider| {
+                imp.get_pages(provider, &files_vec)
+            })
+        },
+    );
+    pages_to_g_list(pages)
+}
+
+#[cfg(nautilus_43)]
+extern "C" fn properties_model_provider_get_models<T: PropertiesModelProviderImpl>(
+    provider: *mut nautilus_ffi::NautilusPropertiesModelProvider,
+    files: *mut GList,
+) -> *mut GList {
+    let files_vec = file_info_vec_from_g_list(files);
+    let index = provider_index::<T>(ProviderKind::PropertiesModel);
+    let models = stats::timed(
+        ProviderKind::PropertiesModel,
+        index,
+        Callback::GetModels,
+        || unsafe {
+            with_provider(provider as *mut c_void, |imp: &T, provider| {
+                imp.get_models(provider, &files_vec)
+            })
+        },
+    );
+    models_to_g_list(models)
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::{
+        ColumnProviderImpl, ColumnProviderInterface, MenuProviderImpl, MenuProviderInterface,
+        Registration,
+    };
+    use crate::glib_ffi::{g_list_append, gboolean, gpointer, GTRUE};
+    use crate::gobject_ffi::{
+        g_object_new, g_type_module_get_type, g_type_register_static_simple, GTypeModule,
+        GTypeModuleClass,
+    };
+    use crate::mock::{columns_from_g_list, menu_items_from_g_list, MockFile};
+    use crate::nautilus_ffi::{
+        nautilus_column_provider_get_columns, nautilus_menu_provider_get_file_items,
+    };
+    use crate::stats::{Callback, ProviderKind};
+    use crate::{Column, FileInfo, MenuItem};
+    use glib::subclass::prelude::*;
+    use glib::translate::IntoGlib;
+    use glib::{ObjectType, StaticType};
+    use libc::c_char;
+    use std::cell::Cell;
+    use std::mem;
+    use std::ptr;
+
+    #[derive(Default)]
+    pub struct Counter {
+        calls: Cell<u32>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Counter {
+        const NAME: &'static str = "NautilusExtensionTestCounter";
+        type Type = CounterExtension;
+        type ParentType = glib::Object;
+    }
+
+    impl ObjectImpl for Counter {}
+
+    impl ColumnProviderImpl for Counter {
+        fn get_columns(&self, _provider: &CounterExtension) -> Vec<Column> {
+            vec![Column::new("Counter::calls", "calls", "Calls", "")]
+        }
+    }
+
+    impl Counter {
+        fn file_items(&self, files: &[FileInfo]) -> Vec<MenuItem> {
+            self.calls.set(self.calls.get() + 1);
+            let label = format!("{} files, call {}", files.len(), self.calls.get());
+            vec![MenuItem::new(
+                String::from("Counter::count"),
+                label,
+                String::new(),
+                None,
+            )]
+        }
+    }
+
+    impl MenuProviderImpl for Counter {
+        #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+        fn get_file_items(
+            &self,
+            _provider: &CounterExtension,
+            _window: *mut crate::gtk_ffi::GtkWidget,
+            files: &[FileInfo],
+        ) -> Vec<MenuItem> {
+            self.file_items(files)
+        }
+
+        #[cfg(feature = "gtk-rs")]
+        fn get_file_items(
+            &self,
+            _provider: &CounterExtension,
+            _window: Option<&gtk::Window>,
+            files: &[FileInfo],
+        ) -> Vec<MenuItem> {
+            self.file_items(files)
+        }
+
+        #[cfg(nautilus_43)]
+        fn get_file_items(
+            &self,
+            _provider: &CounterExtension,
+            files: &[FileInfo],
+        ) -> Vec<MenuItem> {
+            self.file_items(files)
+        }
+    }
+
+    glib::wrapper! {
+        pub struct CounterExtension(ObjectSubclass<Counter>)
+            @implements ColumnProviderInterface, MenuProviderInterface;
+    }
+
+    unsafe extern "C" fn test_module_load(_module: *mut GTypeModule) -> gboolean {
+        GTRUE
+    }
+
+    unsafe extern "C" fn test_module_unload(_module: *mut GTypeModule) {}
+
+    unsafe extern "C" fn test_module_class_init(class: gpointer, _class_data: gpointer) {
+        let module_class = class as *mut GTypeModuleClass;
+        (*module_class).load = Some(test_module_load);
+        (*module_class).unload = Some(test_module_unload);
+    }
+
+    /// A module that loads nothing, standing in for the one Nautilus passes in.
+    fn test_module() -> *mut GTypeModule {
+        unsafe {
+            let module_type = g_type_register_static_simple(
+                g_type_module_get_type(),
+                b"NautilusExtensionSubclassTestModule\0".as_ptr() as *const c_char,
+                mem::size_of::<GTypeModuleClass>() as u32,
+                Some(test_module_class_init),
+                mem::size_of::<GTypeModule>() as u32,
+                None,
+                0,
+            );
+            g_object_new(module_type, ptr::null::<c_char>()) as *mut GTypeModule
+        }
+    }
+
+    #[test]
+    fn dispatches_to_subclass() {
+        let module = test_module();
+        let registration = Registration::<Counter>::new(module)
+            .add_column_provider()
+            .add_menu_provider();
+        assert_eq!(
+            registration.register(),
+            CounterExtension::static_type().into_glib()
+        );
+
+        let extension: CounterExtension = glib::Object::new(&[]).unwrap();
+        let object = extension.as_ptr();
+        let file = MockFile::new("file:///home/user/photo.jpg");
+
+        unsafe {
+            let columns =
+                columns_from_g_list(nautilus_column_provider_get_columns(object as *mut _));
+            assert_eq!(columns[0].name, "Counter::calls");
+
+            let files = g_list_append(ptr::null_mut(), file.file_info().raw_file_info as *mut _);
+            for call in 1..=2 {
+                #[cfg(not(nautilus_43))]
+                let items =
+                    nautilus_menu_provider_get_file_items(object as *mut _, ptr::null_mut(), files);
+                #[cfg(nautilus_43)]
+                let items = nautilus_menu_provider_get_file_items(object as *mut _, files);
+                let items = menu_items_from_g_list(items);
+                assert_eq!(items[0].label, format!("1 files, call {}", call));
+            }
+            crate::glib_ffi::g_list_free(files);
+        }
+
+        let stats = registration.stats();
+        assert_eq!(stats.providers[0].kind, ProviderKind::Column);
+        assert_eq!(stats.providers[1].kind, ProviderKind::Menu);
+        let (callback, ref menu_stats) = stats.providers[1].callbacks[0];
+        assert_eq!(callback, Callback::GetFileItems);
+        assert_eq!(menu_stats.calls, 2);
+    }
+}
@@ -0,0 +1,893 @@
+//! Providers compiled to WebAssembly components, with the `wasm` feature.
+//!
+//! A component implements the `extension` world of `wit/extension.wit`, whose `provider`
+//! interface mirrors `MenuProvider`, `ColumnProvider` and `InfoProvider`:
+//!
+//! ```ignore
+//! fn register_provider(module: *mut GTypeModule) -> GType {
+//!     let provider =
+//!         WasmProvider::load("/usr/lib/photos/extension.wasm", WasmLimits::default()).unwrap();
+//!     NautilusModule::new(module, "Photos")
+//!         .add_wasm_provider(provider)
+//!         .register()
+//! }
+//! ```
+//!
+//! The component runs in its own sandbox. It imports nothing but the `files` interface: no WASI,
+//! so no file system, network, clock or processes. Files are passed as a `files` resource that
+//! the component borrows for one call, and it can only read the metadata its methods return.
+//! When the user activates a menu item, the component's `activate` is called with the item's
+//! name and files. Items may instead have a `command` for the host to run, but those are
+//! dropped unless `WasmLimits::run_commands` is set. As WASI is not provided, build a Rust
+//! guest with `wit-bindgen` for `wasm32-unknown-unknown` rather than `wasm32-wasip2`, and make
+//! it a component with `wasm-tools component new`.
+//!
+//! Each call gets the fuel of `WasmLimits`, roughly one unit per instruction, and traps when
+//! it runs out. The component's memories and tables are limited in size. Traps and invalid
+//! results are logged as warnings, and the call returns nothing. A component instance runs one
+//! call at a time, so calls made at the same time get instances of their own, up to
+//! `WasmLimits::max_instances`, and further calls wait for one to be done. Instances are kept
+//! for later calls, but a component cannot rely on state kept between calls.
+
+use crate::column_provider::{Column, ColumnProvider};
+use crate::command::Command;
+#[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+use crate::gtk_ffi::GtkWidget;
+use crate::info_provider::{FileInfo, InfoProvider};
+use crate::logging;
+use crate::menu_provider::{Menu, MenuItem, MenuProvider};
+use std::error;
+use std::fmt;
+use std::path::Path;
+use std::slice;
+use std::sync::{Arc, Condvar, Mutex};
+use wasmtime::component::{Component, Linker, Resource, ResourceTable};
+use wasmtime::{Config, Engine, Store, StoreLimits, StoreLimitsBuilder};
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        path: "wit",
+        world: "extension",
+        trappable_imports: true,
+        with: {
+            "nautilus:extension/files/files": super::HostFiles,
+        },
+    });
+}
+
+use self::bindings::exports::nautilus::extension::provider;
+use self::bindings::nautilus::extension::files::{Host, HostFiles as FilesMethods};
+use self::bindings::Extension;
+
+/// The resources a component may use.
+#[derive(Clone, Debug)]
+pub struct WasmLimits {
+    /// The fuel of each call.
+    pub fuel: u64,
+    /// The size of each linear memory, in bytes.
+    pub memory_size: usize,
+    /// The number of elements of each table.
+    pub table_elements: usize,
+    /// The number of instances running calls at the same time, at least one. Each has
+    /// memories of its own.
+    pub max_instances: usize,
+    /// Accept menu items with a `command`.
+    pub run_commands: bool,
+}
+
+impl Default for WasmLimits {
+    /// 100 million units of fuel, 64 MiB memories, tables of 10,000 elements, 4 instances and
+    /// no commands.
+    fn default() -> WasmLimits {
+        WasmLimits {
+            fuel: 100_000_000,
+            memory_size: 64 << 20,
+            table_elements: 10_000,
+            max_instances: 4,
+            run_commands: false,
+        }
+    }
+}
+
+/// A menu, column and info provider implemented by a WebAssembly component. Clones share the
+/// component instances.
+#[derive(Clone)]
+pub struct WasmProvider {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    component: Component,
+    linker: Linker<HostState>,
+    limits: WasmLimits,
+    pool: Mutex<Pool>,
+    /// Notified when an instance is put back or dropped.
+    returned: Condvar,
+}
+
+struct Pool {
+    /// The instances not running a call.
+    idle: Vec<Instance>,
+    /// The number of instances, idle or running a call.
+    instances: usize,
+}
+
+struct Instance {
+    store: Store<HostState>,
+    extension: Extension,
+}
+
+struct HostState {
+    table: ResourceTable,
+    limits: StoreLimits,
+}
+
+/// The files lent to the component.
+pub struct HostFiles {
+    files: Vec<FileInfo>,
+}
+
+#[derive(Debug)]
+pub enum WasmError {
+    /// The file cannot be read or is not a valid component.
+    Compile(wasmtime::Error),
+    /// The component does not implement the `extension` world.
+    Instantiate(wasmtime::Error),
+}
+
+impl fmt::Display for WasmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WasmError::Compile(ref error) => write!(f, "cannot compile component: {:#}", error),
+            WasmError::Instantiate(ref error) => {
+                write!(f, "cannot instantiate component: {:#}", error)
+            }
+        }
+    }
+}
+
+impl error::Error for WasmError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            WasmError::Compile(ref error) | WasmError::Instantiate(ref error) => {
+                Some(error.as_ref())
+            }
+        }
+    }
+}
+
+impl WasmProvider {
+    /// The provider implemented by the component at `path`, in binary or text format.
+    pub fn load<P: AsRef<Path>>(path: P, limits: WasmLimits) -> Result<WasmProvider, WasmError> {
+        let engine = new_engine()?;
+        let component = Component::from_file(&engine, path).map_err(WasmError::Compile)?;
+        WasmProvider::instantiate(&engine, component, limits)
+    }
+
+    /// The provider implemented by `bytes`, e.g. a component included with `include_bytes!`.
+    pub fn from_bytes(bytes: &[u8], limits: WasmLimits) -> Result<WasmProvider, WasmError> {
+        let engine = new_engine()?;
+        let component = Component::new(&engine, bytes).map_err(WasmError::Compile)?;
+        WasmProvider::instantiate(&engine, component, limits)
+    }
+
+    fn instantiate(
+        engine: &Engine,
+        component: Component,
+        limits: WasmLimits,
+    ) -> Result<WasmProvider, WasmError> {
+        let mut linker = Linker::new(engine);
+        Extension::add_to_linker(&mut linker, |state: &mut HostState| state)
+            .map_err(WasmError::Instantiate)?;
+        let instance =
+            Instance::new(&component, &linker, &limits).map_err(WasmError::Instantiate)?;
+
+        Ok(WasmProvider {
+            inner: Arc::new(Inner {
+                component,
+                linker,
+                limits,
+                pool: Mutex::new(Pool {
+                    idle: vec![instance],
+                    instances: 1,
+                }),
+                returned: Condvar::new(),
+            }),
+        })
+    }
+
+    /// The result of `call` with `files` lent to the component, or `None` if it traps.
+    ///
+    /// The call takes an idle instance, or a new one if all are running calls, and returns it
+    /// when done. A component that traps may have been left in any state, so its instance is
+    /// dropped instead.
+    fn call<T, F>(&self, function: &str, files: &[FileInfo], call: F) -> Option<T>
+    where
+        F: FnOnce(
+            &provider::Guest,
+            &mut Store<HostState>,
+            Resource<HostFiles>,
+        ) -> wasmtime::Result<T>,
+    {
+        let mut instance = self.take_instance()?;
+        match instance.call(self.inner.limits.fuel, files, call) {
+            Ok(result) => {
+                self.put_back(Some(instance));
+                Some(result)
+            }
+            Err(error) => {
+                logging::warning(&format!("{}: {:#}", function, error));
+                self.put_back(None);
+                None
+            }
+        }
+    }
+
+    /// An idle instance, or a new one below `max_instances`. Otherwise waits for a call to
+    /// be done.
+    fn take_instance(&self) -> Option<Instance> {
+        let max_instances = self.inner.limits.max_instances.max(1);
+        let mut pool = self.inner.pool.lock().unwrap();
+        loop {
+            if let Some(instance) = pool.idle.pop() {
+                return Some(instance);
+            }
+            if pool.instances < max_instances {
+                break;
+            }
+            pool = self.inner.returned.wait(pool).unwrap();
+        }
+        pool.instances += 1;
+        drop(pool);
+
+        match Instance::new(
+            &self.inner.component,
+            &self.inner.linker,
+            &self.inner.limits,
+        ) {
+            Ok(instance) => Some(instance),
+            Err(error) => {
+                logging::warning(&format!("cannot instantiate component: {:#}", error));
+                self.put_back(None);
+                None
+            }
+        }
+    }
+
+    /// Return `instance` to the pool, or `None` for one that was dropped.
+    fn put_back(&self, instance: Option<Instance>) {
+        let mut pool = self.inner.pool.lock().unwrap();
+        match instance {
+            Some(instance) => pool.idle.push(instance),
+            None => pool.instances -= 1,
+        }
+        drop(pool);
+        self.inner.returned.notify_one();
+    }
+
+    fn file_items(&self, files: &[FileInfo]) -> Vec<MenuItem> {
+        let items = self.call("get-file-items", files, |guest, store, files| {
+            guest.call_get_file_items(store, files)
+        });
+        self.menu_items("get-file-items", items.unwrap_or_default())
+    }
+
+    fn background_items(&self, current_folder: &FileInfo) -> Vec<MenuItem> {
+        let items = self.call(
+            "get-background-items",
+            slice::from_ref(current_folder),
+            |guest, store, files| guest.call_get_background_items(store, files),
+        );
+        self.menu_items("get-background-items", items.unwrap_or_default())
+    }
+
+    /// The menu tree of a flattened list, or no items if the list is invalid. Without
+    /// `run_commands`, items with a `command` are dropped, and so are submenus left empty.
+    fn menu_items(&self, function: &str, items: Vec<provider::MenuItem>) -> Vec<MenuItem> {
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); items.len()];
+        let mut roots = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            match item.parent {
+                Some(parent) if (parent as usize) < index => children[parent as usize].push(index),
+                Some(parent) => {
+                    logging::warning(&format!(
+                        "{}: item {} has parent {}, which does not come before it",
+                        function, index, parent
+                    ));
+                    return Vec::new();
+                }
+                None => roots.push(index),
+            }
+        }
+
+        let tree = MenuTree {
+            provider: self,
+            function,
+            items: &items,
+            children: &children,
+        };
+        match tree.items(&roots) {
+            Ok(items) => items,
+            Err(message) => {
+                logging::warning(&format!("{}: {}", function, message));
+                Vec::new()
+            }
+        }
+    }
+
+    /// Call the component's `activate` for the item named `name`.
+    fn activate(&self, name: &str, files: &[FileInfo]) {
+        self.call("activate", files, |guest, store, files| {
+            guest.call_activate(store, name, files)
+        });
+    }
+}
+
+impl MenuProvider for WasmProvider {
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    fn get_file_items(&self, _window: *mut GtkWidget, files: &[FileInfo]) -> Vec<MenuItem> {
+        self.file_items(files)
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    fn get_file_items(
+        &self,
+        _window: Option<&crate::gtk::Window>,
+        files: &[FileInfo],
+    ) -> Vec<MenuItem> {
+        self.file_items(files)
+    }
+
+    #[cfg(nautilus_43)]
+    fn get_file_items(&self, files: &[FileInfo]) -> Vec<MenuItem> {
+        self.file_items(files)
+    }
+
+    #[cfg(all(not(nautilus_43), not(feature = "gtk-rs")))]
+    fn get_background_items(
+        &self,
+        _window: *mut GtkWidget,
+        current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        self.background_items(current_folder)
+    }
+
+    #[cfg(feature = "gtk-rs")]
+    fn get_background_items(
+        &self,
+        _window: Option<&crate::gtk::Window>,
+        current_folder: &FileInfo,
+    ) -> Vec<MenuItem> {
+        self.background_items(current_folder)
+    }
+
+    #[cfg(nautilus_43)]
+    fn get_background_items(&self, current_folder: &FileInfo) -> Vec<MenuItem> {
+        self.background_items(current_folder)
+    }
+}
+
+impl ColumnProvider for WasmProvider {
+    fn get_columns(&self) -> Vec<Column> {
+        let columns = self.call("get-columns", &[], |guest, store, _| {
+            guest.call_get_columns(store)
+        });
+        columns
+            .unwrap_or_default()
+            .into_iter()
+            .map(|column| {
+                Column::new(
+                    column.name,
+                    column.attribute,
+                    column.label,
+                    column.description,
+                )
+            })
+            .collect()
+    }
+}
+
+impl InfoProvider for WasmProvider {
+    fn should_update_file_info(&self, file_info: &FileInfo) -> bool {
+        self.call(
+            "should-update-file-info",
+            slice::from_ref(file_info),
+            |guest, store, files| guest.call_should_update_file_info(store, files),
+        )
+        .unwrap_or(false)
+    }
+
+    fn update_file_info(&self, file_info: &mut FileInfo) {
+        let attributes = self.call(
+            "update-file-info",
+            slice::from_ref(file_info),
+            |guest, store, files| guest.call_update_file_info(store, files),
+        );
+        for attribute in attributes.unwrap_or_default() {
+            file_info.add_attribute(&attribute.name, &attribute.value);
+        }
+    }
+}
+
+impl Instance {
+    fn new(
+        component: &Component,
+        linker: &Linker<HostState>,
+        limits: &WasmLimits,
+    ) -> wasmtime::Result<Instance> {
+        let state = HostState {
+            table: ResourceTable::new(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(limits.memory_size)
+                .table_elements(limits.table_elements)
+                .build(),
+        };
+        let mut store = Store::new(component.engine(), state);
+        store.limiter(|state| &mut state.limits);
+        // instantiating runs the component's start functions
+        store.set_fuel(limits.fuel)?;
+        let extension = Extension::instantiate(&mut store, component, linker)?;
+        Ok(Instance { store, extension })
+    }
+
+    fn call<T, F>(&mut self, fuel: u64, files: &[FileInfo], call: F) -> wasmtime::Result<T>
+    where
+        F: FnOnce(
+            &provider::Guest,
+            &mut Store<HostState>,
+            Resource<HostFiles>,
+        ) -> wasmtime::Result<T>,
+    {
+        let files = HostFiles {
+            files: files
+                .iter()
+                .map(|file| FileInfo::new(file.raw_file_info))
+                .collect(),
+        };
+        let files = self.store.data_mut().table.push(files)?;
+        let borrowed = Resource::new_borrow(files.rep());
+        self.store.set_fuel(fuel)?;
+        let result = call(
+            self.extension.nautilus_extension_provider(),
+            &mut self.store,
+            borrowed,
+        );
+        self.store.data_mut().table.delete(files)?;
+        result
+    }
+}
+
+impl Host for HostState {}
+
+impl FilesMethods for HostState {
+    fn len(&mut self, files: Resource<HostFiles>) -> wasmtime::Result<u32> {
+        Ok(self.table.get(&files)?.files.len() as u32)
+    }
+
+    fn uri(&mut self, files: Resource<HostFiles>, index: u32) -> wasmtime::Result<String> {
+        Ok(self.file(&files, index)?.get_uri())
+    }
+
+    fn scheme(&mut self, files: Resource<HostFiles>, index: u32) -> wasmtime::Result<String> {
+        Ok(self.file(&files, index)?.get_uri_scheme())
+    }
+
+    fn name(&mut self, files: Resource<HostFiles>, index: u32) -> wasmtime::Result<String> {
+        let name = self.file(&files, index)?.name().unwrap_or_default();
+        Ok(name.to_string_lossy().into_owned())
+    }
+
+    fn mime_type(&mut self, files: Resource<HostFiles>, index: u32) -> wasmtime::Result<String> {
+        Ok(self.file(&files, index)?.get_mime_type())
+    }
+
+    fn is_directory(&mut self, files: Resource<HostFiles>, index: u32) -> wasmtime::Result<bool> {
+        Ok(self.file(&files, index)?.is_directory())
+    }
+
+    fn can_write(&mut self, files: Resource<HostFiles>, index: u32) -> wasmtime::Result<bool> {
+        Ok(self.file(&files, index)?.can_write())
+    }
+
+    fn attribute(
+        &mut self,
+        files: Resource<HostFiles>,
+        index: u32,
+        name: String,
+    ) -> wasmtime::Result<Option<String>> {
+        Ok(self.file(&files, index)?.attributes.get(&name).cloned())
+    }
+
+    fn drop(&mut self, _files: Resource<HostFiles>) -> wasmtime::Result<()> {
+        // the files are only borrowed, and deleted by the host after each call
+        Ok(())
+    }
+}
+
+impl HostState {
+    fn file(&self, files: &Resource<HostFiles>, index: u32) -> wasmtime::Result<&FileInfo> {
+        let files = &self.table.get(files)?.files;
+        files.get(index as usize).ok_or_else(|| {
+            wasmtime::Error::msg(format!("no file at index {} of {}", index, files.len()))
+        })
+    }
+}
+
+fn new_engine() -> Result<Engine, WasmError> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    Engine::new(&config).map_err(WasmError::Compile)
+}
+
+struct MenuTree<'a> {
+    provider: &'a WasmProvider,
+    function: &'a str,
+    items: &'a [provider::MenuItem],
+    children: &'a [Vec<usize>],
+}
+
+impl<'a> MenuTree<'a> {
+    /// The menu items at `indices`, leaving out the dropped ones.
+    fn items(&self, indices: &[usize]) -> Result<Vec<MenuItem>, String> {
+        let mut menu_items = Vec::new();
+        for &index in indices {
+            if let Some(menu_item) = self.item(index)? {
+                menu_items.push(menu_item);
+            }
+        }
+        Ok(menu_items)
+    }
+
+    fn item(&self, index: usize) -> Result<Option<MenuItem>, String> {
+        let item = &self.items[index];
+        let children = &self.children[index];
+        let mut menu_item = MenuItem::new(
+            item.name.clone(),
+            item.label.clone(),
+            item.tip.clone(),
+            item.icon.clone(),
+        );
+        menu_item
+            .set_sensitive(item.sensitive)
+            .set_priority(item.priority);
+
+        match (&item.command, children.is_empty()) {
+            (Some(_), false) => {
+                return Err(format!(
+                    "`{}`: an item with a submenu has no `command`",
+                    item.name
+                ));
+            }
+            (Some(_), true) if !self.provider.inner.limits.run_commands => {
+                logging::warning(&format!(
+                    "{}: `{}` is dropped, as the component may not run commands",
+                    self.function, item.name
+                ));
+                return Ok(None);
+            }
+            (Some(exec), true) => {
+                let command = Command::new(exec.clone());
+                command
+                    .expand(&[])
+                    .map_err(|error| format!("`{}`: {}", item.name, error))?;
+                menu_item.set_command(command);
+            }
+            (None, false) => {
+                let submenu = self.items(children)?;
+                if submenu.is_empty() {
+                    return Ok(None);
+                }
+                menu_item.set_submenu(&Menu::new(&submenu));
+            }
+            (None, true) => {
+                let provider = self.provider.clone();
+                let name = item.name.clone();
+                menu_item.on_activate(move |files| provider.activate(&name, files));
+            }
+        }
+        Ok(Some(menu_item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{provider, WasmError, WasmLimits, WasmProvider};
+    use crate::column_provider::ColumnProvider;
+    use std::thread;
+
+    /// A component whose `get-columns` runs `columns`, then returns one column named `wasm`,
+    /// and whose `activate` runs `activate`. `get-file-items` returns one item named `wasm`,
+    /// without a command. The other functions return nothing.
+    fn component(columns: &str, activate: &str) -> String {
+        format!(
+            r#"
+(component
+  (import "nautilus:extension/files@0.1.0" (instance $files-instance
+    (export "files" (type (sub resource)))
+  ))
+  (alias export $files-instance "files" (type $files))
+
+  (core module $m
+    (import "host" "drop" (func $drop (param i32)))
+    (memory (export "memory") 1)
+    ;; strings passed to the component are copied to 256
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (i32.const 256))
+    (func $spin (param $n i32)
+      (loop $l
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (br_if $l (local.get $n))))
+    ;; the list at 0 has one column at 8, all of whose strings are `wasm`, at 64
+    (data (i32.const 0) "\08\00\00\00\01\00\00\00")
+    (data (i32.const 8) "\40\00\00\00\04\00\00\00\40\00\00\00\04\00\00\00")
+    (data (i32.const 24) "\40\00\00\00\04\00\00\00\40\00\00\00\04\00\00\00")
+    (data (i32.const 64) "wasm")
+    ;; the list at 96 has one sensitive menu item at 104, all of whose strings are `wasm`
+    (data (i32.const 96) "\68\00\00\00\01\00\00\00")
+    (data (i32.const 104) "\40\00\00\00\04\00\00\00\40\00\00\00\04\00\00\00")
+    (data (i32.const 120) "\40\00\00\00\04\00\00\00")
+    (data (i32.const 140) "\01")
+    ;; the empty list at 48
+    (func (export "get-items") (param i32) (result i32)
+      (call $drop (local.get 0))
+      (i32.const 48))
+    (func (export "get-file-items") (param i32) (result i32)
+      (call $drop (local.get 0))
+      (i32.const 96))
+    (func (export "get-columns") (result i32)
+      {}
+      (i32.const 0))
+    (func (export "activate") (param i32 i32 i32)
+      (call $drop (local.get 2))
+      {})
+    (func (export "should-update-file-info") (param i32) (result i32)
+      (call $drop (local.get 0))
+      (i32.const 0))
+  )
+  (core func $drop (canon resource.drop $files))
+  (core instance $host (export "drop" (func $drop)))
+  (core instance $i (instantiate $m (with "host" (instance $host))))
+
+  (type $menu-item-record (record
+    (field "name" string)
+    (field "label" string)
+    (field "tip" string)
+    (field "icon" (option string))
+    (field "sensitive" bool)
+    (field "priority" bool)
+    (field "command" (option string))
+    (field "parent" (option u32))
+  ))
+  (export $menu-item "menu-item" (type $menu-item-record))
+  (type $column-record (record
+    (field "name" string)
+    (field "attribute" string)
+    (field "label" string)
+    (field "description" string)
+  ))
+  (export $column "column" (type $column-record))
+  (type $attribute-record (record (field "name" string) (field "value" string)))
+  (export $attribute "attribute" (type $attribute-record))
+
+  (func $get-file-items (param "files" (borrow $files)) (result (list $menu-item))
+    (canon lift (core func $i "get-file-items") (memory $i "memory")))
+  (func $get-background-items (param "current-folder" (borrow $files)) (result (list $menu-item))
+    (canon lift (core func $i "get-items") (memory $i "memory")))
+  (func $get-columns (result (list $column))
+    (canon lift (core func $i "get-columns") (memory $i "memory")))
+  (func $should-update-file-info (param "file" (borrow $files)) (result bool)
+    (canon lift (core func $i "should-update-file-info")))
+  (func $update-file-info (param "file" (borrow $files)) (result (list $attribute))
+    (canon lift (core func $i "get-items") (memory $i "memory")))
+  (func $activate (param "item-name" string) (param "files" (borrow $files))
+    (canon lift (core func $i "activate") (memory $i "memory") (realloc (func $i "realloc"))))
+  (instance $provider
+    (export "get-file-items" (func $get-file-items))
+    (export "get-background-items" (func $get-background-items))
+    (export "get-columns" (func $get-columns))
+    (export "should-update-file-info" (func $should-update-file-info))
+    (export "update-file-info" (func $update-file-info))
+    (export "activate" (func $activate))
+  )
+  (export "nautilus:extension/provider@0.1.0" (instance $provider))
+)
+"#,
+            columns, activate
+        )
+    }
+
+    fn idle_instances(provider: &WasmProvider) -> usize {
+        provider.inner.pool.lock().unwrap().idle.len()
+    }
+
+    fn with_commands(run_commands: bool) -> WasmProvider {
+        let limits = WasmLimits {
+            run_commands,
+            ..WasmLimits::default()
+        };
+        WasmProvider::from_bytes(component("", "").as_bytes(), limits).unwrap()
+    }
+
+    fn item(name: &str, command: Option<&str>, parent: Option<u32>) -> provider::MenuItem {
+        provider::MenuItem {
+            name: name.to_string(),
+            label: name.to_string(),
+            tip: String::new(),
+            icon: None,
+            sensitive: true,
+            priority: false,
+            command: command.map(str::to_string),
+            parent,
+        }
+    }
+
+    #[test]
+    fn rejects_components_without_the_extension_world() {
+        match WasmProvider::from_bytes(b"not wasm", WasmLimits::default()) {
+            Err(WasmError::Compile(_)) => {}
+            _ => panic!("expected a compile error"),
+        }
+        match WasmProvider::from_bytes(b"(component)", WasmLimits::default()) {
+            Err(WasmError::Instantiate(error)) => {
+                assert!(format!("{:#}", error).contains("nautilus:extension/provider"));
+            }
+            _ => panic!("expected an instantiate error"),
+        }
+    }
+
+    #[test]
+    fn calls_components() {
+        let provider =
+            WasmProvider::from_bytes(component("", "").as_bytes(), WasmLimits::default()).unwrap();
+        for _ in 0..2 {
+            let columns = provider.get_columns();
+            assert_eq!(columns.len(), 1);
+            assert_eq!(columns[0].name, "wasm");
+            assert_eq!(columns[0].description, "wasm");
+            let items = provider.file_items(&[]);
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].name(), "wasm");
+            assert_eq!(idle_instances(&provider), 1);
+        }
+    }
+
+    #[test]
+    fn stops_calls_out_of_fuel() {
+        let limits = WasmLimits {
+            fuel: 10_000,
+            ..WasmLimits::default()
+        };
+        let provider =
+            WasmProvider::from_bytes(component("(loop $l (br $l))", "").as_bytes(), limits)
+                .unwrap();
+        assert!(provider.get_columns().is_empty());
+        // the trapped instance is dropped, and the next call gets a new one
+        assert_eq!(idle_instances(&provider), 0);
+        assert_eq!(provider.file_items(&[]).len(), 1);
+        assert_eq!(idle_instances(&provider), 1);
+    }
+
+    #[test]
+    fn limits_instances() {
+        let limits = WasmLimits {
+            max_instances: 2,
+            ..WasmLimits::default()
+        };
+        let component = component("(call $spin (i32.const 100000))", "");
+        let provider = WasmProvider::from_bytes(component.as_bytes(), limits).unwrap();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let provider = provider.clone();
+                thread::spawn(move || {
+                    for _ in 0..5 {
+                        assert_eq!(provider.get_columns().len(), 1);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        // calls beyond the limit waited for an instance instead of making one
+        let pool = provider.inner.pool.lock().unwrap();
+        assert!(pool.instances <= 2);
+        assert_eq!(pool.idle.len(), pool.instances);
+    }
+
+    #[test]
+    fn activates_items_in_the_component() {
+        // `activate` traps unless it gets the name of the item
+        let check_name = "(if (i32.ne (local.get 1) (i32.const 4)) (then unreachable))";
+        let provider =
+            WasmProvider::from_bytes(component("", check_name).as_bytes(), WasmLimits::default())
+                .unwrap();
+        provider.activate("wasm", &[]);
+        assert_eq!(idle_instances(&provider), 1);
+        provider.activate("photos", &[]);
+        assert_eq!(idle_instances(&provider), 0);
+    }
+
+    #[test]
+    fn limits_memory() {
+        let grow = "(if (i32.eq (memory.grow (i32.const 16)) (i32.const -1)) (then unreachable))";
+        let provider =
+            WasmProvider::from_bytes(component(grow, "").as_bytes(), WasmLimits::default())
+                .unwrap();
+        assert_eq!(provider.get_columns().len(), 1);
+
+        let limits = WasmLimits {
+            memory_size: 2 << 16,
+            ..WasmLimits::default()
+        };
+        let provider = WasmProvider::from_bytes(component(grow, "").as_bytes(), limits).unwrap();
+        assert!(provider.get_columns().is_empty());
+
+        let limits = WasmLimits {
+            memory_size: 1 << 15,
+            ..WasmLimits::default()
+        };
+        match WasmProvider::from_bytes(component("", "").as_bytes(), limits) {
+            Err(WasmError::Instantiate(_)) => {}
+            _ => panic!("expected an instantiate error"),
+        }
+    }
+
+    #[test]
+    fn builds_submenus() {
+        let items = vec![
+            item("Photos::tools", None, None),
+            item("Photos::rotate", Some("jpegtran -rotate 90 %F"), Some(0)),
+            item("Photos::share", None, Some(0)),
+            item("Photos::mail", Some("thunderbird %U"), Some(2)),
+            item("Photos::print", Some("lp %F"), None),
+        ];
+        let menu = with_commands(true).menu_items("get-file-items", items);
+        assert_eq!(menu.len(), 2);
+        assert_eq!(menu[0].name(), "Photos::tools");
+        let tools = menu[0].submenu().unwrap().items();
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0].command().unwrap().exec(), "jpegtran -rotate 90 %F");
+        let share = tools[1].submenu().unwrap().items();
+        assert_eq!(share[0].name(), "Photos::mail");
+        assert_eq!(menu[1].name(), "Photos::print");
+    }
+
+    #[test]
+    fn rejects_invalid_menus() {
+        let provider = with_commands(true);
+        // a parent must come before its items
+        let items = vec![
+            item("Photos::rotate", Some("true"), Some(1)),
+            item("Photos::tools", None, None),
+        ];
+        assert!(provider.menu_items("get-file-items", items).is_empty());
+        let items = vec![item("Photos::rotate", Some("true"), Some(0))];
+        assert!(provider.menu_items("get-file-items", items).is_empty());
+        // an item has a command or a submenu, not both
+        let items = vec![
+            item("Photos::tools", Some("true"), None),
+            item("Photos::rotate", Some("true"), Some(0)),
+        ];
+        assert!(provider.menu_items("get-file-items", items).is_empty());
+        let items = vec![item("Photos::rotate", Some("rotate %F%U"), None)];
+        assert!(provider.menu_items("get-file-items", items).is_empty());
+    }
+
+    #[test]
+    fn drops_commands_without_permission() {
+        let items = vec![
+            item("Photos::tools", None, None),
+            item("Photos::rotate", Some("jpegtran -rotate 90 %F"), Some(0)),
+            item("Photos::share", None, None),
+            item("Photos::mail", Some("thunderbird %U"), Some(2)),
+            item("Photos::about", None, Some(2)),
+            item("Photos::version", Some("true"), Some(4)),
+            item("Photos::open", None, None),
+        ];
+        // only the item activated through the component is left
+        let menu = with_commands(false).menu_items("get-file-items", items);
+        assert_eq!(menu.len(), 1);
+        assert_eq!(menu[0].name(), "Photos::open");
+        assert!(!WasmLimits::default().run_commands);
+    }
+}
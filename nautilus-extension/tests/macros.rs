@@ -0,0 +1,103 @@
+//! The `macros` feature's attribute and derive macros, expanded in a real crate and driven
+//! through the mock.
+#![cfg(all(feature = "macros", feature = "mock"))]
+
+extern crate nautilus_extension as nautilus;
+
+use nautilus_extension::mock::{self, MockFile};
+use nautilus_extension::{lazy_static, GType, GTypeModule};
+use nautilus_extension::{
+    menu_background_activate, menu_item_activate, nautilus_extension, ColumnProvider, FileInfo,
+};
+use std::os::raw::c_int;
+use std::ptr;
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref ACTIVATED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+}
+
+#[derive(ColumnProvider)]
+#[column(name = "Tags::tags", attribute = "tags", label = "Tags")]
+#[column(
+    name = "Tags::count",
+    attribute = "tag_count",
+    label = "Tag count",
+    description = "Number of tags"
+)]
+struct TagColumns;
+
+#[derive(ColumnProvider)]
+#[column_provider(crate = "nautilus")]
+#[column(name = "Renamed::column", attribute = "renamed", label = "Renamed")]
+struct RenamedColumns;
+
+#[menu_item_activate]
+fn open_files(files: Vec<FileInfo>) {
+    let mut activated = ACTIVATED.lock().unwrap();
+    activated.extend(files.iter().map(FileInfo::get_uri));
+}
+
+#[menu_background_activate(crate = "nautilus")]
+fn open_folder(current_folder: FileInfo) {
+    ACTIVATED.lock().unwrap().push(current_folder.get_uri());
+}
+
+#[nautilus_extension]
+fn disabled_extension(_module: *mut GTypeModule) -> Option<GType> {
+    None
+}
+
+#[test]
+fn derives_column_providers() {
+    let columns = mock::get_columns(&TagColumns);
+    assert_eq!(columns.len(), 2);
+    assert_eq!(columns[0].name, "Tags::tags");
+    assert_eq!(columns[0].attribute, "tags");
+    assert_eq!(columns[0].label, "Tags");
+    assert_eq!(columns[0].description, "");
+    assert_eq!(columns[1].name, "Tags::count");
+    assert_eq!(columns[1].description, "Number of tags");
+
+    let columns = mock::get_columns(&RenamedColumns);
+    assert_eq!(columns.len(), 1);
+    assert_eq!(columns[0].label, "Renamed");
+}
+
+#[test]
+fn activate_handlers_receive_files() {
+    let files = [
+        MockFile::new("file:///tmp/a.txt"),
+        MockFile::new("file:///tmp/b.txt"),
+    ];
+    let folder = MockFile::new("file:///tmp");
+
+    // Nautilus passes the user data the menu glue boxed when connecting the signal
+    unsafe {
+        let file_infos: Vec<FileInfo> = files.iter().map(MockFile::file_info).collect();
+        open_files(ptr::null_mut(), Box::into_raw(Box::new(file_infos)) as _);
+        open_folder(
+            ptr::null_mut(),
+            Box::into_raw(Box::new(folder.file_info())) as _,
+        );
+    }
+
+    assert_eq!(
+        *ACTIVATED.lock().unwrap(),
+        ["file:///tmp/a.txt", "file:///tmp/b.txt", "file:///tmp"]
+    );
+}
+
+#[test]
+fn generates_entry_points() {
+    let mut types: *const GType = ptr::null();
+    let mut num_types: c_int = -1;
+
+    nautilus_module_initialize(ptr::null_mut());
+    unsafe {
+        nautilus_module_list_types(&mut types, &mut num_types);
+    }
+    assert_eq!(num_types, 0);
+
+    nautilus_module_shutdown();
+}
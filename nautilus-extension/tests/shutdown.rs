@@ -0,0 +1,154 @@
+//! `NautilusModule::shutdown_within()` against the generated provider glue.
+//!
+//! Providers live in process-wide slots, so these tests have a binary of their own and take
+//! turns through `SERIAL`.
+#![cfg(feature = "mock")]
+
+use gobject_sys::{g_cclosure_new, g_closure_unref, g_object_new, G_TYPE_OBJECT};
+use nautilus_extension::column_provider::rust_column_provider_setters;
+use nautilus_extension::info_provider::{
+    info_provider_cancel_update_0, info_provider_update_file_info_0, rust_info_provider_setters,
+};
+use nautilus_extension::mock::MockFile;
+use nautilus_extension::{lazy_static, Column, ColumnProvider, FileInfo, InfoProvider};
+use nautilus_extension::{NautilusModule, DEFAULT_SHUTDOWN_TIMEOUT};
+use nautilus_extension_sys::{NautilusInfoProvider, NautilusOperationResult};
+use std::ptr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+lazy_static! {
+    static ref SERIAL: Mutex<()> = Mutex::new(());
+}
+
+type Events = Arc<Mutex<Vec<&'static str>>>;
+
+/// An info provider whose updates announce themselves, then block until released.
+struct BlockingInfoProvider {
+    events: Events,
+    started: Mutex<Sender<()>>,
+    release: Mutex<Receiver<()>>,
+}
+
+impl InfoProvider for BlockingInfoProvider {
+    fn should_update_file_info(&self, _file_info: &FileInfo) -> bool {
+        true
+    }
+
+    fn update_file_info(&self, _file_info: &mut FileInfo) {
+        self.started.lock().unwrap().send(()).unwrap();
+        self.release.lock().unwrap().recv().unwrap();
+        self.events.lock().unwrap().push("updated");
+    }
+
+    fn shutdown(&self) {
+        self.events.lock().unwrap().push("info shutdown");
+    }
+}
+
+struct RecordingColumnProvider {
+    events: Events,
+}
+
+impl ColumnProvider for RecordingColumnProvider {
+    fn get_columns(&self) -> Vec<Column> {
+        Vec::new()
+    }
+
+    fn shutdown(&self) {
+        self.events.lock().unwrap().push("column shutdown");
+    }
+}
+
+struct Providers {
+    events: Events,
+    started: Receiver<()>,
+    release: Sender<()>,
+}
+
+/// Put the providers in the first slots.
+fn set_providers() -> Providers {
+    let events = Events::default();
+    let (started_sender, started) = channel();
+    let (release, release_receiver) = channel();
+
+    rust_info_provider_setters()[0](Box::new(BlockingInfoProvider {
+        events: events.clone(),
+        started: Mutex::new(started_sender),
+        release: Mutex::new(release_receiver),
+    }));
+    rust_column_provider_setters()[0](Box::new(RecordingColumnProvider {
+        events: events.clone(),
+    }));
+
+    Providers {
+        events,
+        started,
+        release,
+    }
+}
+
+unsafe extern "C" fn ignore_update_complete() {}
+
+/// Start a background update of `file`, cancelled so it does not report back to Nautilus, and
+/// wait until it has reached the provider.
+fn start_update(providers: &Providers, file: &MockFile) {
+    unsafe {
+        let provider = g_object_new(G_TYPE_OBJECT, ptr::null()) as *mut NautilusInfoProvider;
+        let update_complete = g_cclosure_new(Some(ignore_update_complete), ptr::null_mut(), None);
+        let mut handle = ptr::null_mut();
+
+        let result = info_provider_update_file_info_0(
+            provider,
+            file.file_info().raw_file_info,
+            update_complete,
+            &mut handle,
+        );
+        assert_eq!(result, NautilusOperationResult::NautilusOperationInProgress);
+
+        info_provider_cancel_update_0(provider, handle);
+        g_closure_unref(update_complete);
+    }
+    providers.started.recv().unwrap();
+}
+
+#[test]
+fn shuts_down_after_pending_updates() {
+    let _serial = SERIAL.lock().unwrap_or_else(|error| error.into_inner());
+    let providers = set_providers();
+    let file = MockFile::new("file:///tmp/photo.jpg");
+
+    start_update(&providers, &file);
+    let shutdown = thread::spawn(|| NautilusModule::shutdown_within(DEFAULT_SHUTDOWN_TIMEOUT));
+
+    // give a shutdown that does not wait the chance to run the hooks first
+    thread::sleep(Duration::from_millis(50));
+    providers.release.send(()).unwrap();
+    shutdown.join().unwrap();
+
+    assert_eq!(
+        *providers.events.lock().unwrap(),
+        ["updated", "column shutdown", "info shutdown"]
+    );
+}
+
+#[test]
+fn skips_providers_still_updating_after_timeout() {
+    let _serial = SERIAL.lock().unwrap_or_else(|error| error.into_inner());
+    let providers = set_providers();
+    let file = MockFile::new("file:///tmp/photo.jpg");
+
+    start_update(&providers, &file);
+    NautilusModule::shutdown_within(Duration::from_millis(10));
+    assert_eq!(*providers.events.lock().unwrap(), ["column shutdown"]);
+
+    // the skipped provider is shut down once its update has finished
+    providers.release.send(()).unwrap();
+    NautilusModule::shutdown_within(DEFAULT_SHUTDOWN_TIMEOUT);
+    assert_eq!(
+        *providers.events.lock().unwrap(),
+        ["column shutdown", "updated", "info shutdown"]
+    );
+}